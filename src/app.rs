@@ -1,9 +1,30 @@
+use crate::config::options::{self, LocalOptions, OptionDef, OptionScope, OptionValue};
+use crate::config::settings::EditorConfig;
 use crate::config::Config;
-use crate::editor::{Buffer, Clipboard, Cursor, Mode, Selection};
+use crate::editor::comment::{self, CommentTokens};
+use crate::editor::fold::{self, FoldSet};
+use crate::editor::format::{self, FormatOptions, DEFAULT_FORMAT_WIDTH};
+use crate::editor::increment;
+use crate::editor::indent::{self, IndentRules};
+use crate::editor::lines;
+use crate::editor::search;
+use crate::editor::substitute::Substitution;
+use crate::editor::{
+    AutoPairs, BracketMatcher, Buffer, CaseOp, Clipboard, Cursor, MatchPairs, Mode, SearchMatch,
+    SearchPattern, Selection, WrapOptions,
+};
 use crate::file::watcher::{FileEvent, FileWatcher};
-use crate::syntax::SyntaxHighlighter;
-use crate::ui::components::FileExplorer;
+use crate::file::{GrepSearch, History, HistoryKind, ViewState, ViewStore};
+use crate::input::completion::{self, CompletionSource};
+use crate::input::range::RangeContext;
+use crate::input::CommandLine;
+use crate::syntax::{self, LineState, SyntaxHighlighter};
+use crate::ui::components::{FileExplorer, FuzzyFinder, OpenTarget, QuickfixList, Wildmenu};
+use crate::utils::shell;
+use crate::utils::TextUtils;
 use crate::Result;
+use ropey::Rope;
+use std::cell::RefCell;
 use std::path::PathBuf;
 use std::time::Instant;
 
@@ -16,10 +37,14 @@ pub struct App {
     pub mode: Mode,
     pub config: Config,
     pub status_message: Option<String>,
-    pub command_line: String,
+    pub command_line: CommandLine,
+    /// `:` and search history, saved across sessions.
+    pub history: History,
     pub file_explorer: FileExplorer,
     pub syntax_highlighter: SyntaxHighlighter,
     pub search_state: SearchState,
+    /// The `/` or `?` prompt being typed, if any.
+    pub search_prompt: Option<SearchPrompt>,
     pub error_message: Option<String>,
     pub help_window: HelpWindow,
     pub file_change_dialog: FileChangeDialog,
@@ -27,6 +52,43 @@ pub struct App {
     pub cursor_blink_state: bool,
     pub last_cursor_blink: Instant,
     pub horizontal_scroll_offset: usize,
+    pub view_store: ViewStore,
+    /// First and last line of the most recent visual selection (`'<` and `'>`).
+    pub visual_marks: Option<(usize, usize)>,
+    /// Window-local option values set with `:setlocal`.
+    pub window_options: LocalOptions,
+    /// The `:s///c` waiting for a y/n/a/q/l answer, if any.
+    pub substitute_confirm: Option<SubstituteConfirm>,
+    pub quickfix: QuickfixList,
+    /// The `:grep` still filling the quickfix list, if any.
+    pub grep_search: Option<GrepSearch>,
+    pub fuzzy_finder: FuzzyFinder,
+    /// Completion candidates shown while Tab cycles on the command line.
+    pub wildmenu: Option<Wildmenu>,
+    /// The command-line window, while it is open.
+    pub command_window: Option<CommandWindow>,
+    /// The last `:!` command, repeated by `:!!`.
+    pub last_shell_command: Option<String>,
+    /// The `:q` or `:w` waiting for an answer because `confirm` is set.
+    pub write_confirm: Option<WriteConfirm>,
+    /// The other window of the current tab page, when it is split.
+    pub split: Option<Split>,
+    /// Tab pages other than the current one, in order.
+    pub tabs: Vec<TabPage>,
+    /// Position of the current tab page among all of them.
+    pub tab_index: usize,
+    /// Syntax state at the start of the line last checked for comments.
+    comment_state: RefCell<Option<CommentStateCache>>,
+}
+
+/// The syntax state at the start of `line`, reused while the text above it,
+/// `above`, is unchanged.
+struct CommentStateCache {
+    buffer: usize,
+    syntax: String,
+    line: usize,
+    above: Rope,
+    state: LineState,
 }
 
 #[derive(Debug, Clone)]
@@ -46,21 +108,105 @@ pub struct FileChangeDialog {
 #[derive(Debug, Clone)]
 pub struct SearchState {
     pub query: String,
-    pub results: Vec<SearchResult>,
+    pub pattern: Option<SearchPattern>,
+    pub results: Vec<SearchMatch>,
     pub current_result: usize,
     pub is_active: bool,
+    /// Whether the last search went upwards (`?`, `#`), which `n` repeats.
+    pub backward: bool,
+}
+
+/// State kept while typing a `/` or `?` search so Esc can undo it.
+#[derive(Debug, Clone)]
+pub struct SearchPrompt {
+    pub backward: bool,
+    pub origin: Cursor,
+    pub previous: SearchState,
+}
+
+/// The `q:` / `q/` window listing the history as an editable buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct CommandWindow {
+    pub kind: HistoryKind,
+    /// The buffer and cursor to go back to when the window closes.
+    pub return_buffer: usize,
+    pub return_cursor: Cursor,
+}
+
+/// A window that is not the current one: the buffer it shows and its view.
+/// The current window's state lives in `App` itself.
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub buffer: usize,
+    pub cursor: Cursor,
+    pub selection: Selection,
+    pub horizontal_scroll_offset: usize,
+    pub options: LocalOptions,
+}
+
+/// The window next to the current one in a split tab page.
+#[derive(Debug, Clone)]
+pub struct Split {
+    pub window: Window,
+    /// Side by side (`:vsplit`) rather than one above the other.
+    pub vertical: bool,
+    /// Whether the current window is the top (or left) one.
+    pub current_first: bool,
+}
+
+/// A tab page other than the current one.
+#[derive(Debug, Clone)]
+pub struct TabPage {
+    /// Its current window.
+    pub window: Window,
+    pub split: Option<Split>,
+}
+
+/// What a `:w`-family command writes, kept so it can be done again once an
+/// overwrite is confirmed.
+#[derive(Debug, Clone, Default)]
+pub struct FileWrite {
+    /// The file name as typed; the buffer's own file without one.
+    pub file: Option<String>,
+    /// First and last line to write; the whole buffer without one.
+    pub lines: Option<(usize, usize)>,
+    /// `:w >> file`: add the lines to the end of the file.
+    pub append: bool,
+    /// `:saveas`: the buffer takes the new file name.
+    pub rename: bool,
 }
 
+/// A question asked before quitting or writing when `confirm` is set.
 #[derive(Debug, Clone)]
-pub struct SearchResult {
-    pub line: usize,
-    pub col: usize,
-    pub match_length: usize,
+pub enum WriteConfirm {
+    /// Quitting with unsaved changes: each of these buffers is asked about
+    /// in turn.
+    Quit { buffers: Vec<usize> },
+    /// Writing over an existing file, then quitting if `quit` is set.
+    Overwrite { write: FileWrite, quit: bool },
+}
+
+/// An interactive `:s///c` in progress.
+#[derive(Debug, Clone)]
+pub struct SubstituteConfirm {
+    pub substitution: Substitution,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// The text of the range, kept in step with the buffer as matches are
+    /// replaced.
+    pub text: String,
+    /// Byte range in `text` of the match being asked about.
+    pub current: (usize, usize),
+    pub count: usize,
 }
 
 impl App {
     pub fn new() -> Result<Self> {
-        let config = Config::load()?;
+        Self::with_config(Config::load()?)
+    }
+
+    /// The editor state for an already loaded `config`.
+    pub fn with_config(config: Config) -> Result<Self> {
         let file_explorer = FileExplorer::new(".")?;
         let syntax_highlighter = SyntaxHighlighter::new();
 
@@ -73,10 +219,12 @@ impl App {
             mode: Mode::Normal,
             config,
             status_message: None,
-            command_line: String::new(),
+            command_line: CommandLine::default(),
+            history: History::load(),
             file_explorer,
             syntax_highlighter,
             search_state: SearchState::new(),
+            search_prompt: None,
             error_message: None,
             help_window: HelpWindow::new(),
             file_change_dialog: FileChangeDialog::new(),
@@ -84,6 +232,21 @@ impl App {
             cursor_blink_state: true,
             last_cursor_blink: Instant::now(),
             horizontal_scroll_offset: 0,
+            view_store: ViewStore::load(),
+            visual_marks: None,
+            window_options: LocalOptions::default(),
+            substitute_confirm: None,
+            quickfix: QuickfixList::default(),
+            grep_search: None,
+            fuzzy_finder: FuzzyFinder::default(),
+            wildmenu: None,
+            command_window: None,
+            last_shell_command: None,
+            write_confirm: None,
+            split: None,
+            tabs: Vec::new(),
+            tab_index: 0,
+            comment_state: RefCell::new(None),
         })
     }
 
@@ -94,32 +257,45 @@ impl App {
                 log::warn!("Failed to watch file {path:?}: {e}");
             }
         }
+        self.save_view_state();
         self.cursor.col = 0;
         self.cursor.line = 0;
         self.buffers.push(buffer);
         self.current_buffer = self.buffers.len() - 1;
+        self.apply_filetype_options();
+        self.restore_view_state();
         Ok(())
     }
 
-    pub fn open_or_create_file(&mut self, filename: &str) -> Result<()> {
-        let path = if std::path::Path::new(filename).is_absolute() {
+    /// `filename` relative to the current directory, unless it is absolute.
+    fn resolve_path(&self, filename: &str) -> PathBuf {
+        if std::path::Path::new(filename).is_absolute() {
             PathBuf::from(filename)
         } else {
             self.file_explorer.get_current_path().join(filename)
-        };
+        }
+    }
+
+    pub fn open_or_create_file(&mut self, filename: &str) -> Result<()> {
+        let path = self.resolve_path(filename);
 
         if path.exists() {
             let buffer = Buffer::from_file(&path)?;
             if let Err(e) = self.file_watcher.watch(&path) {
                 log::warn!("Failed to watch file {path:?}: {e}");
             }
+            self.save_view_state();
             self.buffers.push(buffer);
             self.current_buffer = self.buffers.len() - 1;
+            self.apply_filetype_options();
+            self.restore_view_state();
             Ok(())
         } else {
             let buffer = Buffer::new_file(&path);
+            self.save_view_state();
             self.buffers.push(buffer);
             self.current_buffer = self.buffers.len() - 1;
+            self.apply_filetype_options();
             Ok(())
         }
     }
@@ -215,6 +391,842 @@ impl App {
         }
     }
 
+    pub fn indent_rules(&self) -> Option<IndentRules> {
+        let path = self.current_buffer().file_path()?;
+        let syntax = self.syntax_highlighter.detect_language(Some(path))?;
+        IndentRules::for_syntax(&syntax.name)
+    }
+
+    /// Returns the auto-pair set for the current buffer, or `None` when
+    /// auto-pairing is disabled.
+    pub fn auto_pairs(&self) -> Option<AutoPairs> {
+        let editor = self.editor_config();
+        editor.auto_pairs.then(|| AutoPairs::parse(&editor.pairs))
+    }
+
+    /// Builds a matcher for the current buffer that skips strings and comments.
+    pub fn bracket_matcher(&self) -> BracketMatcher<'_> {
+        let buffer = self.current_buffer();
+        let syntax = buffer
+            .file_path()
+            .and_then(|path| self.syntax_highlighter.detect_language(Some(path)))
+            .map(|syntax| (&self.syntax_highlighter, syntax));
+        let match_pairs = MatchPairs::parse(&self.editor_config().matchpairs).unwrap_or_default();
+        BracketMatcher::new(buffer, syntax, match_pairs)
+    }
+
+    /// `'{a-z}`: moves to the first non-blank character of the marked line.
+    pub fn jump_to_mark(&mut self, name: char) {
+        let buffer = self.current_buffer();
+        let Some(line) = Buffer::mark_slot(name)
+            .and_then(|slot| buffer.marks[slot])
+            .filter(|&line| line < buffer.line_count())
+        else {
+            self.set_error_message(format!("Mark not set: '{name}"));
+            return;
+        };
+        let text = buffer.line(line).unwrap_or_default();
+        self.cursor.line = line;
+        self.cursor.col = indent::leading_indent(&text).chars().count();
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    pub fn jump_to_match(&mut self) {
+        match self
+            .bracket_matcher()
+            .jump_target(self.cursor.line, self.cursor.col)
+        {
+            Some((line, col)) => {
+                self.cursor.line = line;
+                self.cursor.col = col;
+                self.cursor.desired_col = col;
+            }
+            None => self.set_status_message("No match found".to_string()),
+        }
+    }
+
+    fn is_comment_at_cursor(&self) -> bool {
+        let Some(path) = self.current_buffer().file_path() else {
+            return false;
+        };
+        let Some(syntax) = self.syntax_highlighter.detect_language(Some(path)) else {
+            return false;
+        };
+        let buffer = self.current_buffer();
+        let first = self.cursor.line.saturating_sub(syntax::CONTEXT_LINES);
+        let above = buffer.content.slice(
+            buffer.content.line_to_char(first)..buffer.content.line_to_char(self.cursor.line),
+        );
+
+        let mut cache = self.comment_state.borrow_mut();
+        let fresh = cache.as_ref().is_some_and(|cache| {
+            cache.buffer == self.current_buffer
+                && cache.syntax == syntax.name
+                && cache.line == self.cursor.line
+                && cache.above.slice(..) == above
+        });
+        if !fresh {
+            *cache = Some(CommentStateCache {
+                buffer: self.current_buffer,
+                syntax: syntax.name.clone(),
+                line: self.cursor.line,
+                above: Rope::from(above),
+                state: self.syntax_highlighter.state_after(
+                    syntax,
+                    (first..self.cursor.line).map(|idx| buffer.line(idx).unwrap_or_default()),
+                ),
+            });
+        }
+        let Some(cache) = cache.as_ref() else {
+            return false;
+        };
+
+        let line = buffer.line(self.cursor.line).unwrap_or_default();
+        let byte_pos = TextUtils::char_to_byte(&line, self.cursor.col);
+        self.syntax_highlighter
+            .is_comment_at(&cache.state, &line, byte_pos.saturating_sub(1))
+    }
+
+    /// Replaces the word just typed before the cursor with its expansion
+    /// from `[abbreviations]`, if it has one. Called when a non-keyword
+    /// character is typed and when insert mode ends.
+    pub fn expand_abbreviation(&mut self) -> bool {
+        if self.config.abbreviations.is_empty() {
+            return false;
+        }
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let before: Vec<char> = line.chars().take(self.cursor.col).collect();
+        let word_len = before
+            .iter()
+            .rev()
+            .take_while(|&&ch| ch.is_alphanumeric() || ch == '_')
+            .count();
+        let start = before.len() - word_len;
+        let word: String = before[start..].iter().collect();
+        let Some(expansion) = self.config.abbreviations.get(&word).cloned() else {
+            return false;
+        };
+
+        let col = self.cursor.col;
+        let buffer = self.current_buffer_mut();
+        buffer.delete_range(line_idx, start, line_idx, col);
+        buffer.insert_str(line_idx, start, &expansion);
+        match expansion.rsplit_once('\n') {
+            Some((head, last)) => {
+                self.cursor.line += head.matches('\n').count() + 1;
+                self.cursor.col = last.chars().count();
+            }
+            None => self.cursor.col = start + expansion.chars().count(),
+        }
+        self.cursor.desired_col = self.cursor.col;
+        true
+    }
+
+    /// Inserts a character typed in insert mode, handling auto-pairs and
+    /// closing-bracket dedent.
+    pub fn insert_typed_char(&mut self, ch: char) {
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let chars: Vec<char> = line.chars().collect();
+        let before = self
+            .cursor
+            .col
+            .checked_sub(1)
+            .and_then(|i| chars.get(i).copied());
+        let after = chars.get(self.cursor.col).copied();
+
+        if let Some(pairs) = self.auto_pairs() {
+            if pairs.is_closer(ch) && after == Some(ch) {
+                self.cursor.col += 1;
+                self.cursor.desired_col = self.cursor.col;
+                return;
+            }
+
+            if pairs.should_pair(ch, before, after)
+                && !(pairs.is_quote(ch) && self.is_comment_at_cursor())
+            {
+                if let Some(closer) = pairs.closer_for(ch) {
+                    let col = self.cursor.col;
+                    self.current_buffer_mut()
+                        .insert_str(line_idx, col, &format!("{ch}{closer}"));
+                    self.cursor.col += 1;
+                    self.cursor.desired_col = self.cursor.col;
+                    return;
+                }
+            }
+        }
+
+        self.apply_closing_indent(ch);
+        let col = self.cursor.col;
+        self.current_buffer_mut().insert_char(line_idx, col, ch);
+        self.cursor.col += 1;
+        self.cursor.desired_col = self.cursor.col;
+
+        if !ch.is_whitespace() {
+            self.auto_wrap();
+        }
+    }
+
+    /// Breaks the cursor line once it grows past `textwidth`.
+    fn auto_wrap(&mut self) {
+        if self.editor_config().textwidth == 0 {
+            return;
+        }
+
+        let options = self.format_options();
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let Some(split) = format::break_line(&line, self.cursor.col, &options) else {
+            return;
+        };
+
+        self.current_buffer_mut()
+            .replace_lines(line_idx, line_idx, &[split.head, split.tail]);
+        self.cursor.line = line_idx + 1;
+        self.cursor.col = split.cursor_col;
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    /// Deletes both halves of an empty pair around the cursor, e.g. `(|)`.
+    pub fn delete_empty_pair(&mut self) -> bool {
+        let Some(pairs) = self.auto_pairs() else {
+            return false;
+        };
+        if self.cursor.col == 0 {
+            return false;
+        }
+
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let chars: Vec<char> = line.chars().collect();
+        let col = self.cursor.col;
+        match (chars.get(col - 1), chars.get(col)) {
+            (Some(&before), Some(&after)) if pairs.is_empty_pair(before, after) => {
+                self.current_buffer_mut()
+                    .delete_range(line_idx, col - 1, line_idx, col + 1);
+                self.cursor.col -= 1;
+                self.cursor.desired_col = self.cursor.col;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn insert_newline(&mut self) {
+        let line_idx = self.cursor.line;
+        let col = self.cursor.col;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let (before, after) = line.split_at(TextUtils::char_to_byte(&line, col));
+
+        let between_pair = matches!(
+            (before.chars().last(), after.chars().next()),
+            (Some(open), Some(close)) if self
+                .auto_pairs()
+                .is_some_and(|pairs| !pairs.is_quote(open) && pairs.is_empty_pair(open, close))
+        );
+        if between_pair && self.editor_config().autoindent {
+            let base = indent::leading_indent(before).to_string();
+            let inner = format!("{base}{}", indent::indent_unit(&self.editor_config()));
+            self.current_buffer_mut()
+                .insert_str(line_idx, col, &format!("\n{inner}\n{base}"));
+            self.cursor.line += 1;
+            self.cursor.col = inner.chars().count();
+            self.cursor.desired_col = self.cursor.col;
+            return;
+        }
+
+        let indent = indent::newline_indent(before, &self.editor_config(), self.indent_rules());
+        let moved_whitespace = if self.editor_config().autoindent {
+            indent::leading_indent(after).chars().count()
+        } else {
+            0
+        };
+
+        let buffer = self.current_buffer_mut();
+        buffer.delete_range(line_idx, col, line_idx, col + moved_whitespace);
+        buffer.insert_str(line_idx, col, &format!("\n{indent}"));
+
+        self.cursor.line += 1;
+        self.cursor.col = indent.chars().count();
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    pub fn open_line_below(&mut self) {
+        self.cursor.col = self.current_buffer().line_len(self.cursor.line);
+        self.insert_newline();
+    }
+
+    pub fn open_line_above(&mut self) {
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let indent = if self.editor_config().autoindent {
+            indent::leading_indent(&line).to_string()
+        } else {
+            String::new()
+        };
+
+        self.current_buffer_mut()
+            .insert_str(line_idx, 0, &format!("{indent}\n"));
+
+        self.cursor.col = indent.chars().count();
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    /// Dedents the current line when a closing bracket is typed as its first
+    /// non-blank character.
+    pub fn apply_closing_indent(&mut self, ch: char) {
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let before = &line[..TextUtils::char_to_byte(&line, self.cursor.col)];
+
+        if let Some(indent) =
+            indent::closing_indent(before, ch, &self.editor_config(), self.indent_rules())
+        {
+            self.current_buffer_mut().set_line_indent(line_idx, &indent);
+            self.cursor.col = indent.chars().count();
+            self.cursor.desired_col = self.cursor.col;
+        }
+    }
+
+    pub fn shift_lines(&mut self, start_line: usize, end_line: usize, levels: isize) {
+        let config = self.editor_config();
+        let buffer = self.current_buffer_mut();
+        let last_line = end_line.min(buffer.line_count().saturating_sub(1));
+
+        for line_idx in start_line..=last_line {
+            if let Some(line) = buffer.line(line_idx) {
+                if TextUtils::is_blank_line(&line) {
+                    continue;
+                }
+                let new_indent = indent::shifted_indent(&line, levels, &config);
+                buffer.set_line_indent(line_idx, &new_indent);
+            }
+        }
+
+        let line = self.current_buffer().line(start_line).unwrap_or_default();
+        self.cursor.line = start_line;
+        self.cursor.col = indent::leading_indent(&line).chars().count();
+        self.cursor.desired_col = self.cursor.col;
+
+        let count = last_line + 1 - start_line;
+        let direction = if levels >= 0 { ">" } else { "<" };
+        self.set_status_message(format!("{count} line(s) {direction}ed 1 time"));
+    }
+
+    pub fn range_context(&self) -> RangeContext {
+        RangeContext::new(self.current_buffer(), self.cursor.line, self.visual_marks)
+    }
+
+    /// Replaces lines `start_line..=end_line` with `transform` applied to them,
+    /// as a single undo step. Returns the number of resulting lines.
+    pub fn transform_lines(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        transform: impl FnOnce(&[String]) -> Vec<String>,
+    ) -> usize {
+        let buffer = self.current_buffer();
+        let last_line = end_line.min(buffer.text_line_count().saturating_sub(1));
+        let lines: Vec<String> = (start_line..=last_line)
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+            .collect();
+
+        let transformed = transform(&lines);
+        if transformed != lines {
+            self.save_undo_state();
+            self.current_buffer_mut()
+                .replace_lines(start_line, last_line, &transformed);
+        }
+
+        self.cursor.line = start_line;
+        self.cursor.col = 0;
+        self.cursor.desired_col = 0;
+        transformed.len()
+    }
+
+    /// `:[range]!cmd`: pipes the lines through a shell command and replaces
+    /// them with its output. A failing command leaves the buffer alone. The
+    /// empty line after a final newline is not part of the input, so the
+    /// newline itself is kept.
+    pub fn filter_lines(&mut self, start_line: usize, end_line: usize, command: &str) {
+        let buffer = self.current_buffer();
+        let end_line = end_line.min(buffer.text_line_count().saturating_sub(1));
+        let input: String = (start_line..=end_line)
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default() + "\n")
+            .collect();
+        let output = match shell::run(command, Some(&input)) {
+            Ok(output) if output.success() => output,
+            Ok(output) => {
+                self.set_error_message(output.error_message());
+                return;
+            }
+            Err(e) => {
+                self.set_error_message(e.to_string());
+                return;
+            }
+        };
+
+        let lines: Vec<String> = output.stdout.lines().map(String::from).collect();
+        if lines.is_empty() {
+            self.delete_lines(start_line, end_line);
+        } else {
+            self.transform_lines(start_line, end_line, |_| lines);
+        }
+        self.set_status_message(format!("{} line(s) filtered", end_line + 1 - start_line));
+    }
+
+    /// Inserts `text` as new lines below `line_idx`, as one undo step, and
+    /// moves the cursor to the first of them.
+    pub fn insert_lines_below(&mut self, line_idx: usize, text: &str) {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let line_idx = line_idx.min(self.current_buffer().line_count().saturating_sub(1));
+        let line_len = self.current_buffer().line_len(line_idx);
+        self.save_undo_state();
+        self.current_buffer_mut()
+            .insert_str(line_idx, line_len, &format!("\n{text}"));
+        self.cursor.line = line_idx + 1;
+        self.cursor.col = 0;
+        self.cursor.desired_col = 0;
+    }
+
+    /// `!{motion}`: opens the command line with the range of lines the
+    /// motion covers and `!`, ready for a filter command.
+    pub fn start_filter_prompt(&mut self, start_line: usize, end_line: usize) {
+        self.cursor.line = start_line;
+        self.cursor.col = 0;
+        self.cursor.desired_col = 0;
+        self.mode = Mode::Command;
+        self.clear_error_message();
+        if end_line > start_line {
+            self.command_line
+                .set(&format!(".,.+{}!", end_line - start_line));
+        } else {
+            self.command_line.set(".!");
+        }
+    }
+
+    /// `:[range]r !cmd`: inserts the output of a shell command below the
+    /// range.
+    pub fn read_shell_output(&mut self, line_idx: usize, command: &str) {
+        match shell::run(command, None) {
+            Ok(output) if output.success() => {
+                let count = output.stdout.lines().count();
+                if count > 0 {
+                    self.insert_lines_below(line_idx, &output.stdout);
+                }
+                self.set_status_message(format!("{count} line(s) read"));
+            }
+            Ok(output) => self.set_error_message(output.error_message()),
+            Err(e) => self.set_error_message(e.to_string()),
+        }
+    }
+
+    /// `:!cmd` and `:w !cmd`: runs a shell command, feeding it `input`, and
+    /// shows what it printed.
+    pub fn run_shell_command(&mut self, command: &str, input: Option<&str>) {
+        match shell::run(command, input) {
+            Ok(output) if output.success() => {
+                let lines: Vec<&str> = output.stdout.lines().collect();
+                if lines.is_empty() {
+                    self.set_status_message(format!("!{command}"));
+                } else {
+                    self.set_status_message(lines.join(" | "));
+                }
+            }
+            Ok(output) => self.set_error_message(output.error_message()),
+            Err(e) => self.set_error_message(e.to_string()),
+        }
+    }
+
+    /// Lines `start_line..=end_line` joined with `\n`.
+    pub fn range_text(&self, start_line: usize, end_line: usize) -> String {
+        let buffer = self.current_buffer();
+        (start_line..=end_line)
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Runs `:[range]s/pattern/replacement/flags` over `start_line..=end_line`
+    /// as a single undo step, or starts asking about each match with `c`.
+    pub fn substitute(&mut self, start_line: usize, end_line: usize, args: &str) {
+        let editor = self.editor_config();
+        let substitution = match Substitution::parse(
+            args,
+            self.search_state.pattern.as_ref(),
+            editor.ignorecase,
+            editor.smartcase,
+        ) {
+            Ok(substitution) => substitution,
+            Err(e) => {
+                self.set_error_message(e.to_string());
+                return;
+            }
+        };
+
+        self.set_last_search(substitution.pattern.clone(), false);
+
+        let end_line = end_line.min(self.current_buffer().text_line_count().saturating_sub(1));
+        let text = self.range_text(start_line, end_line);
+        let (new_text, lines) = substitution.apply(&text);
+        if lines.is_empty() {
+            if !substitution.flags.quiet {
+                self.set_error_message(format!("Pattern not found: {}", self.search_state.query));
+            }
+            return;
+        }
+
+        let mut changed_lines = lines.clone();
+        changed_lines.dedup();
+        if substitution.flags.count_only {
+            self.set_status_message(format!(
+                "{} match(es) on {} line(s)",
+                lines.len(),
+                changed_lines.len()
+            ));
+            return;
+        }
+
+        if substitution.flags.confirm {
+            self.substitute_confirm = Some(SubstituteConfirm {
+                substitution,
+                start_line,
+                end_line,
+                text,
+                current: (0, 0),
+                count: 0,
+            });
+            self.search_state.is_active = true;
+            self.next_substitute_match(Some(0));
+            return;
+        }
+
+        self.save_undo_state();
+        let new_lines: Vec<String> = new_text.split('\n').map(String::from).collect();
+        self.current_buffer_mut()
+            .replace_lines(start_line, end_line, &new_lines);
+        self.cursor.line = start_line + lines.last().copied().unwrap_or(0);
+        self.cursor.col = 0;
+        let buffer = self.current_buffer().clone();
+        self.cursor.clamp_to_buffer(&buffer);
+        self.cursor.desired_col = self.cursor.col;
+        self.set_status_message(format!(
+            "{} substitution(s) on {} line(s)",
+            lines.len(),
+            changed_lines.len()
+        ));
+    }
+
+    /// Handles the answer to a `:s///c` prompt: `y` replaces the match, `n`
+    /// skips it, `a` replaces it and all remaining ones, `l` replaces it and
+    /// stops, and `q` stops.
+    pub fn answer_substitute_confirm(&mut self, answer: char) {
+        let Some(confirm) = &self.substitute_confirm else {
+            return;
+        };
+        let (start, end) = confirm.current;
+        match answer {
+            'y' => {
+                let from = self.replace_substitute_match();
+                self.next_substitute_match(from);
+            }
+            'n' => {
+                let from = confirm
+                    .substitution
+                    .resume_at(&confirm.text, end, start == end);
+                self.next_substitute_match(from);
+            }
+            'a' => {
+                while self.substitute_confirm.is_some() {
+                    let from = self.replace_substitute_match();
+                    self.next_substitute_match(from);
+                }
+            }
+            'l' => {
+                self.replace_substitute_match();
+                self.finish_substitute_confirm();
+            }
+            'q' => self.finish_substitute_confirm(),
+            _ => {}
+        }
+    }
+
+    /// Replaces the match being asked about in the buffer and returns where
+    /// to look for the next one.
+    fn replace_substitute_match(&mut self) -> Option<usize> {
+        let confirm = self.substitute_confirm.as_ref()?;
+        let (start, end) = confirm.current;
+        let regex = &confirm.substitution.pattern.regex;
+        let replacement = match regex.captures_at(&confirm.text, start) {
+            Some(caps) => confirm.substitution.expand(&caps),
+            None => return None,
+        };
+        if confirm.count == 0 {
+            self.save_undo_state();
+        }
+
+        let confirm = self.substitute_confirm.as_mut()?;
+        confirm.text.replace_range(start..end, &replacement);
+        confirm.count += 1;
+        let new_lines: Vec<String> = confirm.text.split('\n').map(String::from).collect();
+        let (start_line, end_line) = (confirm.start_line, confirm.end_line);
+        confirm.end_line = start_line + new_lines.len() - 1;
+        let from =
+            confirm
+                .substitution
+                .resume_at(&confirm.text, start + replacement.len(), start == end);
+        self.current_buffer_mut()
+            .replace_lines(start_line, end_line, &new_lines);
+        from
+    }
+
+    /// Moves to the next match at or after byte `from` of the range and asks
+    /// about it, or ends the confirmation when there are none left.
+    fn next_substitute_match(&mut self, from: Option<usize>) {
+        let Some(confirm) = &mut self.substitute_confirm else {
+            return;
+        };
+        let found = from.and_then(|from| {
+            confirm
+                .substitution
+                .pattern
+                .regex
+                .find_at(&confirm.text, from)
+        });
+        let Some(found) = found else {
+            self.finish_substitute_confirm();
+            return;
+        };
+        confirm.current = (found.start(), found.end());
+
+        let position = |byte: usize| {
+            let before = &confirm.text[..byte];
+            let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+            let mut cursor = Cursor::new();
+            cursor.line = confirm.start_line + before.matches('\n').count();
+            cursor.col = before[line_start..].chars().count();
+            cursor
+        };
+        let (start, end) = (position(found.start()), position(found.end()));
+        let replacement = confirm.substitution.replacement.clone();
+
+        self.cursor = start;
+        self.cursor.desired_col = start.col;
+        self.selection.start_selection(start);
+        self.selection.update_selection(end);
+        let buffer = &self.buffers[self.current_buffer];
+        if let Some(pattern) = &self.search_state.pattern {
+            self.search_state.results = pattern.find_all(&buffer.content);
+        }
+        self.set_status_message(format!("replace with {replacement} (y/n/a/q/l)?"));
+    }
+
+    fn finish_substitute_confirm(&mut self) {
+        let Some(confirm) = self.substitute_confirm.take() else {
+            return;
+        };
+        self.selection.clear();
+        let buffer = self.current_buffer().clone();
+        self.cursor.clamp_to_buffer(&buffer);
+        self.set_status_message(format!("{} substitution(s)", confirm.count));
+    }
+
+    /// Joins `count` lines starting at `start_line` (`J` / `gJ`); a count
+    /// below two still joins with the next line.
+    pub fn join_lines(&mut self, start_line: usize, count: usize, adjust_spaces: bool) {
+        let buffer = self.current_buffer();
+        let last_line =
+            (start_line + count.max(2) - 1).min(buffer.text_line_count().saturating_sub(1));
+        if last_line <= start_line {
+            return;
+        }
+
+        let lines: Vec<String> = (start_line..=last_line)
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+            .collect();
+        let (joined, join_col) = lines::join_lines(&lines, adjust_spaces);
+
+        self.save_undo_state();
+        self.current_buffer_mut()
+            .replace_lines(start_line, last_line, &[joined]);
+        self.cursor.line = start_line;
+        self.cursor.col = join_col;
+        self.cursor.desired_col = join_col;
+    }
+
+    /// Deletes lines `start_line..=end_line` (`:d`), leaving the cursor on
+    /// the line that follows them.
+    pub fn delete_lines(&mut self, start_line: usize, end_line: usize) {
+        let buffer = self.current_buffer();
+        let last_line = buffer.line_count().saturating_sub(1);
+        let end_line = end_line.min(last_line);
+        let end_len = buffer.line_len(end_line);
+        let before_len = start_line.checked_sub(1).map(|line| buffer.line_len(line));
+
+        self.save_undo_state();
+        let buffer = self.current_buffer_mut();
+        if end_line < last_line {
+            buffer.delete_range(start_line, 0, end_line + 1, 0);
+        } else if let Some(before_len) = before_len {
+            buffer.delete_range(start_line - 1, before_len, end_line, end_len);
+        } else {
+            buffer.delete_range(0, 0, end_line, end_len);
+        }
+
+        let buffer = self.current_buffer().clone();
+        self.cursor.line = start_line.min(buffer.line_count().saturating_sub(1));
+        self.cursor.col = 0;
+        self.cursor.clamp_to_buffer(&buffer);
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    /// Adds `delta` to the number under or after the cursor (`Ctrl-A` / `Ctrl-X`).
+    pub fn increment_number(&mut self, delta: i64) {
+        let line_idx = self.cursor.line;
+        let line = self.current_buffer().line(line_idx).unwrap_or_default();
+        let Some((new_line, col)) = increment::increment(&line, self.cursor.col, delta) else {
+            return;
+        };
+
+        self.save_undo_state();
+        self.current_buffer_mut().replace_line(line_idx, &new_line);
+        self.cursor.col = col;
+        self.cursor.desired_col = col;
+    }
+
+    /// Adds `delta` to the first number on each line from `start` to `end`.
+    /// With `progressive` the n-th changed line gets `n * delta` (`g Ctrl-A`).
+    pub fn increment_lines(&mut self, start: Cursor, end: Cursor, delta: i64, progressive: bool) {
+        let buffer = self.current_buffer_mut();
+        let last_line = end.line.min(buffer.line_count().saturating_sub(1));
+        let mut step = 1;
+
+        for line_idx in start.line..=last_line {
+            let line = buffer.line(line_idx).unwrap_or_default();
+            let col = if line_idx == start.line { start.col } else { 0 };
+            let amount = if progressive { delta * step } else { delta };
+            if let Some((new_line, _)) = increment::increment(&line, col, amount) {
+                buffer.replace_line(line_idx, &new_line);
+                step += 1;
+            }
+        }
+
+        self.cursor = start;
+        self.cursor.desired_col = start.col;
+    }
+
+    /// Changes the case of the text from `start` up to, but not including, `end`.
+    pub fn change_case(&mut self, start: Cursor, end: Cursor, op: CaseOp) {
+        let buffer = self.current_buffer_mut();
+        let last_line = end.line.min(buffer.line_count().saturating_sub(1));
+
+        for line_idx in start.line..=last_line {
+            let line = buffer.line(line_idx).unwrap_or_default();
+            let chars: Vec<char> = line.chars().collect();
+            let from = if line_idx == start.line { start.col } else { 0 }.min(chars.len());
+            let to = if line_idx == end.line {
+                end.col
+            } else {
+                chars.len()
+            }
+            .clamp(from, chars.len());
+
+            let changed: String = chars[..from].iter().collect::<String>()
+                + &op.apply(&chars[from..to].iter().collect::<String>())
+                + &chars[to..].iter().collect::<String>();
+            buffer.replace_line(line_idx, &changed);
+        }
+
+        self.cursor = start;
+        self.cursor.desired_col = start.col;
+    }
+
+    /// Returns comment tokens for the current buffer, preferring a
+    /// `comment_tokens` override from the config.
+    pub fn comment_tokens(&self) -> Option<CommentTokens> {
+        let path = self.current_buffer().file_path()?;
+        let syntax = self.syntax_highlighter.detect_language(Some(path))?;
+        self.config
+            .editor
+            .comment_tokens
+            .get(&syntax.name)
+            .and_then(|value| CommentTokens::parse(value))
+            .or_else(|| CommentTokens::for_syntax(&syntax.name))
+    }
+
+    pub fn toggle_comment(&mut self, start_line: usize, end_line: usize) {
+        let Some(tokens) = self.comment_tokens() else {
+            self.set_status_message("No comment syntax for this file type".to_string());
+            return;
+        };
+
+        let tab_size = self.editor_config().tab_size;
+        let buffer = self.current_buffer_mut();
+        let last_line = end_line.min(buffer.line_count().saturating_sub(1));
+        let lines: Vec<String> = (start_line..=last_line)
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+            .collect();
+
+        for (offset, line) in comment::toggle_lines(&lines, &tokens, tab_size)
+            .iter()
+            .enumerate()
+        {
+            buffer.replace_line(start_line + offset, line);
+        }
+
+        let line = self.current_buffer().line(start_line).unwrap_or_default();
+        self.cursor.line = start_line;
+        self.cursor.col = indent::leading_indent(&line).chars().count();
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    pub fn format_options(&self) -> FormatOptions {
+        let editor = self.editor_config();
+        let width = match editor.textwidth {
+            0 => DEFAULT_FORMAT_WIDTH,
+            width => width,
+        };
+        FormatOptions::new(width, editor.tab_size, self.comment_tokens().as_ref())
+    }
+
+    /// Reflows lines `start_line..=end_line` to `textwidth` (`gq`).
+    pub fn format_lines(&mut self, start_line: usize, end_line: usize) {
+        let options = self.format_options();
+        let buffer = self.current_buffer_mut();
+        let last_line = end_line.min(buffer.line_count().saturating_sub(1));
+        let lines: Vec<String> = (start_line..=last_line)
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+            .collect();
+
+        let formatted = format::reflow(&lines, &options);
+        if formatted != lines {
+            buffer.replace_lines(start_line, last_line, &formatted);
+        }
+
+        let cursor_line = start_line + formatted.len().saturating_sub(1);
+        let line = self.current_buffer().line(cursor_line).unwrap_or_default();
+        self.cursor.line = cursor_line;
+        self.cursor.col = indent::leading_indent(&line).chars().count();
+        self.cursor.desired_col = self.cursor.col;
+    }
+
+    /// Returns the run of non-blank lines around `line`.
+    pub fn paragraph_bounds(&self, line: usize) -> (usize, usize) {
+        let buffer = self.current_buffer();
+        let is_blank = |idx: usize| TextUtils::is_blank_line(&buffer.line(idx).unwrap_or_default());
+
+        let mut start = line;
+        while start > 0 && !is_blank(start - 1) {
+            start -= 1;
+        }
+        let mut end = line;
+        while end + 1 < buffer.line_count() && !is_blank(end + 1) {
+            end += 1;
+        }
+        (start, end)
+    }
+
     pub fn open_terminal(&mut self) {
         for (i, buffer) in self.buffers.iter().enumerate() {
             if buffer.is_terminal() {
@@ -230,6 +1242,7 @@ impl App {
 
     pub fn switch_to_previous_buffer(&mut self) {
         if self.buffers.len() > 1 {
+            self.save_view_state();
             for (i, buffer) in self.buffers.iter().enumerate() {
                 if i != self.current_buffer && !buffer.is_terminal() {
                     self.current_buffer = i;
@@ -247,10 +1260,414 @@ impl App {
         &mut self.buffers[self.current_buffer]
     }
 
+    /// Effective editor settings: the global settings overridden by the
+    /// current buffer's local options and then the window's.
+    pub fn editor_config(&self) -> EditorConfig {
+        let mut editor = self.config.editor.clone();
+        self.current_buffer().options.apply(&mut editor);
+        self.window_options.apply(&mut editor);
+        editor
+    }
+
+    /// Puts the `[filetype.<name>]` options for the current buffer's language
+    /// underneath its own local values.
+    fn apply_filetype_options(&mut self) {
+        let Some(syntax) = self
+            .current_buffer()
+            .file_path()
+            .and_then(|path| self.syntax_highlighter.detect_language(Some(path)))
+        else {
+            return;
+        };
+        let filetype_options = self.config.filetype_options(&syntax.name);
+        let buffer = self.current_buffer_mut();
+        buffer.options = filetype_options.merged(&buffer.options);
+    }
+
+    /// The local option values of the given scope, or `None` for global
+    /// options.
+    pub fn local_options_mut(&mut self, scope: OptionScope) -> Option<&mut LocalOptions> {
+        match scope {
+            OptionScope::Global => None,
+            OptionScope::Buffer => Some(&mut self.current_buffer_mut().options),
+            OptionScope::Window => Some(&mut self.window_options),
+        }
+    }
+
+    /// The value of an option in effect for the current buffer and window.
+    pub fn option_value(&self, option: &OptionDef) -> OptionValue {
+        option
+            .get_editor(&self.editor_config())
+            .unwrap_or_else(|| option.get(&self.config))
+    }
+
+    /// Drops the local values of an option after `:set` changed it globally,
+    /// so the new value is visible in the current buffer and window.
+    pub fn clear_local_option(&mut self, name: &str) {
+        if let Some(option) = options::lookup(name) {
+            self.current_buffer_mut().options.clear(option.name);
+            self.window_options.clear(option.name);
+        }
+    }
+
     pub fn quit(&mut self) {
+        self.save_view_state();
         self.should_quit = true;
     }
 
+    /// Buffers with changes that have not been written, leaving out the
+    /// terminal and the command-line window.
+    pub fn modified_buffers(&self) -> Vec<usize> {
+        let window = self.command_window.map(|_| self.current_buffer);
+        (0..self.buffers.len())
+            .filter(|&idx| Some(idx) != window)
+            .filter(|&idx| !self.buffers[idx].is_terminal() && self.buffers[idx].is_modified)
+            .collect()
+    }
+
+    /// `:q` and `:qa`: quits unless buffers have unsaved changes, which
+    /// `force` discards. With `confirm` set, asks whether to save each of
+    /// them instead of refusing.
+    pub fn quit_checked(&mut self, force: bool) {
+        let modified = self.modified_buffers();
+        if force || modified.is_empty() {
+            self.quit();
+        } else if self.editor_config().confirm {
+            self.write_confirm = Some(WriteConfirm::Quit { buffers: modified });
+            self.ask_write_confirm();
+        } else {
+            let names = self.buffer_names();
+            let modified: Vec<&str> = modified.iter().map(|&idx| names[idx].as_str()).collect();
+            self.set_error_message(format!(
+                "No write since last change: {} (add ! to override)",
+                modified.join(", ")
+            ));
+        }
+    }
+
+    /// Writes buffer `idx` to its file. Returns false, with an error shown,
+    /// when it has no file name or could not be written.
+    pub fn write_buffer(&mut self, idx: usize) -> bool {
+        let name = self.buffer_names()[idx].clone();
+        let buffer = &mut self.buffers[idx];
+        if buffer.file_path().is_none() {
+            self.set_error_message(format!("No file name for {name}"));
+            return false;
+        }
+        match buffer.save() {
+            Ok(()) => true,
+            Err(e) => {
+                self.set_error_message(format!("Error saving: {e}"));
+                false
+            }
+        }
+    }
+
+    /// `:wa`: writes every modified buffer. Returns false when any of them
+    /// could not be written.
+    pub fn write_all(&mut self) -> bool {
+        let modified = self.modified_buffers();
+        let names = self.buffer_names();
+        let failed: Vec<&str> = modified
+            .iter()
+            .filter(|&&idx| !self.write_buffer(idx))
+            .map(|&idx| names[idx].as_str())
+            .collect();
+        if failed.is_empty() {
+            self.set_status_message(format!("{} buffer(s) written", modified.len()));
+            true
+        } else {
+            self.set_error_message(format!("Not written: {}", failed.join(", ")));
+            false
+        }
+    }
+
+    /// Does the write of `:w`, `:saveas` and friends. Writing over another
+    /// existing file or part of the buffer over its own file needs `force`;
+    /// with `confirm` set an overwrite is asked about, and `quit` follows a
+    /// yes. Returns false when nothing was written.
+    pub fn write_file(&mut self, write: &FileWrite, force: bool, quit: bool) -> bool {
+        let buffer = self.current_buffer();
+        let last = buffer.text_line_count().saturating_sub(1);
+        let (start, end) = write.lines.unwrap_or((0, last));
+        let whole = start == 0 && end >= last;
+        let own_path = buffer.file_path().map(|path| path.to_path_buf());
+        let path = match (&write.file, &own_path) {
+            (Some(file), _) => self.resolve_path(file),
+            (None, Some(path)) => path.clone(),
+            (None, None) => {
+                self.set_error_message("No file name".to_string());
+                return false;
+            }
+        };
+        let own = own_path.as_ref().is_some_and(|own| {
+            *own == path
+                || own
+                    .canonicalize()
+                    .ok()
+                    .is_some_and(|own| path.canonicalize().ok() == Some(own))
+        });
+        // Writing a whole unnamed buffer names it, as `:saveas` does.
+        let rename = write.rename || own_path.is_none() && whole && !write.append;
+
+        if own && !whole && !write.append && !force {
+            self.set_error_message("Use ! to write partial buffer".to_string());
+            return false;
+        }
+        if !own && !write.append && !force && path.exists() {
+            if self.editor_config().confirm {
+                self.write_confirm = Some(WriteConfirm::Overwrite {
+                    write: write.clone(),
+                    quit,
+                });
+                self.ask_write_confirm();
+            } else {
+                self.set_error_message("File exists (add ! to override)".to_string());
+            }
+            return false;
+        }
+
+        let text = if whole {
+            self.current_buffer().content.to_string()
+        } else {
+            self.range_text(start, end) + "\n"
+        };
+        let result = if rename || own && whole && !write.append {
+            self.current_buffer_mut().save_as(&path)
+        } else {
+            self.current_buffer().write_to(&path, &text, write.append)
+        };
+        if let Err(e) = result {
+            self.set_error_message(format!("Error saving: {e}"));
+            return false;
+        }
+        if rename {
+            if let Err(e) = self.file_watcher.watch(&path) {
+                log::warn!("Failed to watch file {path:?}: {e}");
+            }
+        }
+
+        let name = path
+            .strip_prefix(self.get_current_directory())
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        let verb = if write.append { "appended" } else { "written" };
+        self.set_status_message(format!(
+            "\"{name}\" {} line(s) {verb}",
+            text.lines().count()
+        ));
+        true
+    }
+
+    fn ask_write_confirm(&mut self) {
+        let question = match &self.write_confirm {
+            Some(WriteConfirm::Quit { buffers }) => {
+                let Some(&idx) = buffers.first() else {
+                    self.write_confirm = None;
+                    self.quit();
+                    return;
+                };
+                format!(
+                    "Save changes to \"{}\"? (y)es, (n)o, (a)ll, (d)iscard all, (c)ancel",
+                    self.buffer_names()[idx]
+                )
+            }
+            Some(WriteConfirm::Overwrite { write, .. }) => format!(
+                "Overwrite existing file \"{}\"? (y)es, (n)o",
+                write.file.as_deref().unwrap_or_default()
+            ),
+            None => return,
+        };
+        self.set_status_message(question);
+    }
+
+    /// Handles the answer to a `confirm` prompt. Before quitting, `y` saves
+    /// the buffer asked about, `n` leaves it unsaved, `a` saves all the
+    /// remaining ones, `d` quits without saving and `c` cancels; before an
+    /// overwrite, `y` writes and `n` cancels.
+    pub fn answer_write_confirm(&mut self, answer: char) {
+        let Some(confirm) = self.write_confirm.take() else {
+            return;
+        };
+        match (confirm, answer) {
+            (WriteConfirm::Quit { mut buffers }, 'y') => {
+                if self.write_buffer(buffers[0]) {
+                    buffers.remove(0);
+                    self.write_confirm = Some(WriteConfirm::Quit { buffers });
+                    self.ask_write_confirm();
+                }
+            }
+            (WriteConfirm::Quit { mut buffers }, 'n') => {
+                buffers.remove(0);
+                self.write_confirm = Some(WriteConfirm::Quit { buffers });
+                self.ask_write_confirm();
+            }
+            (WriteConfirm::Quit { buffers }, 'a') => {
+                if buffers.into_iter().all(|idx| self.write_buffer(idx)) {
+                    self.quit();
+                }
+            }
+            (WriteConfirm::Quit { .. }, 'd') => self.quit(),
+            (WriteConfirm::Overwrite { write, quit }, 'y') => {
+                if self.write_file(&write, true, false) && quit {
+                    self.quit_checked(false);
+                }
+            }
+            (_, 'n' | 'c' | 'q') => self.status_message = None,
+            (confirm, _) => self.write_confirm = Some(confirm),
+        }
+    }
+
+    /// Remembers the cursor position and folds of the current file.
+    pub fn save_view_state(&mut self) {
+        let buffer = self.current_buffer();
+        if buffer.is_terminal() {
+            return;
+        }
+        let Some(path) = buffer.file_path().map(|path| path.to_path_buf()) else {
+            return;
+        };
+
+        let state = ViewState {
+            cursor_line: self.cursor.line,
+            cursor_col: self.cursor.col,
+            folds: buffer.folds.folds.clone(),
+        };
+        self.view_store.set(&path, state);
+        if let Err(e) = self.view_store.save() {
+            log::warn!("Failed to save view state: {e}");
+        }
+    }
+
+    fn restore_view_state(&mut self) {
+        let state = self
+            .current_buffer()
+            .file_path()
+            .and_then(|path| self.view_store.get(path))
+            .cloned();
+
+        if let Some(state) = state {
+            let line_count = self.current_buffer().line_count();
+            self.current_buffer_mut()
+                .folds
+                .restore(&state.folds, line_count);
+            self.cursor.line = state.cursor_line;
+            self.cursor.col = state.cursor_col;
+            let buffer = self.current_buffer().clone();
+            self.cursor.clamp_to_buffer(&buffer);
+        }
+        self.refresh_folds();
+    }
+
+    /// Recomputes folds for the automatic fold methods when the buffer changed.
+    pub fn refresh_folds(&mut self) {
+        let method = self.editor_config().foldmethod.clone();
+        let buffer = self.current_buffer();
+        if method == "manual"
+            || buffer.is_terminal()
+            || !buffer.folds.needs_refresh(&buffer.content)
+        {
+            return;
+        }
+
+        let ranges = match method.as_str() {
+            "indent" => {
+                let lines = Self::buffer_lines(buffer);
+                fold::indent_folds(&lines, self.editor_config().tab_size)
+            }
+            "marker" => fold::marker_folds(&Self::buffer_lines(buffer)),
+            _ => self.bracket_matcher().regions(),
+        };
+        let content = self.current_buffer().content.clone();
+        self.current_buffer_mut().folds.replace(ranges, &content);
+    }
+
+    /// Drops all folds, e.g. after `foldmethod` changes, and recomputes them.
+    pub fn reset_folds(&mut self) {
+        self.current_buffer_mut().folds = FoldSet::default();
+        self.refresh_folds();
+    }
+
+    fn buffer_lines(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.line_count())
+            .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+            .collect()
+    }
+
+    /// Creates a fold over `start_line..=end_line` (`zf`). With `foldmethod=marker`
+    /// the markers are written into the buffer as comments instead.
+    pub fn create_fold(&mut self, start_line: usize, end_line: usize) {
+        match self.editor_config().foldmethod.as_str() {
+            "manual" => {
+                self.current_buffer_mut().folds.create(start_line, end_line);
+            }
+            "marker" => {
+                let leader = self
+                    .comment_tokens()
+                    .and_then(|tokens| tokens.line)
+                    .map(|token| format!("{token} "))
+                    .unwrap_or_default();
+                self.save_undo_state();
+                for (line_idx, marker) in [
+                    (end_line, fold::FOLD_MARKER_CLOSE),
+                    (start_line, fold::FOLD_MARKER_OPEN),
+                ] {
+                    let line = self.current_buffer().line(line_idx).unwrap_or_default();
+                    let separator = if line.is_empty() { "" } else { " " };
+                    let col = line.chars().count();
+                    self.current_buffer_mut().insert_str(
+                        line_idx,
+                        col,
+                        &format!("{separator}{leader}{marker}"),
+                    );
+                }
+                self.refresh_folds();
+                self.current_buffer_mut().folds.close_at(start_line);
+            }
+            method => {
+                self.set_error_message(format!("Cannot create fold with foldmethod={method}"));
+                return;
+            }
+        }
+
+        self.cursor.line = start_line;
+        let buffer = self.current_buffer().clone();
+        self.cursor.clamp_to_buffer(&buffer);
+    }
+
+    /// Runs a fold command: `o`pen, `c`lose, toggle (`a`), open all (`R`) or
+    /// close all (`M`).
+    pub fn fold_command(&mut self, command: char) {
+        self.refresh_folds();
+        let line = self.cursor.line;
+        let folds = &mut self.current_buffer_mut().folds;
+        let found = match command {
+            'o' => folds.open_at(line),
+            'c' => folds.close_at(line),
+            'a' => folds.toggle_at(line),
+            'R' => {
+                folds.set_all(false);
+                true
+            }
+            'M' => {
+                folds.set_all(true);
+                true
+            }
+            _ => return,
+        };
+
+        if !found {
+            self.set_error_message("No fold found".to_string());
+        }
+
+        if let Some((start, _)) = self.current_buffer().folds.closed_range(line) {
+            self.cursor.line = start;
+            let buffer = self.current_buffer().clone();
+            self.cursor.clamp_to_buffer(&buffer);
+        }
+    }
+
     pub fn update_cursor_blink(&mut self) {
         if self.config.ui.cursor_blink {
             let now = Instant::now();
@@ -288,47 +1705,226 @@ impl App {
         self.error_message = None;
     }
 
-    pub fn save_undo_state(&mut self) {
-        let cursor = self.cursor;
-        self.current_buffer_mut().save_state(&cursor);
+    pub fn save_undo_state(&mut self) {
+        let cursor = self.cursor;
+        self.current_buffer_mut().save_state(&cursor);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(cursor) = self.current_buffer_mut().undo() {
+            self.cursor = cursor;
+            let buffer = self.current_buffer().clone();
+            self.cursor.clamp_to_buffer(&buffer);
+            self.set_status_message("Undo".to_string());
+        } else {
+            self.set_status_message("Nothing to undo".to_string());
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(cursor) = self.current_buffer_mut().redo() {
+            self.cursor = cursor;
+            let buffer = self.current_buffer().clone();
+            self.cursor.clamp_to_buffer(&buffer);
+            self.set_status_message("Redo".to_string());
+        } else {
+            self.set_status_message("Nothing to redo".to_string());
+        }
+    }
+
+    pub fn search(&mut self, query: &str) {
+        if self.run_search(query, '/', false) {
+            self.set_status_message(format!("Found {} matches", self.search_state.results.len()));
+        }
+    }
+
+    /// Makes `pattern` the last search pattern, which `n` and `N` repeat.
+    pub fn set_last_search(&mut self, pattern: SearchPattern, backward: bool) {
+        self.search_state.query = pattern.source.clone();
+        self.search_state.backward = backward;
+        self.search_state.pattern = Some(pattern);
+    }
+
+    /// Searches for `query` (with an optional `<delimiter>offset`) from the
+    /// cursor and jumps to the match. Reports an invalid pattern or a pattern
+    /// without matches in the error message and returns false.
+    fn run_search(&mut self, query: &str, delimiter: char, backward: bool) -> bool {
+        let editor = self.editor_config();
+        let pattern =
+            match SearchPattern::parse(query, delimiter, editor.ignorecase, editor.smartcase) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    self.set_error_message(e.to_string());
+                    return false;
+                }
+            };
+        self.set_last_search(pattern, backward);
+        let cursor = self.cursor;
+        self.search_state
+            .search(&self.buffers[self.current_buffer], &cursor);
+        if self.search_state.results.is_empty() {
+            self.set_error_message(format!("Pattern not found: {}", self.search_state.query));
+            return false;
+        }
+        self.search_state
+            .goto_current_result(&mut self.cursor, &self.buffers[self.current_buffer]);
+        true
+    }
+
+    /// Opens the `/` (or `?` when `backward`) search prompt.
+    pub fn start_search_prompt(&mut self, backward: bool) {
+        self.search_prompt = Some(SearchPrompt {
+            backward,
+            origin: self.cursor,
+            previous: self.search_state.clone(),
+        });
+        self.mode = Mode::Command;
+        self.command_line.clear();
+        self.clear_error_message();
+    }
+
+    /// Highlights the matches of the partly typed pattern and moves the
+    /// cursor to the nearest one (incsearch).
+    pub fn update_search_prompt(&mut self) {
+        let Some(prompt) = self.search_prompt.clone() else {
+            return;
+        };
+        self.cursor = prompt.origin;
+        let editor = self.editor_config();
+        let delimiter = if prompt.backward { '?' } else { '/' };
+        match SearchPattern::parse(
+            self.command_line.as_str(),
+            delimiter,
+            editor.ignorecase,
+            editor.smartcase,
+        ) {
+            Ok(pattern) => {
+                self.set_last_search(pattern, prompt.backward);
+                self.search_state
+                    .search(&self.buffers[self.current_buffer], &prompt.origin);
+                self.search_state
+                    .goto_current_result(&mut self.cursor, &self.buffers[self.current_buffer]);
+            }
+            Err(_) => self.search_state = prompt.previous,
+        }
+    }
+
+    /// Closes the search prompt with Esc, restoring the cursor and the
+    /// previous search.
+    pub fn cancel_search_prompt(&mut self) {
+        if let Some(prompt) = self.search_prompt.take() {
+            self.cursor = prompt.origin;
+            self.search_state = prompt.previous;
+        }
+        self.command_line.clear();
+        self.mode = Mode::Normal;
     }
 
-    pub fn undo(&mut self) {
-        if let Some(cursor) = self.current_buffer_mut().undo() {
-            self.cursor = cursor;
-            let buffer = self.current_buffer().clone();
-            self.cursor.clamp_to_buffer(&buffer);
-            self.set_status_message("Undo".to_string());
-        } else {
-            self.set_status_message("Nothing to undo".to_string());
+    /// Runs the typed search on Enter. An empty pattern repeats the last one
+    /// in the prompt's direction.
+    pub fn finish_search_prompt(&mut self) {
+        let Some(prompt) = self.search_prompt.take() else {
+            return;
+        };
+        let query = self.command_line.take();
+        self.add_history(HistoryKind::Search, &query);
+        self.mode = Mode::Normal;
+        self.cursor = prompt.origin;
+        self.search_state = prompt.previous;
+
+        let delimiter = if prompt.backward { '?' } else { '/' };
+        if query.is_empty() || query.starts_with(delimiter) {
+            if self.search_state.pattern.is_none() {
+                self.set_error_message("No previous search pattern".to_string());
+                return;
+            }
+            self.search_state.backward = prompt.backward;
+            self.search_state.is_active = true;
+            self.search_repeat(false);
+        } else if self.run_search(&query, delimiter, prompt.backward) {
+            self.set_status_message(format!("{delimiter}{query}"));
         }
     }
 
-    pub fn redo(&mut self) {
-        if let Some(cursor) = self.current_buffer_mut().redo() {
-            self.cursor = cursor;
-            let buffer = self.current_buffer().clone();
-            self.cursor.clamp_to_buffer(&buffer);
-            self.set_status_message("Redo".to_string());
-        } else {
-            self.set_status_message("Nothing to redo".to_string());
+    /// Jumps to the next match of the last search: `n` keeps its direction,
+    /// `N` (`reverse`) goes the other way.
+    pub fn search_repeat(&mut self, reverse: bool) {
+        let Some(pattern) = self.search_state.pattern.clone() else {
+            self.set_error_message("No previous search pattern".to_string());
+            return;
+        };
+        let backward = self.search_state.backward != reverse;
+
+        // Step from the current match rather than the cursor, which a search
+        // offset may have placed before or after it.
+        let buffer = &self.buffers[self.current_buffer];
+        let mut anchor = (self.cursor.line, self.cursor.col);
+        if let Some(current) = self
+            .search_state
+            .results
+            .get(self.search_state.current_result)
+        {
+            if pattern.target(current, &buffer.content) == anchor {
+                anchor = (current.line, current.col);
+            }
         }
+        self.search_from(&pattern, anchor, backward);
     }
 
-    pub fn search(&mut self, query: &str) {
+    /// Jumps to the first match after (or before) `anchor`, wrapping around.
+    fn search_from(&mut self, pattern: &SearchPattern, anchor: (usize, usize), backward: bool) {
+        let buffer = &self.buffers[self.current_buffer];
+        self.search_state.results = pattern.find_all(&buffer.content);
+        self.search_state.is_active = true;
+        if self.search_state.results.is_empty() {
+            self.set_error_message(format!("Pattern not found: {}", self.search_state.query));
+            return;
+        }
+        let wrapped = self.search_state.select_from(anchor, backward);
         self.search_state
-            .search(query, &self.buffers[self.current_buffer]);
-        if !self.search_state.results.is_empty() {
-            self.search_state.goto_current_result(&mut self.cursor);
-            self.set_status_message(format!("Found {} matches", self.search_state.results.len()));
+            .goto_current_result(&mut self.cursor, &self.buffers[self.current_buffer]);
+        let delimiter = if backward { '?' } else { '/' };
+        if wrapped {
+            self.set_status_message(if backward {
+                "search hit TOP, continuing at BOTTOM".to_string()
+            } else {
+                "search hit BOTTOM, continuing at TOP".to_string()
+            });
         } else {
-            self.set_error_message(format!("Pattern not found: {query}"));
+            self.set_status_message(format!("{delimiter}{}", self.search_state.query));
         }
     }
 
+    /// Searches for the whole word under (or after) the cursor, like `*`
+    /// and `#`.
+    pub fn search_word_under_cursor(&mut self, backward: bool) {
+        let line = self
+            .current_buffer()
+            .line(self.cursor.line)
+            .unwrap_or_default();
+        let Some((start, word)) = search::word_at(&line, self.cursor.col) else {
+            self.set_error_message("No word under cursor".to_string());
+            return;
+        };
+        let query = format!(r"\b{}\b", regex::escape(&word));
+        let ignorecase = self.editor_config().ignorecase;
+        let pattern = match SearchPattern::parse(&query, '\0', ignorecase, false) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.set_error_message(e.to_string());
+                return;
+            }
+        };
+
+        self.set_last_search(pattern.clone(), backward);
+        // Anchor at the word's start so both directions skip the word itself.
+        self.search_from(&pattern, (self.cursor.line, start), backward);
+    }
+
     pub fn search_next(&mut self) {
         if self.search_state.next() {
-            self.search_state.goto_current_result(&mut self.cursor);
+            self.search_state
+                .goto_current_result(&mut self.cursor, &self.buffers[self.current_buffer]);
             self.set_status_message(format!(
                 "Match {} of {}",
                 self.search_state.current_result + 1,
@@ -339,7 +1935,8 @@ impl App {
 
     pub fn search_previous(&mut self) {
         if self.search_state.previous() {
-            self.search_state.goto_current_result(&mut self.cursor);
+            self.search_state
+                .goto_current_result(&mut self.cursor, &self.buffers[self.current_buffer]);
             self.set_status_message(format!(
                 "Match {} of {}",
                 self.search_state.current_result + 1,
@@ -356,15 +1953,25 @@ impl App {
         self.help_window.hide();
     }
 
+    /// Soft-wrap layout options for a text area `width` columns wide.
+    pub fn wrap_options(&self, width: usize) -> WrapOptions {
+        let eol_width = if self.editor_config().list && self.config.list_chars().eol.is_some() {
+            1
+        } else {
+            0
+        };
+        WrapOptions::new(&self.editor_config(), width, eol_width)
+    }
+
     pub fn update_horizontal_scroll(&mut self, viewport_width: usize) {
-        if self.config.editor.wrap_lines {
+        if self.editor_config().wrap_lines {
             self.horizontal_scroll_offset = 0;
             return;
         }
 
         let line_number_width =
-            if self.config.editor.line_numbers || self.config.editor.relative_line_numbers {
-                if self.config.editor.relative_line_numbers {
+            if self.editor_config().line_numbers || self.editor_config().relative_line_numbers {
+                if self.editor_config().relative_line_numbers {
                     5
                 } else {
                     4
@@ -375,22 +1982,29 @@ impl App {
 
         let content_width = viewport_width.saturating_sub(line_number_width);
         let scroll_margin = 5;
-
-        if self.cursor.col < self.horizontal_scroll_offset {
-            self.horizontal_scroll_offset = self.cursor.col;
-        } else if self.cursor.col >= self.horizontal_scroll_offset + content_width {
-            self.horizontal_scroll_offset = self.cursor.col - content_width + 1;
-        } else if self.cursor.col < self.horizontal_scroll_offset + scroll_margin
+        let cursor_col = self
+            .current_buffer()
+            .line(self.cursor.line)
+            .map(|line| {
+                TextUtils::display_column(&line, self.cursor.col, self.editor_config().tab_size)
+            })
+            .unwrap_or(self.cursor.col);
+
+        if cursor_col < self.horizontal_scroll_offset {
+            self.horizontal_scroll_offset = cursor_col;
+        } else if cursor_col >= self.horizontal_scroll_offset + content_width {
+            self.horizontal_scroll_offset = cursor_col - content_width + 1;
+        } else if cursor_col < self.horizontal_scroll_offset + scroll_margin
             && self.horizontal_scroll_offset > 0
         {
-            self.horizontal_scroll_offset = self.cursor.col.saturating_sub(scroll_margin);
-        } else if self.cursor.col >= self.horizontal_scroll_offset + content_width - scroll_margin {
-            self.horizontal_scroll_offset = self.cursor.col + scroll_margin - content_width + 1;
+            self.horizontal_scroll_offset = cursor_col.saturating_sub(scroll_margin);
+        } else if cursor_col >= self.horizontal_scroll_offset + content_width - scroll_margin {
+            self.horizontal_scroll_offset = cursor_col + scroll_margin - content_width + 1;
         }
     }
 
     pub fn get_horizontal_scroll_offset(&self) -> usize {
-        if self.config.editor.wrap_lines {
+        if self.editor_config().wrap_lines {
             0
         } else {
             self.horizontal_scroll_offset
@@ -408,6 +2022,490 @@ impl App {
         }
     }
 
+    /// Switches to the buffer showing `path`, opening it with
+    /// `open_or_create_file` if no buffer has it yet.
+    pub fn edit_file(&mut self, path: &std::path::Path) -> Result<()> {
+        let open = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.file_path() == Some(path));
+        match open {
+            Some(buffer_idx) => {
+                self.switch_to_buffer(buffer_idx);
+                Ok(())
+            }
+            None => self.open_or_create_file(&path.to_string_lossy()),
+        }
+    }
+
+    /// Makes the buffer at `buffer_idx` current, keeping the view of the one
+    /// being left.
+    pub fn switch_to_buffer(&mut self, buffer_idx: usize) {
+        if buffer_idx == self.current_buffer || buffer_idx >= self.buffers.len() {
+            return;
+        }
+        self.save_view_state();
+        self.current_buffer = buffer_idx;
+        self.cursor.line = 0;
+        self.cursor.col = 0;
+        self.restore_view_state();
+    }
+
+    fn window_state(&self) -> Window {
+        Window {
+            buffer: self.current_buffer,
+            cursor: self.cursor,
+            selection: self.selection.clone(),
+            horizontal_scroll_offset: self.horizontal_scroll_offset,
+            options: self.window_options.clone(),
+        }
+    }
+
+    /// Makes `window` the current window, returning the one it replaces.
+    fn enter_window(&mut self, window: Window) -> Window {
+        let previous = self.window_state();
+        self.current_buffer = window.buffer.min(self.buffers.len() - 1);
+        self.cursor = window.cursor;
+        self.selection = window.selection;
+        self.horizontal_scroll_offset = window.horizontal_scroll_offset;
+        self.window_options = window.options;
+        let buffer = self.current_buffer().clone();
+        self.cursor.clamp_to_buffer(&buffer);
+        previous
+    }
+
+    /// Windows and tab pages cannot change while the command-line window,
+    /// whose buffer goes away when it closes, is open.
+    fn check_window_change(&mut self) -> bool {
+        if self.command_window.is_some() {
+            self.set_error_message("Not allowed in the command-line window".to_string());
+            return false;
+        }
+        true
+    }
+
+    /// `:split` / `:vsplit`: splits the current window in two, both showing
+    /// the current buffer, and stays in the new one on top (or left). Only
+    /// one split per tab page is supported.
+    pub fn split_window(&mut self, vertical: bool) -> bool {
+        if !self.check_window_change() {
+            return false;
+        }
+        if self.split.is_some() {
+            self.set_error_message("Only two windows per tab page are supported".to_string());
+            return false;
+        }
+        self.split = Some(Split {
+            window: self.window_state(),
+            vertical,
+            current_first: true,
+        });
+        true
+    }
+
+    /// `Ctrl-W w`: moves to the other window of a split.
+    pub fn switch_window(&mut self) {
+        if !self.check_window_change() {
+            return;
+        }
+        let Some(mut split) = self.split.take() else {
+            return;
+        };
+        self.save_view_state();
+        split.window = self.enter_window(split.window);
+        split.current_first = !split.current_first;
+        self.split = Some(split);
+    }
+
+    /// `:close` / `Ctrl-W c`: closes the current window, or the tab page when
+    /// it has a single window. Returns false, changing nothing, for the last
+    /// window of the last tab page.
+    pub fn close_window(&mut self) -> bool {
+        if self.split.is_none() && self.tabs.is_empty() || !self.check_window_change() {
+            return false;
+        }
+        self.save_view_state();
+        match self.split.take() {
+            Some(split) => {
+                self.enter_window(split.window);
+            }
+            None => {
+                let next = self.tab_index.min(self.tabs.len() - 1);
+                let tab = self.tabs.remove(next);
+                self.enter_window(tab.window);
+                self.split = tab.split;
+                self.tab_index = next;
+            }
+        }
+        true
+    }
+
+    /// `:tabclose`: closes the current tab page with all its windows.
+    /// Returns false for the last tab page.
+    pub fn close_tab(&mut self) -> bool {
+        if self.tabs.is_empty() || !self.check_window_change() {
+            return false;
+        }
+        self.split = None;
+        self.close_window()
+    }
+
+    /// `:tabnew`: opens a tab page after the current one, showing the
+    /// current buffer.
+    pub fn new_tab(&mut self) -> bool {
+        if !self.check_window_change() {
+            return false;
+        }
+        self.tabs.insert(
+            self.tab_index,
+            TabPage {
+                window: self.window_state(),
+                split: self.split.take(),
+            },
+        );
+        self.tab_index += 1;
+        true
+    }
+
+    /// `gt` / `gT`: moves to the next (or previous) tab page, wrapping
+    /// around.
+    pub fn switch_tab(&mut self, forward: bool) {
+        if self.tabs.is_empty() || !self.check_window_change() {
+            return;
+        }
+        self.save_view_state();
+        let count = self.tabs.len() + 1;
+        let target = if forward {
+            (self.tab_index + 1) % count
+        } else {
+            (self.tab_index + count - 1) % count
+        };
+        let current = TabPage {
+            window: self.window_state(),
+            split: self.split.take(),
+        };
+        self.tabs.insert(self.tab_index, current);
+        let tab = self.tabs.remove(target);
+        self.enter_window(tab.window);
+        self.split = tab.split;
+        self.tab_index = target;
+    }
+
+    /// Runs `f` with the other window of a split made current for the
+    /// moment, so it can be drawn like the current one.
+    pub fn with_other_window<R>(&mut self, f: impl FnOnce(&mut App) -> R) -> Option<R> {
+        let mut split = self.split.take()?;
+        split.window = self.enter_window(split.window);
+        let result = f(self);
+        split.window = self.enter_window(split.window);
+        self.split = Some(split);
+        Some(result)
+    }
+
+    /// The name of the buffer in each tab page's current window, in order.
+    pub fn tab_names(&self) -> Vec<String> {
+        let names = self.buffer_names();
+        let mut tabs: Vec<String> = self
+            .tabs
+            .iter()
+            .map(|tab| names[tab.window.buffer.min(names.len() - 1)].clone())
+            .collect();
+        tabs.insert(self.tab_index, names[self.current_buffer].clone());
+        tabs
+    }
+
+    /// A name for each buffer as listed by `:ls`: the path relative to the
+    /// current directory where possible.
+    pub fn buffer_names(&self) -> Vec<String> {
+        let current_dir = self.get_current_directory();
+        self.buffers
+            .iter()
+            .map(|buffer| match buffer.file_path() {
+                _ if buffer.is_terminal() => "[Terminal]".to_string(),
+                Some(path) => path
+                    .strip_prefix(current_dir)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string(),
+                None => "[No Name]".to_string(),
+            })
+            .collect()
+    }
+
+    /// The history Up/Down browse: searches while a search prompt is open.
+    fn prompt_history_kind(&self) -> HistoryKind {
+        if self.search_prompt.is_some() {
+            HistoryKind::Search
+        } else {
+            HistoryKind::Command
+        }
+    }
+
+    /// Records a command line or search and saves the history.
+    pub fn add_history(&mut self, kind: HistoryKind, entry: &str) {
+        self.history.add(kind, entry);
+        if let Err(e) = self.history.save() {
+            log::warn!("Failed to save history: {e}");
+        }
+    }
+
+    /// Replaces the command line with the previous (or next) history entry
+    /// starting with what was typed before browsing started.
+    pub fn browse_history(&mut self, older: bool) {
+        let kind = self.prompt_history_kind();
+        let line = &mut self.command_line;
+        if line.history_index.is_none() {
+            line.history_prefix = line.text.clone();
+        }
+        let index = self
+            .history
+            .find(kind, line.history_index, &line.history_prefix, older);
+        match index {
+            Some(index) => {
+                line.set(&self.history.entries(kind)[index]);
+                line.history_index = Some(index);
+            }
+            // Going past the newest entry brings back the typed text.
+            None if !older && line.history_index.is_some() => {
+                let prefix = line.history_prefix.clone();
+                line.set(&prefix);
+            }
+            None => {}
+        }
+    }
+
+    /// `Ctrl-R {reg}` on the command line. The unnamed, `0`, `+` and `*`
+    /// registers are the clipboard; `/` is the last search, `:` the last
+    /// command line, `%` the file name and `Ctrl-W` the word under the
+    /// cursor. Returns false for an unknown register.
+    pub fn insert_register(&mut self, register: char) -> bool {
+        let text = match register {
+            '"' | '0' | '+' | '*' => {
+                let text = Clipboard::get_text();
+                text.trim_end_matches('\n').replace('\n', " ")
+            }
+            '/' => match &self.search_state.pattern {
+                Some(pattern) => pattern.source.clone(),
+                None => String::new(),
+            },
+            ':' => self.history.commands.last().cloned().unwrap_or_default(),
+            '%' => {
+                let buffer_idx = self.current_buffer;
+                self.buffer_names().swap_remove(buffer_idx)
+            }
+            '\u{17}' => {
+                let line = self
+                    .current_buffer()
+                    .line(self.cursor.line)
+                    .unwrap_or_default();
+                search::word_at(&line, self.cursor.col)
+                    .map(|(_, word)| word)
+                    .unwrap_or_default()
+            }
+            _ => return false,
+        };
+        self.command_line.insert_str(&text);
+        true
+    }
+
+    /// `q:` and `q/`: opens the history in a buffer. Enter runs the line
+    /// under the cursor; `:q` closes the window.
+    pub fn open_command_window(&mut self, kind: HistoryKind) {
+        if self.command_window.is_some() {
+            return;
+        }
+        let mut buffer = Buffer::empty();
+        let mut text = self.history.entries(kind).join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        buffer.content = ropey::Rope::from_str(&text);
+
+        self.command_window = Some(CommandWindow {
+            kind,
+            return_buffer: self.current_buffer,
+            return_cursor: self.cursor,
+        });
+        self.buffers.push(buffer);
+        self.current_buffer = self.buffers.len() - 1;
+        self.cursor.line = self.current_buffer().line_count().saturating_sub(1);
+        self.cursor.col = 0;
+        self.cursor.desired_col = 0;
+        self.mode = Mode::Normal;
+    }
+
+    /// Closes the command-line window, returning the line under the cursor.
+    pub fn close_command_window(&mut self) -> Option<(HistoryKind, String)> {
+        let window = self.command_window.take()?;
+        let line = self
+            .current_buffer()
+            .line(self.cursor.line)
+            .unwrap_or_default();
+        let window_buffer = self.current_buffer;
+        self.current_buffer = window.return_buffer;
+        self.buffers.remove(window_buffer);
+        self.cursor = window.return_cursor;
+        Some((window.kind, line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+
+    /// Completes the word at the end of the command line, or cycles through
+    /// the candidates when the wildmenu is already open.
+    pub fn complete_command_line(&mut self, forward: bool) {
+        if let Some(menu) = self.wildmenu.as_mut() {
+            let start = menu.start;
+            let text = menu.cycle(forward).to_string();
+            self.command_line.replace_before_cursor(start, &text);
+            return;
+        }
+
+        let buffers = self.buffer_names();
+        let user_commands: Vec<String> = self.config.commands.keys().cloned().collect();
+        let completion = completion::complete(
+            self.command_line.before_cursor(),
+            &CompletionSource {
+                current_dir: self.get_current_directory(),
+                themes: self.config.theme_manager.list_themes(),
+                buffers: &buffers,
+                user_commands: &user_commands,
+            },
+        );
+        match completion.candidates.len() {
+            0 => {}
+            1 => {
+                self.command_line
+                    .replace_before_cursor(completion.start, &completion.candidates[0]);
+            }
+            _ => {
+                let original = self.command_line.before_cursor()[completion.start..].to_string();
+                self.wildmenu = Some(Wildmenu::new(
+                    completion.candidates,
+                    completion.start,
+                    original,
+                ));
+                self.complete_command_line(forward);
+            }
+        }
+    }
+
+    /// Shows the fuzzy file finder over the current directory.
+    pub fn open_fuzzy_finder(&mut self) {
+        let root = self.get_current_directory().to_path_buf();
+        self.fuzzy_finder.open(root);
+    }
+
+    /// Opens the file selected in the fuzzy finder and closes it.
+    pub fn open_finder_selection(&mut self, target: OpenTarget) {
+        let Some(path) = self.fuzzy_finder.selected_file() else {
+            return;
+        };
+        self.fuzzy_finder.close();
+        let opened = match target {
+            OpenTarget::Current => true,
+            OpenTarget::Split => self.split_window(false),
+            OpenTarget::VerticalSplit => self.split_window(true),
+            OpenTarget::Tab => self.new_tab(),
+        };
+        if !opened {
+            return;
+        }
+        if let Err(e) = self.edit_file(&path) {
+            self.set_error_message(format!("Error opening file: {e}"));
+            return;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.set_status_message(format!("Opened: {name}"));
+    }
+
+    /// Starts `:grep` for `pattern` in the background, searching `path`
+    /// (relative to the current directory) or the current directory itself.
+    pub fn grep(&mut self, pattern: &str, path: Option<&str>) {
+        let editor = self.editor_config();
+        let pattern = match SearchPattern::parse(pattern, '\0', editor.ignorecase, editor.smartcase)
+        {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.set_error_message(e.to_string());
+                return;
+            }
+        };
+
+        let root = self.get_current_directory().to_path_buf();
+        let search_root = path.map_or_else(|| root.clone(), |path| root.join(path));
+        if !search_root.exists() {
+            self.set_error_message(format!(
+                "No such file or directory: {}",
+                search_root.display()
+            ));
+            return;
+        }
+
+        self.quickfix
+            .reset(format!(":grep {}", pattern.source), root);
+        self.quickfix.visible = true;
+        self.grep_search = Some(GrepSearch::start(pattern.regex, search_root));
+        self.set_status_message(format!("Searching for {}...", pattern.source));
+    }
+
+    /// Moves the matches found by a running `:grep` into the quickfix list.
+    pub fn poll_grep(&mut self) {
+        let Some(search) = &mut self.grep_search else {
+            return;
+        };
+        let found = search.poll();
+        let finished = search.finished;
+        self.quickfix.entries.extend(found);
+
+        if finished {
+            self.grep_search = None;
+            self.quickfix.searching = false;
+            match self.quickfix.entries.len() {
+                0 => self.set_error_message(format!("No matches for {}", self.quickfix.title)),
+                count => self.set_status_message(format!("{count} match(es) in quickfix list")),
+            }
+        }
+    }
+
+    /// Opens quickfix entry `index` in its buffer (reusing an open one) and
+    /// moves the cursor to the match.
+    pub fn quickfix_jump(&mut self, index: usize) {
+        let Some(entry) = self.quickfix.entries.get(index).cloned() else {
+            self.set_error_message("No matches in quickfix list".to_string());
+            return;
+        };
+        self.quickfix.selected_index = index;
+
+        if let Err(e) = self.edit_file(&entry.path) {
+            self.set_error_message(format!("Error opening file: {e}"));
+            return;
+        }
+
+        self.cursor.line = entry.line;
+        self.cursor.col = entry.col;
+        let buffer = self.current_buffer().clone();
+        self.cursor.clamp_to_buffer(&buffer);
+        self.cursor.desired_col = self.cursor.col;
+        self.set_status_message(format!(
+            "({} of {}) {}",
+            index + 1,
+            self.quickfix.entries.len(),
+            entry.text
+        ));
+    }
+
+    /// `:cn` / `:cp`: jumps `offset` entries from the current one.
+    pub fn quickfix_step(&mut self, offset: isize) {
+        if self.quickfix.entries.is_empty() {
+            self.set_error_message("No matches in quickfix list".to_string());
+            return;
+        }
+        let target = self.quickfix.selected_index as isize + offset;
+        if target < 0 || target as usize >= self.quickfix.entries.len() {
+            self.set_error_message("No more items".to_string());
+            return;
+        }
+        self.quickfix_jump(target as usize);
+    }
+
     pub fn has_buffer_for_file(&self, path: &std::path::Path) -> bool {
         self.buffers
             .iter()
@@ -451,38 +2549,92 @@ impl SearchState {
     pub fn new() -> Self {
         Self {
             query: String::new(),
+            pattern: None,
             results: Vec::new(),
             current_result: 0,
             is_active: false,
+            backward: false,
         }
     }
 
-    pub fn search(&mut self, query: &str, buffer: &Buffer) {
-        self.query = query.to_string();
-        self.results.clear();
-        self.current_result = 0;
-        self.is_active = !query.is_empty();
-
-        if query.is_empty() {
+    /// Finds every match of the last pattern and selects the first one
+    /// after (or before, when searching backward) the cursor, wrapping
+    /// around the buffer.
+    pub fn search(&mut self, buffer: &Buffer, cursor: &Cursor) {
+        let Some(pattern) = &self.pattern else {
             return;
+        };
+        self.results = pattern.find_all(&buffer.content);
+        self.is_active = true;
+        self.select_from((cursor.line, cursor.col), self.backward);
+    }
+
+    /// Selects the first match starting after `anchor`, or the last one
+    /// starting before it when `backward`. Returns true if that wrapped
+    /// around the end of the buffer.
+    pub fn select_from(&mut self, anchor: (usize, usize), backward: bool) -> bool {
+        let found = if backward {
+            self.results
+                .iter()
+                .rposition(|found| (found.line, found.col) < anchor)
+        } else {
+            self.results
+                .iter()
+                .position(|found| (found.line, found.col) > anchor)
+        };
+        let wrapped = found.is_none();
+        self.current_result = match found {
+            Some(index) => index,
+            None if backward => self.results.len().saturating_sub(1),
+            None => 0,
+        };
+        wrapped
+    }
+
+    /// Byte ranges of the current pattern's matches within line `line_idx`,
+    /// whose text is `line`.
+    pub fn line_matches(&self, line_idx: usize, line: &str) -> Vec<(usize, usize)> {
+        let Some(pattern) = self.pattern.as_ref().filter(|_| self.is_active) else {
+            return Vec::new();
+        };
+        if !pattern.is_multiline() {
+            return pattern.find_in_line(line);
         }
 
-        for line_idx in 0..buffer.line_count() {
-            if let Some(line_content) = buffer.line(line_idx) {
-                let mut start_pos = 0;
-                while let Some(pos) = line_content[start_pos..].find(query) {
-                    let actual_pos = start_pos + pos;
-                    self.results.push(SearchResult {
-                        line: line_idx,
-                        col: actual_pos,
-                        match_length: query.len(),
-                    });
-                    start_pos = actual_pos + 1;
-                }
-            }
+        let byte = |col: usize| line.char_indices().nth(col).map_or(line.len(), |(i, _)| i);
+        self.results
+            .iter()
+            .filter(|found| (found.line..=found.end_line).contains(&line_idx))
+            .map(|found| {
+                let start = if found.line == line_idx {
+                    byte(found.col)
+                } else {
+                    0
+                };
+                let end = if found.end_line == line_idx {
+                    byte(found.end_col)
+                } else {
+                    line.len()
+                };
+                (start, end)
+            })
+            .filter(|(start, end)| start < end)
+            .collect()
+    }
+
+    /// The `[current/total]` counter shown in the status line.
+    pub fn counter(&self) -> Option<String> {
+        if !self.is_active || self.results.is_empty() {
+            return None;
         }
+        Some(format!(
+            "[{}/{}]",
+            self.current_result.min(self.results.len() - 1) + 1,
+            self.results.len()
+        ))
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> bool {
         if self.results.is_empty() {
             return false;
@@ -503,24 +2655,25 @@ impl SearchState {
         true
     }
 
-    pub fn goto_current_result(&self, cursor: &mut Cursor) {
-        if let Some(result) = self.results.get(self.current_result) {
-            cursor.line = result.line;
-            cursor.col = result.col;
-            cursor.desired_col = result.col;
-        }
+    pub fn goto_current_result(&self, cursor: &mut Cursor, buffer: &Buffer) {
+        let (Some(result), Some(pattern)) = (self.results.get(self.current_result), &self.pattern)
+        else {
+            return;
+        };
+        let (line, col) = pattern.target(result, &buffer.content);
+        cursor.line = line;
+        cursor.col = col;
+        cursor.clamp_to_buffer(buffer);
+        cursor.desired_col = cursor.col;
     }
 
     pub fn clear(&mut self) {
         self.query.clear();
+        self.pattern = None;
         self.results.clear();
         self.current_result = 0;
         self.is_active = false;
     }
-
-    fn default() -> Self {
-        Self::new()
-    }
 }
 
 impl Default for HelpWindow {
@@ -536,27 +2689,119 @@ impl HelpWindow {
             "".to_string(),
             "Available Commands:".to_string(),
             "".to_string(),
+            "Command Line:".to_string(),
+            "  :cmd1 | cmd2       - Run several commands (\\| for a literal |)".to_string(),
+            "  :e \"my file\"       - Quote or \\-escape spaces in arguments".to_string(),
+            "  :10,20d :.,$y :%   - Ranges: n . $ % '<,'> 'a +n -n, n;m relative".to_string(),
+            "  :d 3 / :j!         - Counts after the name, ! after the name".to_string(),
+            "  Tab / Shift+Tab    - Complete commands, files, themes, options and".to_string(),
+            "                       buffers; cycle through the menu of matches".to_string(),
+            "  Left/Right         - Move the cursor in the command line".to_string(),
+            "  Home/End           - Go to the start / end of the command line".to_string(),
+            "  Ctrl+W / Ctrl+U    - Delete word / delete to start".to_string(),
+            "  Ctrl+R {reg}       - Insert register: \" + * 0 (clipboard), / (search),".to_string(),
+            "                       : (last command), % (file), Ctrl+W (word)".to_string(),
+            "  Up / Down          - History matching the typed prefix (: and /)".to_string(),
+            "  q: / q/            - Command-line window (Enter runs, :q closes)".to_string(),
+            "  :command           - List user commands ([commands] in config.toml)".to_string(),
+            "  :iab <lhs> <rhs>   - Add an insert-mode abbreviation (:iab lists)".to_string(),
+            "".to_string(),
             "File Operations:".to_string(),
             "  :e <file>          - Edit/open file (creates if not exists)".to_string(),
-            "  :w                 - Save current file".to_string(),
-            "  :wq                - Save and quit".to_string(),
-            "  :q                 - Quit editor".to_string(),
+            "  Ctrl+P / :files    - Fuzzy find a file in the project (Enter opens,".to_string(),
+            "                       Ctrl+N/P or Up/Down select, Ctrl+U clears, Esc closes)"
+                .to_string(),
+            "                       Ctrl+S / Ctrl+V / Ctrl+T open in a split / vsplit / tab"
+                .to_string(),
+            "  :w [file]          - Save current file (or a copy; names an unnamed buffer)"
+                .to_string(),
+            "  :[range]w[!] file  - Write lines to a file (! overwrites)".to_string(),
+            "  :w >> [file]       - Append the buffer or range to a file".to_string(),
+            "  :saveas <file>     - Save under a new name and keep editing it".to_string(),
+            "  :wa                - Save all modified buffers".to_string(),
+            "  :wq / :x           - Save and quit (:x only saves when modified)".to_string(),
+            "  :wqa / :xa         - Save all modified buffers and quit".to_string(),
+            "  :q / :qa           - Quit editor, refusing with unsaved changes".to_string(),
+            "  :q! / :qa!         - Quit and discard unsaved changes".to_string(),
+            "  :set confirm       - Ask to save instead of refusing to quit".to_string(),
+            "  :ls                - List open buffers".to_string(),
+            "  :b <n or name>     - Switch to a buffer by number or name".to_string(),
+            "  :sp / :vs [file]   - Split the window (two windows per tab page)".to_string(),
+            "  Ctrl+W w / c       - Go to the other window / close this one".to_string(),
+            "  :tabnew [file]     - Open a new tab page (:tabc closes it)".to_string(),
+            "  gt / gT            - Go to the next / previous tab page".to_string(),
             "  :pwd               - Show current directory".to_string(),
             "  :cd <dir>          - Change current directory".to_string(),
             "  :explorer          - Toggle file explorer".to_string(),
             "  :refresh           - Refresh file explorer".to_string(),
             "".to_string(),
             "Search & Navigation:".to_string(),
-            "  :find <pattern>    - Search for pattern".to_string(),
+            "  /pattern / ?pattern - Search forward / backward, highlighting as you type"
+                .to_string(),
+            "                       (Esc returns to where the search started)".to_string(),
+            "  n / N              - Next match in / against the search direction".to_string(),
+            "  * / #              - Search forward / backward for word under cursor".to_string(),
+            "  :find <pattern>    - Search for regex pattern (\\c ignore case,".to_string(),
+            "                       \\C match case, \\n spans lines, \\b word boundary)"
+                .to_string(),
+            "  :find pat/e+1      - Offsets: +n/-n lines, s+n from start, e+n from end".to_string(),
             "  :findnext          - Go to next search result".to_string(),
             "  :findprev          - Go to previous search result".to_string(),
             "  :goto <line>       - Jump to line number".to_string(),
             "  :clear             - Clear search results".to_string(),
+            "  :set ic / scs      - ignorecase / smartcase for search patterns".to_string(),
+            "".to_string(),
+            "Project Search (Quickfix):".to_string(),
+            "  :grep <regex> [path] - Search files under the current directory,".to_string(),
+            "                       skipping .gitignore'd files (quote regex with spaces)"
+                .to_string(),
+            "  :copen / :cclose   - Open (and focus) / close the quickfix panel".to_string(),
+            "  :cn / :cp          - Go to next / previous match".to_string(),
+            "  :cc [n]            - Go to current / nth match".to_string(),
+            "  j/k, Enter         - Select / open match (in the quickfix panel)".to_string(),
+            "  Esc / q            - Return to editor / close panel (in the panel)".to_string(),
+            "".to_string(),
+            "Line Processing (range: %, '<,'>, 3,$, .,+2; default whole file):".to_string(),
+            "  :[range]sort[!] [i][n][u][r] [/pat/] - Sort (! reverse, n numeric,".to_string(),
+            "                       i ignore case, u unique, /pat/ sort after match)".to_string(),
+            "  :[range]uniq       - Remove adjacent duplicate lines".to_string(),
+            "  :[range]reverse    - Reverse line order".to_string(),
+            "  :[range]align <d>  - Align columns on delimiter <d>".to_string(),
+            "  :[range]join[!]    - Join lines (! keeps whitespace)".to_string(),
+            "  :[range]delete     - Delete lines (default current line)".to_string(),
+            "  :[range]yank [n]   - Copy lines to the clipboard (default current line)".to_string(),
+            "  : (visual)         - Enter a command on the selected lines".to_string(),
+            "".to_string(),
+            "Substitute (range defaults to the current line):".to_string(),
+            "  :[range]s/pat/rep/[flags] - Replace pat with rep (:%s for whole file)".to_string(),
+            "                       flags: g all on line, i/I ignore/match case,".to_string(),
+            "                       c confirm, n count only, e no error".to_string(),
+            "  rep: & or \\0 match, \\1 or $1 group, \\u/\\l next char upper/lower,".to_string(),
+            "       \\U...\\E / \\L...\\E upper/lower case, \\r line break".to_string(),
+            "  Confirm: y replace, n skip, a all, l replace and stop, q/Esc stop".to_string(),
+            "".to_string(),
+            "Global Commands (range defaults to the whole file, one undo step):".to_string(),
+            "  :[range]g/pat/cmd  - Run ex command cmd on each line matching pat".to_string(),
+            "  :[range]v/pat/cmd  - Run cmd on each line not matching pat (also :g!)".to_string(),
+            "  :g/pat/            - List the matching lines".to_string(),
+            "  :[range]normal keys - Run normal-mode keys on each line (:g/x/normal A;)"
+                .to_string(),
+            "".to_string(),
+            "Shell Commands:".to_string(),
+            "  :[range]!cmd       - Filter lines through cmd (:%!jq ., :'<,'>!sort -u)".to_string(),
+            "  !{motion} / !!     - Filter the lines a motion covers (opens :.,.+n!)".to_string(),
+            "  :!cmd / :!!        - Run a shell command / repeat the last one".to_string(),
+            "  :r !cmd / :r file  - Insert command output or a file below the cursor".to_string(),
+            "  :w !cmd            - Send the buffer to cmd's standard input".to_string(),
             "".to_string(),
             "Configuration & Settings:".to_string(),
             "  :set               - Show all current settings".to_string(),
             "  :set all           - Show all settings with descriptions".to_string(),
             "  :set <option>?     - Show value of specific setting".to_string(),
+            "  :set <option>!     - Toggle a boolean option (also inv<option>)".to_string(),
+            "  :set <option>&     - Reset an option to its default".to_string(),
+            "  :set so+=2 / so-=2 - Add to or subtract from a number".to_string(),
+            "  :set lcs+=eol:$    - Add or (-=) remove an item of a list option".to_string(),
             "".to_string(),
             "Line Numbers:".to_string(),
             "  :set nu            - Enable line numbers".to_string(),
@@ -576,6 +2821,27 @@ impl HelpWindow {
             "  :set wrap          - Enable line wrapping".to_string(),
             "  :set nowrap        - Disable line wrapping".to_string(),
             "  :set so=5          - Set scroll offset (0-20)".to_string(),
+            "  :set list          - Show tabs, trailing spaces and nbsp".to_string(),
+            "  :set nolist        - Hide whitespace markers".to_string(),
+            "  :set lcs=tab:>-,trail:~,eol:$ - Set whitespace glyphs".to_string(),
+            "  :set ai/noai       - Copy indentation to new lines".to_string(),
+            "  :set si/nosi       - Indent after {, ( or : and dedent on closers".to_string(),
+            "  :set ap/noap       - Auto-close brackets and quotes".to_string(),
+            "  :set pairs=()[]{}  - Characters to auto-pair (opener/closer)".to_string(),
+            "  :set mps=(:),<:>   - Bracket pairs matched by % and highlighting".to_string(),
+            "  :set fdm=indent    - Fold method (manual/indent/marker/syntax)".to_string(),
+            "  :set lbr/nolbr     - Wrap long lines at word boundaries".to_string(),
+            "  :set bri/nobri     - Indent wrapped lines like the original line".to_string(),
+            "  :set sbr=>>        - Marker shown before wrapped lines".to_string(),
+            "  :set tw=72         - Break lines while typing (0 disables)".to_string(),
+            "  .editorconfig files override ts, et and tw for the files they match".to_string(),
+            "".to_string(),
+            "Local Options:".to_string(),
+            "  :setlocal ts=2     - Set an option for this buffer or window only".to_string(),
+            "  :setlocal nowrap   - Flags work like :set (opt, noopt, opt!)".to_string(),
+            "  :setlocal ts<      - Go back to the global value".to_string(),
+            "  :setlocal          - Show local option values".to_string(),
+            "  [filetype.rust] sections in config.toml set options per language".to_string(),
             "".to_string(),
             "UI Options:".to_string(),
             "  :set cursorblink=true/false    - Enable/disable cursor blinking".to_string(),
@@ -605,22 +2871,49 @@ impl HelpWindow {
             "  Shift+Arrow keys   - Select text".to_string(),
             "  w                  - Jump to next word".to_string(),
             "  b                  - Jump to previous word".to_string(),
-            "  g                  - Go to beginning of file".to_string(),
+            "  gg                 - Go to beginning of file".to_string(),
             "  G                  - Go to end of file".to_string(),
+            "  %                  - Jump to matching bracket or block keyword".to_string(),
+            "  m{a-z}, '{a-z}     - Set a mark on the line / jump to the mark".to_string(),
             "  0                  - Go to beginning of line".to_string(),
             "  $                  - Go to end of line".to_string(),
             "".to_string(),
             "Editing:".to_string(),
             "  i                  - Enter insert mode".to_string(),
             "  a                  - Enter insert mode after cursor".to_string(),
+            "  A / I              - Insert at end of line / before first non-blank".to_string(),
             "  o                  - Insert new line below and enter insert mode".to_string(),
+            "  O                  - Insert new line above and enter insert mode".to_string(),
+            "  >> / <<            - Shift line right / left".to_string(),
+            "  > / < (visual)     - Shift selected lines right / left".to_string(),
+            "  gcc                - Toggle comment on current line".to_string(),
+            "  gc{motion}         - Toggle comment over motion (e.g. gcj, gcG)".to_string(),
+            "  gc (visual)        - Toggle comment on selected lines".to_string(),
+            "  gqq / gqip         - Reflow line / paragraph to textwidth".to_string(),
+            "  gq{motion}         - Reflow lines over motion (e.g. gqj, gqG)".to_string(),
+            "  gq (visual)        - Reflow selected lines".to_string(),
+            "  [count]Ctrl+A / Ctrl+X - Increment / decrement number at or after cursor"
+                .to_string(),
+            "  Ctrl+A / Ctrl+X (visual) - Increment / decrement numbers in selection".to_string(),
+            "  g Ctrl+A (visual)  - Increment numbers as a sequence (1, 2, 3...)".to_string(),
+            "  [count]~           - Toggle case of characters under cursor".to_string(),
+            "  gu / gU / g~{motion} - Lowercase / uppercase / toggle case over motion".to_string(),
+            "  guu / gUU / g~~    - Change case of current line".to_string(),
+            "  u / U / ~ (visual) - Lowercase / uppercase / toggle case of selection".to_string(),
+            "  [count]J / gJ      - Join lines with / without adjusting spaces".to_string(),
             "  x                  - Delete character under cursor".to_string(),
             "".to_string(),
+            "Folding:".to_string(),
+            "  zf{motion} / zf    - Create fold over motion / selection".to_string(),
+            "  zo / zc / za       - Open / close / toggle fold under cursor".to_string(),
+            "  zR / zM            - Open / close all folds".to_string(),
+            "".to_string(),
             "Clipboard Operations:".to_string(),
             "  Ctrl+C             - Copy selection".to_string(),
             "  Ctrl+X             - Cut selection".to_string(),
             "  Ctrl+V             - Paste from clipboard".to_string(),
-            "  Ctrl+A             - Select all text".to_string(),
+            "  Ctrl+Shift+A       - Select all text (Ctrl+A in insert mode; many".to_string(),
+            "                       terminals send Ctrl+Shift+A as Ctrl+A)".to_string(),
             "".to_string(),
             "Special Keys:".to_string(),
             "  Esc                - Return to normal mode / Close this help".to_string(),
@@ -628,7 +2921,7 @@ impl HelpWindow {
             "  Enter              - Next search result (when searching)".to_string(),
             "  Shift+Enter        - Previous search result (when searching)".to_string(),
             "  F2                 - Toggle file explorer".to_string(),
-            "  Ctrl+Q             - Quit editor".to_string(),
+            "  Ctrl+Q             - Quit editor (like :q)".to_string(),
             "".to_string(),
             "Examples:".to_string(),
             "  :set rnu           - Enable relative line numbers".to_string(),
@@ -669,10 +2962,6 @@ impl HelpWindow {
             self.scroll_offset += 1;
         }
     }
-
-    fn default() -> Self {
-        Self::new()
-    }
 }
 
 impl Default for FileChangeDialog {
@@ -711,10 +3000,6 @@ impl FileChangeDialog {
     pub fn get_selected_option(&self) -> usize {
         self.selected_option
     }
-
-    fn default() -> Self {
-        Self::new()
-    }
 }
 
 impl Default for App {
@@ -722,3 +3007,94 @@ impl Default for App {
         Self::new().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An editor showing `text` in a new unnamed buffer.
+    fn app_with(text: &str) -> App {
+        let mut app = App::with_config(Config::default()).unwrap();
+        let mut buffer = Buffer::empty();
+        buffer.content = Rope::from_str(text);
+        app.buffers.push(buffer);
+        app.current_buffer = app.buffers.len() - 1;
+        app
+    }
+
+    fn text(app: &App) -> String {
+        app.current_buffer().content.to_string()
+    }
+
+    #[test]
+    fn test_join_keeps_final_newline() {
+        let mut app = app_with("one\ntwo\n");
+        app.join_lines(1, 1, true);
+        assert_eq!(text(&app), "one\ntwo\n");
+        app.join_lines(0, 3, true);
+        assert_eq!(text(&app), "one two\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_filter_keeps_final_newline() {
+        use crate::input::range::{Range, RangeContext};
+
+        let mut app = app_with("b\na\n");
+        let range = Range::Whole
+            .resolve(&RangeContext::new(app.current_buffer(), 0, None))
+            .unwrap();
+        app.filter_lines(range.start, range.end, "sort");
+        assert_eq!(text(&app), "a\nb\n");
+    }
+
+    #[test]
+    fn test_write_whole_range_saves_own_file() {
+        use crate::input::range::{Range, RangeContext};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut app = app_with("one\ntwo\n");
+        app.current_buffer_mut().file_path = Some(path.clone());
+        app.current_buffer_mut().is_modified = true;
+
+        // `:%w`
+        let range = Range::Whole
+            .resolve(&RangeContext::new(app.current_buffer(), 0, None))
+            .unwrap();
+        let write = FileWrite {
+            lines: Some((range.start, range.end)),
+            ..FileWrite::default()
+        };
+        assert!(app.write_file(&write, false, false));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        assert!(!app.current_buffer().is_modified);
+    }
+
+    #[test]
+    fn test_split_and_tab_windows_keep_their_cursor() {
+        let mut app = app_with("one\ntwo\nthree\n");
+        assert!(app.split_window(false));
+        assert!(!app.split_window(true));
+        app.cursor.line = 2;
+        app.switch_window();
+        assert_eq!(app.cursor.line, 0);
+        app.switch_window();
+        assert_eq!(app.cursor.line, 2);
+
+        assert!(app.new_tab());
+        assert!(app.split.is_none());
+        app.cursor.line = 1;
+        app.switch_tab(false);
+        assert_eq!((app.tab_index, app.cursor.line), (0, 2));
+        assert!(app.split.is_some());
+        app.switch_tab(true);
+        assert_eq!((app.tab_index, app.cursor.line), (1, 1));
+
+        assert!(app.close_tab());
+        assert!(app.close_window());
+        assert!(!app.close_window());
+        assert_eq!(app.cursor.line, 0);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::Result;
+
+pub const DEFAULT_LISTCHARS: &str = "tab:> ,trail:-,nbsp:+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TabGlyphs {
+    pub head: char,
+    pub fill: char,
+    pub tail: Option<char>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ListChars {
+    pub tab: Option<TabGlyphs>,
+    pub trail: Option<char>,
+    pub nbsp: Option<char>,
+    pub eol: Option<char>,
+}
+
+impl ListChars {
+    /// Parses a Vim-style `listchars` value, e.g. `tab:> ,trail:-,eol:$`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut list_chars = Self::default();
+
+        for item in value.split(',').filter(|item| !item.is_empty()) {
+            let (name, glyphs) = item
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("Invalid listchars item: {}", item))?;
+            let glyphs: Vec<char> = glyphs.chars().collect();
+
+            match name {
+                "tab" => {
+                    if glyphs.len() < 2 || glyphs.len() > 3 {
+                        return Err(anyhow::anyhow!(
+                            "listchars tab needs two or three characters: {}",
+                            item
+                        ));
+                    }
+                    list_chars.tab = Some(TabGlyphs {
+                        head: glyphs[0],
+                        fill: glyphs[1],
+                        tail: glyphs.get(2).copied(),
+                    });
+                }
+                "trail" | "nbsp" | "eol" => {
+                    if glyphs.len() != 1 {
+                        return Err(anyhow::anyhow!(
+                            "listchars {} needs exactly one character: {}",
+                            name,
+                            item
+                        ));
+                    }
+                    let glyph = Some(glyphs[0]);
+                    match name {
+                        "trail" => list_chars.trail = glyph,
+                        "nbsp" => list_chars.nbsp = glyph,
+                        _ => list_chars.eol = glyph,
+                    }
+                }
+                _ => return Err(anyhow::anyhow!("Unknown listchars item: {}", name)),
+            }
+        }
+
+        Ok(list_chars)
+    }
+
+    /// Renders a tab that spans `width` display columns.
+    pub fn render_tab(&self, width: usize) -> Option<String> {
+        let tab = self.tab?;
+        let mut rendered = String::with_capacity(width);
+        for i in 0..width {
+            let glyph = match tab.tail {
+                Some(tail) if i + 1 == width => tail,
+                _ if i == 0 => tab.head,
+                _ => tab.fill,
+            };
+            rendered.push(glyph);
+        }
+        Some(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_listchars() {
+        let list_chars = ListChars::parse(DEFAULT_LISTCHARS).unwrap();
+        assert_eq!(list_chars.trail, Some('-'));
+        assert_eq!(list_chars.nbsp, Some('+'));
+        assert_eq!(list_chars.eol, None);
+        assert_eq!(list_chars.render_tab(4).as_deref(), Some(">   "));
+    }
+
+    #[test]
+    fn test_render_tab_with_tail() {
+        let list_chars = ListChars::parse("tab:<->,eol:$").unwrap();
+        assert_eq!(list_chars.render_tab(4).as_deref(), Some("<-->"));
+        assert_eq!(list_chars.render_tab(1).as_deref(), Some(">"));
+        assert_eq!(list_chars.eol, Some('$'));
+    }
+
+    #[test]
+    fn test_parse_invalid_listchars() {
+        assert!(ListChars::parse("tab:>").is_err());
+        assert!(ListChars::parse("space:.").is_err());
+        assert!(ListChars::parse("trail").is_err());
+    }
+}
@@ -1,3 +1,6 @@
+pub mod listchars;
+pub mod options;
 pub mod settings;
 
+pub use listchars::ListChars;
 pub use settings::Config;
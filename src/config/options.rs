@@ -0,0 +1,853 @@
+use crate::config::listchars::ListChars;
+use crate::config::settings::{Config, EditorConfig, UiConfig};
+use crate::editor::fold::FoldSet;
+use crate::editor::matching::MatchPairs;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where an option's value lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionScope {
+    Global,
+    /// Can be overridden per buffer with `:setlocal`, `.editorconfig` or
+    /// `[filetype.<name>]` sections.
+    Buffer,
+    /// Can be overridden for the window with `:setlocal`.
+    Window,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionType {
+    Bool,
+    Number {
+        min: usize,
+        max: usize,
+    },
+    /// Free text; `+=` appends and `-=` removes a substring.
+    Text,
+    /// Comma-separated items; `+=` adds and `-=` removes an item.
+    List,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OptionValue {
+    Bool(bool),
+    Number(usize),
+    Text(String),
+}
+
+impl OptionValue {
+    fn flag(&self) -> bool {
+        matches!(self, Self::Bool(true))
+    }
+
+    fn number(&self) -> usize {
+        match self {
+            Self::Number(value) => *value,
+            _ => 0,
+        }
+    }
+
+    fn into_text(self) -> String {
+        match self {
+            Self::Text(value) => value,
+            other => other.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Text(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl From<bool> for OptionValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<usize> for OptionValue {
+    fn from(value: usize) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<&String> for OptionValue {
+    fn from(value: &String) -> Self {
+        Self::Text(value.clone())
+    }
+}
+
+/// Reads and writes the config field behind an option.
+pub enum Field {
+    Editor {
+        get: fn(&EditorConfig) -> OptionValue,
+        set: fn(&mut EditorConfig, OptionValue),
+    },
+    Ui {
+        get: fn(&UiConfig) -> OptionValue,
+        set: fn(&mut UiConfig, OptionValue),
+    },
+}
+
+pub struct OptionDef {
+    /// Canonical name, matching the field in `config.toml`.
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub kind: OptionType,
+    pub scope: OptionScope,
+    pub default: &'static str,
+    pub help: &'static str,
+    /// Known values, offered by completion.
+    pub values: &'static [&'static str],
+    validate: Option<fn(&str) -> Result<()>>,
+    field: Field,
+}
+
+pub static OPTIONS: &[OptionDef] = &[
+    OptionDef {
+        name: "line_numbers",
+        aliases: &["nu", "number"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Window,
+        default: "true",
+        help: "Show line numbers",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.line_numbers.into(),
+            set: |c, v| c.line_numbers = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "relative_line_numbers",
+        aliases: &["rnu", "relativenumber"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Window,
+        default: "false",
+        help: "Show line numbers relative to the cursor",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.relative_line_numbers.into(),
+            set: |c, v| c.relative_line_numbers = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "tab_size",
+        aliases: &["ts", "tabsize", "tabstop"],
+        kind: OptionType::Number { min: 1, max: 16 },
+        scope: OptionScope::Buffer,
+        default: "4",
+        help: "Width of a tab and of one indent level",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.tab_size.into(),
+            set: |c, v| c.tab_size = v.number(),
+        },
+    },
+    OptionDef {
+        name: "expandtab",
+        aliases: &["et"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Buffer,
+        default: "true",
+        help: "Indent with spaces instead of tabs",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.expandtab.into(),
+            set: |c, v| c.expandtab = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "auto_save",
+        aliases: &["autosave"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "false",
+        help: "Save modified buffers automatically",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.auto_save.into(),
+            set: |c, v| c.auto_save = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "wrap_lines",
+        aliases: &["wrap"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Window,
+        default: "false",
+        help: "Wrap lines longer than the window",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.wrap_lines.into(),
+            set: |c, v| c.wrap_lines = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "scroll_offset",
+        aliases: &["so", "scrolloff", "scrolloffset"],
+        kind: OptionType::Number { min: 0, max: 20 },
+        scope: OptionScope::Window,
+        default: "5",
+        help: "Lines kept visible above and below the cursor",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.scroll_offset.into(),
+            set: |c, v| c.scroll_offset = v.number(),
+        },
+    },
+    OptionDef {
+        name: "syntax_highlighting",
+        aliases: &["syntax"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "true",
+        help: "Highlight syntax",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.syntax_highlighting.into(),
+            set: |c, v| c.syntax_highlighting = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "fast_command_line",
+        aliases: &["fastcl", "fastcommandline"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "false",
+        help: "Skip the command line animation",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.fast_command_line.into(),
+            set: |c, v| c.fast_command_line = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "list",
+        aliases: &[],
+        kind: OptionType::Bool,
+        scope: OptionScope::Window,
+        default: "false",
+        help: "Show tabs, trailing spaces and nbsp",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.list.into(),
+            set: |c, v| c.list = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "listchars",
+        aliases: &["lcs"],
+        kind: OptionType::List,
+        scope: OptionScope::Global,
+        default: crate::config::listchars::DEFAULT_LISTCHARS,
+        help: "Glyphs used by list, e.g. tab:>-,trail:~,eol:$",
+        values: &[],
+        validate: Some(|value| ListChars::parse(value).map(|_| ())),
+        field: Field::Editor {
+            get: |c| (&c.listchars).into(),
+            set: |c, v| c.listchars = v.into_text(),
+        },
+    },
+    OptionDef {
+        name: "autoindent",
+        aliases: &["ai"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Buffer,
+        default: "true",
+        help: "Copy indentation to new lines",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.autoindent.into(),
+            set: |c, v| c.autoindent = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "smartindent",
+        aliases: &["si"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Buffer,
+        default: "true",
+        help: "Indent after {, ( or : and dedent on closers",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.smartindent.into(),
+            set: |c, v| c.smartindent = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "auto_pairs",
+        aliases: &["ap", "autopairs"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Buffer,
+        default: "true",
+        help: "Auto-close brackets and quotes",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.auto_pairs.into(),
+            set: |c, v| c.auto_pairs = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "pairs",
+        aliases: &[],
+        kind: OptionType::Text,
+        scope: OptionScope::Buffer,
+        default: "()[]{}\"\"''",
+        help: "Characters to auto-pair, as opener/closer pairs",
+        values: &[],
+        validate: Some(|value| {
+            if value.chars().count().is_multiple_of(2) {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "pairs must list opener/closer characters in pairs, e.g. ()[]{{}}"
+                ))
+            }
+        }),
+        field: Field::Editor {
+            get: |c| (&c.pairs).into(),
+            set: |c, v| c.pairs = v.into_text(),
+        },
+    },
+    OptionDef {
+        name: "matchpairs",
+        aliases: &["mps"],
+        kind: OptionType::List,
+        scope: OptionScope::Buffer,
+        default: crate::editor::matching::DEFAULT_MATCHPAIRS,
+        help: "Bracket pairs matched by % and highlighting",
+        values: &[],
+        validate: Some(|value| MatchPairs::parse(value).map(|_| ())),
+        field: Field::Editor {
+            get: |c| (&c.matchpairs).into(),
+            set: |c, v| c.matchpairs = v.into_text(),
+        },
+    },
+    OptionDef {
+        name: "foldmethod",
+        aliases: &["fdm"],
+        kind: OptionType::Text,
+        scope: OptionScope::Window,
+        default: "manual",
+        help: "How folds are created",
+        values: &["manual", "indent", "marker", "syntax"],
+        validate: Some(FoldSet::validate_method),
+        field: Field::Editor {
+            get: |c| (&c.foldmethod).into(),
+            set: |c, v| c.foldmethod = v.into_text(),
+        },
+    },
+    OptionDef {
+        name: "linebreak",
+        aliases: &["lbr"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Window,
+        default: "true",
+        help: "Wrap long lines at word boundaries",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.linebreak.into(),
+            set: |c, v| c.linebreak = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "breakindent",
+        aliases: &["bri"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Window,
+        default: "true",
+        help: "Indent wrapped lines like the original line",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.breakindent.into(),
+            set: |c, v| c.breakindent = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "showbreak",
+        aliases: &["sbr"],
+        kind: OptionType::Text,
+        scope: OptionScope::Global,
+        default: "",
+        help: "Marker shown before wrapped lines",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| (&c.showbreak).into(),
+            set: |c, v| c.showbreak = v.into_text(),
+        },
+    },
+    OptionDef {
+        name: "textwidth",
+        aliases: &["tw"],
+        kind: OptionType::Number {
+            min: 0,
+            max: usize::MAX,
+        },
+        scope: OptionScope::Buffer,
+        default: "0",
+        help: "Break lines while typing past this column (0 disables)",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.textwidth.into(),
+            set: |c, v| c.textwidth = v.number(),
+        },
+    },
+    OptionDef {
+        name: "ignorecase",
+        aliases: &["ic"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "false",
+        help: "Ignore case in search patterns",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.ignorecase.into(),
+            set: |c, v| c.ignorecase = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "smartcase",
+        aliases: &["scs"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "false",
+        help: "Match case when the pattern has uppercase letters (with ignorecase)",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.smartcase.into(),
+            set: |c, v| c.smartcase = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "confirm",
+        aliases: &["cf"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "false",
+        help: "Ask before quitting with unsaved changes or overwriting a file",
+        values: &[],
+        validate: None,
+        field: Field::Editor {
+            get: |c| c.confirm.into(),
+            set: |c, v| c.confirm = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "cursor_blink",
+        aliases: &["cursorblink"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "true",
+        help: "Blink the cursor",
+        values: &[],
+        validate: None,
+        field: Field::Ui {
+            get: |c| c.cursor_blink.into(),
+            set: |c, v| c.cursor_blink = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "show_status_line",
+        aliases: &["statusline"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "true",
+        help: "Show the status line",
+        values: &[],
+        validate: None,
+        field: Field::Ui {
+            get: |c| c.show_status_line.into(),
+            set: |c, v| c.show_status_line = v.flag(),
+        },
+    },
+    OptionDef {
+        name: "show_command_line",
+        aliases: &["commandline"],
+        kind: OptionType::Bool,
+        scope: OptionScope::Global,
+        default: "true",
+        help: "Show the command line",
+        values: &[],
+        validate: None,
+        field: Field::Ui {
+            get: |c| c.show_command_line.into(),
+            set: |c, v| c.show_command_line = v.flag(),
+        },
+    },
+];
+
+/// Finds an option by name or alias, ignoring case.
+pub fn lookup(name: &str) -> Option<&'static OptionDef> {
+    OPTIONS.iter().find(|option| {
+        option.name.eq_ignore_ascii_case(name)
+            || option
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    })
+}
+
+/// Option names and aliases starting with `prefix`, including the `no` forms
+/// of boolean options.
+pub fn complete_names(prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = OPTIONS
+        .iter()
+        .flat_map(|option| {
+            let names = std::iter::once(option.name).chain(option.aliases.iter().copied());
+            let negated = names
+                .clone()
+                .filter(|_| option.kind == OptionType::Bool)
+                .map(|name| format!("no{name}"));
+            names.map(String::from).chain(negated).collect::<Vec<_>>()
+        })
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names
+}
+
+impl OptionDef {
+    pub fn is_editor_option(&self) -> bool {
+        matches!(self.field, Field::Editor { .. })
+    }
+
+    pub fn get(&self, config: &Config) -> OptionValue {
+        match self.field {
+            Field::Editor { get, .. } => get(&config.editor),
+            Field::Ui { get, .. } => get(&config.ui),
+        }
+    }
+
+    /// Stores an already parsed value; see [`OptionDef::parse`].
+    pub fn set(&self, config: &mut Config, value: OptionValue) {
+        match self.field {
+            Field::Editor { set, .. } => set(&mut config.editor, value),
+            Field::Ui { set, .. } => set(&mut config.ui, value),
+        }
+    }
+
+    /// Reads an editor option from `editor`, or `None` for UI options.
+    pub fn get_editor(&self, editor: &EditorConfig) -> Option<OptionValue> {
+        match self.field {
+            Field::Editor { get, .. } => Some(get(editor)),
+            Field::Ui { .. } => None,
+        }
+    }
+
+    pub fn set_editor(&self, editor: &mut EditorConfig, value: OptionValue) {
+        if let Field::Editor { set, .. } = self.field {
+            set(editor, value);
+        }
+    }
+
+    pub fn default_value(&self) -> OptionValue {
+        self.parse(self.default)
+            .unwrap_or_else(|_| OptionValue::Text(self.default.to_string()))
+    }
+
+    /// Parses and validates a value given as text.
+    pub fn parse(&self, text: &str) -> Result<OptionValue> {
+        match self.kind {
+            OptionType::Bool => match text.to_lowercase().as_str() {
+                "true" | "1" => Ok(OptionValue::Bool(true)),
+                "false" | "0" => Ok(OptionValue::Bool(false)),
+                _ => Err(anyhow::anyhow!(
+                    "Invalid value for {}: {} (use true/false)",
+                    self.name,
+                    text
+                )),
+            },
+            OptionType::Number { min, max } => {
+                let value: usize = text
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid number for {}: {}", self.name, text))?;
+                if value < min || value > max {
+                    return Err(anyhow::anyhow!(
+                        "{} must be between {} and {}",
+                        self.name,
+                        min,
+                        max
+                    ));
+                }
+                Ok(OptionValue::Number(value))
+            }
+            OptionType::Text | OptionType::List => {
+                if let Some(validate) = self.validate {
+                    validate(text)?;
+                }
+                Ok(OptionValue::Text(text.to_string()))
+            }
+        }
+    }
+
+    /// Checks a value loaded from `config.toml`, e.g. in a `[filetype]` section.
+    pub fn check(&self, value: &OptionValue) -> Result<OptionValue> {
+        let matches_kind = matches!(
+            (self.kind, value),
+            (OptionType::Bool, OptionValue::Bool(_))
+                | (OptionType::Number { .. }, OptionValue::Number(_))
+                | (OptionType::Text | OptionType::List, OptionValue::Text(_))
+        );
+        if !matches_kind {
+            return Err(anyhow::anyhow!(
+                "Invalid value for {}: {}",
+                self.name,
+                value
+            ));
+        }
+        self.parse(&value.to_string())
+    }
+
+    /// `:set opt+=value`: adds to a number, appends to text or adds a list item.
+    pub fn add(&self, current: &OptionValue, text: &str) -> Result<OptionValue> {
+        match (self.kind, current) {
+            (OptionType::Number { .. }, OptionValue::Number(value)) => {
+                let delta: usize = text
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid number for {}: {}", self.name, text))?;
+                self.parse(&value.saturating_add(delta).to_string())
+            }
+            (OptionType::Text, OptionValue::Text(value)) => self.parse(&format!("{value}{text}")),
+            (OptionType::List, OptionValue::Text(value)) => {
+                let mut items: Vec<&str> =
+                    value.split(',').filter(|item| !item.is_empty()).collect();
+                if !items.contains(&text) {
+                    items.push(text);
+                }
+                self.parse(&items.join(","))
+            }
+            _ => Err(anyhow::anyhow!("Cannot add to {}", self.name)),
+        }
+    }
+
+    /// `:set opt-=value`: subtracts from a number or removes text or a list item.
+    pub fn remove(&self, current: &OptionValue, text: &str) -> Result<OptionValue> {
+        match (self.kind, current) {
+            (OptionType::Number { .. }, OptionValue::Number(value)) => {
+                let delta: usize = text
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid number for {}: {}", self.name, text))?;
+                self.parse(&value.saturating_sub(delta).to_string())
+            }
+            (OptionType::Text, OptionValue::Text(value)) => {
+                self.parse(&value.replacen(text, "", 1))
+            }
+            (OptionType::List, OptionValue::Text(value)) => {
+                let items: Vec<&str> = value
+                    .split(',')
+                    .filter(|item| !item.is_empty() && *item != text)
+                    .collect();
+                self.parse(&items.join(","))
+            }
+            _ => Err(anyhow::anyhow!("Cannot remove from {}", self.name)),
+        }
+    }
+}
+
+/// What a `:set` argument asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetAction {
+    /// `opt?`, or `opt` for a non-boolean option.
+    Show,
+    /// `opt` for a boolean option.
+    Enable,
+    /// `noopt`.
+    Disable,
+    /// `opt!` or `invopt`.
+    Toggle,
+    /// `opt&`: back to the default value.
+    Reset,
+    /// `opt<` (`:setlocal` only): drop the local value.
+    UseGlobal,
+    Assign(String),
+    Add(String),
+    Remove(String),
+}
+
+/// Parses one `:set` argument such as `ts=4`, `noet`, `wrap!` or `lcs+=eol:$`.
+pub fn parse_set_argument(arg: &str) -> Result<(&'static OptionDef, SetAction)> {
+    let unknown = |name: &str| anyhow::anyhow!("Unknown option: {}", name);
+
+    if let Some(eq) = arg.find('=') {
+        let (name, action) = if let Some(name) = arg[..eq].strip_suffix('+') {
+            (name, SetAction::Add(arg[eq + 1..].to_string()))
+        } else if let Some(name) = arg[..eq].strip_suffix('-') {
+            (name, SetAction::Remove(arg[eq + 1..].to_string()))
+        } else {
+            (&arg[..eq], SetAction::Assign(arg[eq + 1..].to_string()))
+        };
+        return Ok((lookup(name).ok_or_else(|| unknown(name))?, action));
+    }
+
+    let suffixed = [
+        ('?', SetAction::Show),
+        ('!', SetAction::Toggle),
+        ('&', SetAction::Reset),
+        ('<', SetAction::UseGlobal),
+    ];
+    for (suffix, action) in suffixed {
+        if let Some(name) = arg.strip_suffix(suffix) {
+            let option = lookup(name).ok_or_else(|| unknown(name))?;
+            if action == SetAction::Toggle && option.kind != OptionType::Bool {
+                return Err(anyhow::anyhow!("{} is not a boolean option", option.name));
+            }
+            return Ok((option, action));
+        }
+    }
+
+    if let Some(option) = lookup(arg) {
+        let action = if option.kind == OptionType::Bool {
+            SetAction::Enable
+        } else {
+            SetAction::Show
+        };
+        return Ok((option, action));
+    }
+
+    let prefixed = [("no", SetAction::Disable), ("inv", SetAction::Toggle)];
+    for (prefix, action) in prefixed {
+        if let Some(option) = arg.strip_prefix(prefix).and_then(lookup) {
+            if option.kind == OptionType::Bool {
+                return Ok((option, action));
+            }
+        }
+    }
+
+    Err(unknown(arg))
+}
+
+/// Local overrides of option values, keyed by canonical option name. Used
+/// for `[filetype.<name>]` sections of `config.toml`, `.editorconfig` files
+/// and `:setlocal`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LocalOptions(BTreeMap<String, OptionValue>);
+
+impl LocalOptions {
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        self.0.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: OptionValue) {
+        self.0.insert(name.to_string(), value);
+    }
+
+    pub fn clear(&mut self, name: &str) {
+        self.0.remove(name);
+    }
+
+    /// Applies the local values to `editor`, skipping unknown options and
+    /// values of the wrong type.
+    pub fn apply(&self, editor: &mut EditorConfig) {
+        for (name, value) in &self.0 {
+            let Some(option) = lookup(name) else {
+                continue;
+            };
+            match option.check(value) {
+                Ok(value) => option.set_editor(editor, value),
+                Err(e) => log::warn!("Ignoring local option: {e}"),
+            }
+        }
+    }
+
+    /// Returns `self` with the values set in `overrides` taking precedence.
+    pub fn merged(&self, overrides: &LocalOptions) -> LocalOptions {
+        let mut merged = self.clone();
+        merged.0.extend(
+            overrides
+                .0
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        merged
+    }
+
+    /// `name = value` lines for every local value.
+    pub fn display(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|(name, value)| format!("  {name} = {value}"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_config() {
+        let config = Config::default();
+        for option in OPTIONS {
+            assert_eq!(
+                option.get(&config),
+                option.default_value(),
+                "{}",
+                option.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_set_arguments() {
+        let parse = |arg| {
+            parse_set_argument(arg)
+                .map(|(option, action)| (option.name, action))
+                .unwrap()
+        };
+        assert_eq!(parse("ts=2"), ("tab_size", SetAction::Assign("2".into())));
+        assert_eq!(parse("noet"), ("expandtab", SetAction::Disable));
+        assert_eq!(parse("invwrap"), ("wrap_lines", SetAction::Toggle));
+        assert_eq!(parse("list!"), ("list", SetAction::Toggle));
+        assert_eq!(parse("tw"), ("textwidth", SetAction::Show));
+        assert_eq!(parse("fdm&"), ("foldmethod", SetAction::Reset));
+        assert_eq!(
+            parse("so-=2"),
+            ("scroll_offset", SetAction::Remove("2".into()))
+        );
+        assert!(parse_set_argument("nots").is_err());
+        assert!(parse_set_argument("ts!").is_err());
+
+        let lcs = lookup("lcs").unwrap();
+        let current = OptionValue::Text("tab:> ,trail:-".into());
+        let added = lcs.add(&current, "eol:$").unwrap();
+        assert_eq!(added, OptionValue::Text("tab:> ,trail:-,eol:$".into()));
+        assert_eq!(
+            lcs.remove(&added, "trail:-").unwrap(),
+            OptionValue::Text("tab:> ,eol:$".into())
+        );
+        assert!(lookup("ts")
+            .unwrap()
+            .add(&OptionValue::Number(15), "4")
+            .is_err());
+    }
+}
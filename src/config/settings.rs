@@ -1,7 +1,12 @@
+use crate::config::listchars::{ListChars, DEFAULT_LISTCHARS};
+use crate::config::options::{self, LocalOptions, OptionDef, OptionValue, OPTIONS};
+use crate::editor::matching::{MatchPairs, DEFAULT_MATCHPAIRS};
+use crate::input::user_commands;
 use crate::ui::{NeoTheme, Theme, ThemeManager};
 use crate::Result;
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +21,16 @@ pub struct Config {
     #[serde(skip)]
     pub theme_manager: ThemeManager,
     pub theme_path: Option<PathBuf>,
+    /// `[filetype.<name>]` sections, keyed by lowercase syntax name.
+    #[serde(default)]
+    pub filetype: HashMap<String, LocalOptions>,
+    /// `[commands]`: user-defined ex commands, name to replacement, e.g.
+    /// `Fmt = "%!rustfmt"`.
+    #[serde(default)]
+    pub commands: HashMap<String, String>,
+    /// `[abbreviations]`: insert-mode abbreviations, word to expansion.
+    #[serde(default)]
+    pub abbreviations: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,12 +38,79 @@ pub struct EditorConfig {
     pub line_numbers: bool,
     pub relative_line_numbers: bool,
     pub tab_size: usize,
-    pub insert_tabs: bool,
+    /// Indent with spaces. Named after Vim's option rather than the old
+    /// `insert_tabs` key, which was never read and is ignored if present.
+    #[serde(default = "default_true")]
+    pub expandtab: bool,
     pub auto_save: bool,
     pub wrap_lines: bool,
     pub scroll_offset: usize,
     pub syntax_highlighting: bool,
     pub fast_command_line: bool,
+    #[serde(default)]
+    pub list: bool,
+    #[serde(default = "default_listchars")]
+    pub listchars: String,
+    #[serde(default = "default_true")]
+    pub autoindent: bool,
+    #[serde(default = "default_true")]
+    pub smartindent: bool,
+    #[serde(default = "default_true")]
+    pub auto_pairs: bool,
+    #[serde(default = "default_pairs")]
+    pub pairs: String,
+    #[serde(default = "default_filetype_pairs")]
+    pub filetype_pairs: HashMap<String, String>,
+    #[serde(default = "default_matchpairs")]
+    pub matchpairs: String,
+    #[serde(default)]
+    pub comment_tokens: HashMap<String, String>,
+    #[serde(default = "default_foldmethod")]
+    pub foldmethod: String,
+    #[serde(default = "default_true")]
+    pub linebreak: bool,
+    #[serde(default = "default_true")]
+    pub breakindent: bool,
+    #[serde(default)]
+    pub showbreak: String,
+    #[serde(default)]
+    pub textwidth: usize,
+    #[serde(default)]
+    pub ignorecase: bool,
+    #[serde(default)]
+    pub smartcase: bool,
+    /// Ask instead of failing when quitting with unsaved changes or writing
+    /// over an existing file.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+fn default_listchars() -> String {
+    DEFAULT_LISTCHARS.to_string()
+}
+
+fn default_matchpairs() -> String {
+    DEFAULT_MATCHPAIRS.to_string()
+}
+
+fn default_foldmethod() -> String {
+    "manual".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_pairs() -> String {
+    "()[]{}\"\"''".to_string()
+}
+
+fn default_filetype_pairs() -> HashMap<String, String> {
+    let mut filetype_pairs = HashMap::new();
+    filetype_pairs.insert("Rust".to_string(), "()[]{}\"\"".to_string());
+    filetype_pairs.insert("Lisp".to_string(), "()[]{}\"\"".to_string());
+    filetype_pairs.insert("Markdown".to_string(), "()[]{}\"\"``".to_string());
+    filetype_pairs
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,6 +138,15 @@ impl Config {
                     toml::from_str(&content).with_context(|| "Failed to parse config file")?;
 
                 config.theme_manager = ThemeManager::new();
+                config.commands.retain(|name, _| {
+                    let valid = user_commands::is_valid_name(name);
+                    if !valid {
+                        log::warn!(
+                            "Ignoring user command {name}: names start with an uppercase letter"
+                        );
+                    }
+                    valid
+                });
 
                 config.current_theme = if let Some(ref theme_path) = config.theme_path {
                     NeoTheme::load_from_file(theme_path).unwrap_or_else(|_| NeoTheme::default())
@@ -172,159 +263,62 @@ impl Config {
         Ok(())
     }
 
-    pub fn set_line_numbers(&mut self, value: bool) -> Result<()> {
-        self.editor.line_numbers = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_relative_line_numbers(&mut self, value: bool) -> Result<()> {
-        self.editor.relative_line_numbers = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_tab_size(&mut self, value: usize) -> Result<()> {
-        if value > 0 && value <= 16 {
-            self.editor.tab_size = value;
-            self.save()?;
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Tab size must be between 1 and 16"))
+    /// Options from the `[filetype.<name>]` section for a syntax name such
+    /// as `Rust`, matched case-insensitively, on top of the language's
+    /// `filetype_pairs` entry as its `pairs`.
+    pub fn filetype_options(&self, syntax_name: &str) -> LocalOptions {
+        let mut options = LocalOptions::default();
+        if let Some(pairs) = self.editor.filetype_pairs.get(syntax_name) {
+            options.set("pairs", OptionValue::Text(pairs.clone()));
         }
+        let section = self
+            .filetype
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(syntax_name))
+            .map(|(_, options)| options.clone())
+            .unwrap_or_default();
+        options.merged(&section)
     }
 
-    pub fn set_insert_tabs(&mut self, value: bool) -> Result<()> {
-        self.editor.insert_tabs = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_auto_save(&mut self, value: bool) -> Result<()> {
-        self.editor.auto_save = value;
-        self.save()?;
-        Ok(())
+    pub fn match_pairs(&self) -> MatchPairs {
+        MatchPairs::parse(&self.editor.matchpairs).unwrap_or_default()
     }
 
-    pub fn set_wrap_lines(&mut self, value: bool) -> Result<()> {
-        self.editor.wrap_lines = value;
-        self.save()?;
-        Ok(())
+    pub fn list_chars(&self) -> ListChars {
+        ListChars::parse(&self.editor.listchars).unwrap_or_default()
     }
 
-    pub fn set_scroll_offset(&mut self, value: usize) -> Result<()> {
-        if value <= 20 {
-            self.editor.scroll_offset = value;
-            self.save()?;
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Scroll offset must be 20 or less"))
-        }
-    }
-
-    pub fn set_syntax_highlighting(&mut self, value: bool) -> Result<()> {
-        self.editor.syntax_highlighting = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_cursor_blink(&mut self, value: bool) -> Result<()> {
-        self.ui.cursor_blink = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_fast_command_line(&mut self, value: bool) -> Result<()> {
-        self.editor.fast_command_line = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_show_status_line(&mut self, value: bool) -> Result<()> {
-        self.ui.show_status_line = value;
-        self.save()?;
-        Ok(())
-    }
-
-    pub fn set_show_command_line(&mut self, value: bool) -> Result<()> {
-        self.ui.show_command_line = value;
-        self.save()?;
-        Ok(())
+    /// Sets an option globally and saves the config.
+    pub fn set_option(&mut self, option: &OptionDef, value: OptionValue) -> Result<()> {
+        option.set(self, value);
+        self.save()
     }
 
     pub fn get_setting_display(&self, setting: &str) -> String {
-        match setting.to_lowercase().as_str() {
-            "linenumbers" | "line_numbers" | "nu" | "number" => {
-                format!("line_numbers = {}", self.editor.line_numbers)
-            }
-            "relativelinenumbers" | "relative_line_numbers" | "rnu" | "relativenumber" => {
-                format!(
-                    "relative_line_numbers = {}",
-                    self.editor.relative_line_numbers
-                )
-            }
-            "tabsize" | "tab_size" | "ts" => {
-                format!("tab_size = {}", self.editor.tab_size)
-            }
-            "inserttabs" | "insert_tabs" | "et" | "expandtab" => {
-                format!("insert_tabs = {}", self.editor.insert_tabs)
-            }
-            "autosave" | "auto_save" => {
-                format!("auto_save = {}", self.editor.auto_save)
-            }
-            "wraplines" | "wrap_lines" | "wrap" => {
-                format!("wrap_lines = {}", self.editor.wrap_lines)
-            }
-            "scrolloffset" | "scroll_offset" | "so" => {
-                format!("scroll_offset = {}", self.editor.scroll_offset)
-            }
-            "syntaxhighlighting" | "syntax_highlighting" | "syntax" => {
-                format!("syntax_highlighting = {}", self.editor.syntax_highlighting)
-            }
-            "cursorblink" | "cursor_blink" => {
-                format!("cursor_blink = {}", self.ui.cursor_blink)
-            }
-            "showstatusline" | "show_status_line" | "statusline" => {
-                format!("show_status_line = {}", self.ui.show_status_line)
-            }
-            "showcommandline" | "show_command_line" | "commandline" => {
-                format!("show_command_line = {}", self.ui.show_command_line)
-            }
-            "theme" => {
-                format!("theme = {}", self.ui.theme)
-            }
-            "fastcommandline" | "fast_command_line" | "fastcl" => {
-                format!("fast_command_line = {}", self.editor.fast_command_line)
-            }
-            _ => format!("Unknown setting: {setting}"),
+        if setting.eq_ignore_ascii_case("theme") {
+            return format!("theme = {}", self.ui.theme);
+        }
+        match options::lookup(setting) {
+            Some(option) => format!("{} = {}", option.name, option.get(self)),
+            None => format!("Unknown setting: {setting}"),
         }
     }
 
     pub fn get_all_settings_display(&self) -> Vec<String> {
-        vec![
-            format!("Editor Settings:"),
-            format!("  line_numbers = {}", self.editor.line_numbers),
-            format!(
-                "  relative_line_numbers = {}",
-                self.editor.relative_line_numbers
-            ),
-            format!("  tab_size = {}", self.editor.tab_size),
-            format!("  insert_tabs = {}", self.editor.insert_tabs),
-            format!("  auto_save = {}", self.editor.auto_save),
-            format!("  wrap_lines = {}", self.editor.wrap_lines),
-            format!("  scroll_offset = {}", self.editor.scroll_offset),
-            format!(
-                "  syntax_highlighting = {}",
-                self.editor.syntax_highlighting
-            ),
-            format!("  fast_command_line = {}", self.editor.fast_command_line),
-            format!(""),
-            format!("UI Settings:"),
-            format!("  cursor_blink = {}", self.ui.cursor_blink),
-            format!("  show_status_line = {}", self.ui.show_status_line),
-            format!("  show_command_line = {}", self.ui.show_command_line),
-            format!("  theme = {}", self.ui.theme),
-        ]
+        let display = |editor: bool| {
+            OPTIONS
+                .iter()
+                .filter(move |option| option.is_editor_option() == editor)
+                .map(|option| format!("  {} = {}", option.name, option.get(self)))
+        };
+
+        let mut lines = vec!["Editor Settings:".to_string()];
+        lines.extend(display(true));
+        lines.push(String::new());
+        lines.push("UI Settings:".to_string());
+        lines.extend(display(false));
+        lines.push(format!("  theme = {}", self.ui.theme));
+        lines
     }
 }
 
@@ -341,12 +335,29 @@ impl Default for Config {
                 line_numbers: true,
                 relative_line_numbers: false,
                 tab_size: 4,
-                insert_tabs: false,
+                expandtab: true,
                 auto_save: false,
                 wrap_lines: false,
                 scroll_offset: 5,
                 syntax_highlighting: true,
                 fast_command_line: false,
+                list: false,
+                listchars: default_listchars(),
+                autoindent: true,
+                smartindent: true,
+                auto_pairs: true,
+                pairs: default_pairs(),
+                filetype_pairs: default_filetype_pairs(),
+                matchpairs: default_matchpairs(),
+                comment_tokens: HashMap::new(),
+                foldmethod: default_foldmethod(),
+                linebreak: true,
+                breakindent: true,
+                showbreak: String::new(),
+                textwidth: 0,
+                ignorecase: false,
+                smartcase: false,
+                confirm: false,
             },
             ui: UiConfig {
                 theme: "dark".to_string(),
@@ -361,6 +372,41 @@ impl Default for Config {
             current_theme,
             theme_manager,
             theme_path: None,
+            filetype: HashMap::new(),
+            commands: HashMap::new(),
+            abbreviations: HashMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_insert_tabs_key_is_ignored() {
+        // Configs saved before `expandtab` existed carry `insert_tabs = false`,
+        // which never had an effect; they keep indenting with spaces.
+        let saved = toml::to_string_pretty(&Config::default())
+            .unwrap()
+            .replace("expandtab = true", "insert_tabs = false");
+        assert!(saved.contains("insert_tabs = false"));
+        let config: Config = toml::from_str(&saved).unwrap();
+        assert!(config.editor.expandtab);
+    }
+
+    #[test]
+    fn test_filetype_pairs_are_buffer_defaults() {
+        let mut config = Config::default();
+        let mut editor = config.editor.clone();
+        config.filetype_options("Rust").apply(&mut editor);
+        assert_eq!(editor.pairs, "()[]{}\"\"");
+
+        // A `[filetype.rust]` section overrides the built-in entry.
+        let mut section = LocalOptions::default();
+        section.set("pairs", OptionValue::Text("()".into()));
+        config.filetype.insert("rust".into(), section);
+        config.filetype_options("Rust").apply(&mut editor);
+        assert_eq!(editor.pairs, "()");
+    }
+}
@@ -1,9 +1,13 @@
+use crate::config::options::{LocalOptions, OptionValue};
+use crate::editor::fold::FoldSet;
 use crate::editor::{Cursor, Selection};
+use crate::file::editorconfig::{Charset, EditorConfigProperties, LineEnding};
 use crate::ui::components::terminal::TerminalOutput;
 use crate::Result;
 use anyhow::Context;
 use ropey::Rope;
 use std::collections::VecDeque;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
@@ -12,6 +16,52 @@ pub enum BufferType {
     Terminal,
 }
 
+/// How a buffer's text is encoded when written to disk.
+#[derive(Debug, Clone, Default)]
+pub struct FileFormat {
+    /// Line ending written for each newline; `None` writes the text as-is.
+    pub line_ending: Option<LineEnding>,
+    pub charset: Charset,
+    pub trim_trailing_whitespace: bool,
+    /// `Some(true)` ensures a final newline, `Some(false)` removes it.
+    pub insert_final_newline: Option<bool>,
+}
+
+impl FileFormat {
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let needs_lines = self.line_ending.is_some() || self.trim_trailing_whitespace;
+        let mut text = if needs_lines {
+            let eol = self.line_ending.unwrap_or(LineEnding::Lf).as_str();
+            text.split('\n')
+                .map(|line| {
+                    let line = line.strip_suffix('\r').unwrap_or(line);
+                    if self.trim_trailing_whitespace {
+                        line.trim_end_matches([' ', '\t'])
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(eol)
+        } else {
+            text.to_string()
+        };
+
+        let eol = self.line_ending.unwrap_or(LineEnding::Lf).as_str();
+        match self.insert_final_newline {
+            Some(true) if !text.is_empty() && !text.ends_with(eol) => text.push_str(eol),
+            Some(false) => {
+                while let Some(stripped) = text.strip_suffix(eol) {
+                    text.truncate(stripped.len());
+                }
+            }
+            _ => {}
+        }
+
+        self.charset.encode(&text)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UndoState {
     pub content: Rope,
@@ -28,6 +78,14 @@ pub struct Buffer {
     pub terminal_output: Option<TerminalOutput>,
     pub undo_stack: VecDeque<UndoState>,
     pub redo_stack: VecDeque<UndoState>,
+    /// Lines remembered by `:g`, kept pointing at the same text as lines
+    /// are inserted and deleted; `None` once a line has been deleted.
+    pub line_marks: Vec<Option<usize>>,
+    /// Lines of the named marks `'a` to `'z`, set with `m{a-z}`.
+    pub marks: [Option<usize>; 26],
+    pub folds: FoldSet,
+    pub options: LocalOptions,
+    pub format: FileFormat,
 }
 
 impl Buffer {
@@ -41,6 +99,11 @@ impl Buffer {
             terminal_output: None,
             undo_stack: VecDeque::new(),
             redo_stack: VecDeque::new(),
+            line_marks: Vec::new(),
+            marks: [None; 26],
+            folds: FoldSet::default(),
+            options: LocalOptions::default(),
+            format: FileFormat::default(),
         }
     }
 
@@ -54,15 +117,19 @@ impl Buffer {
             terminal_output: Some(TerminalOutput::new()),
             undo_stack: VecDeque::new(),
             redo_stack: VecDeque::new(),
+            line_marks: Vec::new(),
+            marks: [None; 26],
+            folds: FoldSet::default(),
+            options: LocalOptions::default(),
+            format: FileFormat::default(),
         }
     }
 
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
-        let content = std::fs::read_to_string(path)
-            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let (content, format) = Self::read_file(path)?;
 
-        Ok(Self {
+        let mut buffer = Self {
             content: Rope::from_str(&content),
             file_path: Some(path.to_path_buf()),
             is_modified: false,
@@ -71,11 +138,18 @@ impl Buffer {
             terminal_output: None,
             undo_stack: VecDeque::new(),
             redo_stack: VecDeque::new(),
-        })
+            line_marks: Vec::new(),
+            marks: [None; 26],
+            folds: FoldSet::default(),
+            options: LocalOptions::default(),
+            format,
+        };
+        buffer.apply_editorconfig();
+        Ok(buffer)
     }
 
     pub fn new_file<P: AsRef<Path>>(path: P) -> Self {
-        Self {
+        let mut buffer = Self {
             content: Rope::new(),
             file_path: Some(path.as_ref().to_path_buf()),
             is_modified: true,
@@ -84,7 +158,63 @@ impl Buffer {
             terminal_output: None,
             undo_stack: VecDeque::new(),
             redo_stack: VecDeque::new(),
+            line_marks: Vec::new(),
+            marks: [None; 26],
+            folds: FoldSet::default(),
+            options: LocalOptions::default(),
+            format: FileFormat::default(),
+        };
+        buffer.apply_editorconfig();
+        buffer
+    }
+
+    /// Reads and decodes a file, converting CRLF line endings to `\n` and
+    /// remembering them so they are written back on save.
+    fn read_file(path: &Path) -> Result<(String, FileFormat)> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let (content, charset) = Charset::decode(&bytes);
+
+        let mut format = FileFormat {
+            charset,
+            ..FileFormat::default()
+        };
+        if content.contains("\r\n") {
+            format.line_ending = Some(LineEnding::Crlf);
+            return Ok((content.replace("\r\n", "\n"), format));
+        }
+        Ok((content, format))
+    }
+
+    /// Applies `.editorconfig` properties for this buffer's file as
+    /// buffer-local settings and file format.
+    pub fn apply_editorconfig(&mut self) {
+        let Some(path) = &self.file_path else {
+            return;
+        };
+        let properties = EditorConfigProperties::for_file(path);
+        if properties.is_empty() {
+            return;
+        }
+
+        if let Some(tab_size) = properties.tab_size() {
+            self.options.set("tab_size", OptionValue::Number(tab_size));
+        }
+        if let Some(expandtab) = properties.indent_with_spaces {
+            self.options.set("expandtab", OptionValue::Bool(expandtab));
+        }
+        if let Some(textwidth) = properties.max_line_length {
+            self.options
+                .set("textwidth", OptionValue::Number(textwidth));
+        }
+        if let Some(line_ending) = properties.end_of_line {
+            self.format.line_ending = Some(line_ending);
         }
+        if let Some(charset) = properties.charset {
+            self.format.charset = charset;
+        }
+        self.format.trim_trailing_whitespace = properties.trim_trailing_whitespace == Some(true);
+        self.format.insert_final_newline = properties.insert_final_newline;
     }
 
     pub fn save(&mut self) -> Result<()> {
@@ -97,7 +227,7 @@ impl Buffer {
                 }
             }
 
-            let content = self.content.to_string();
+            let content = self.format.encode(&self.content.to_string());
             std::fs::write(path, content)
                 .with_context(|| format!("Failed to save file: {}", path.display()))?;
             self.is_modified = false;
@@ -107,7 +237,7 @@ impl Buffer {
 
     pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let path = path.as_ref();
-        let content = self.content.to_string();
+        let content = self.format.encode(&self.content.to_string());
         std::fs::write(path, content)
             .with_context(|| format!("Failed to save file: {}", path.display()))?;
         self.file_path = Some(path.to_path_buf());
@@ -115,6 +245,24 @@ impl Buffer {
         Ok(())
     }
 
+    /// Writes `text` to `path` in this buffer's file format without making
+    /// it the buffer's file. With `append` the text goes at the end of the
+    /// file.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P, text: &str, append: bool) -> Result<()> {
+        let path = path.as_ref();
+        let content = self.format.encode(text);
+        let result = if append {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(&content))
+        } else {
+            std::fs::write(path, content)
+        };
+        result.with_context(|| format!("Failed to write file: {}", path.display()))
+    }
+
     pub fn insert_char(&mut self, line: usize, col: usize, ch: char) {
         if let Some(char_idx) = self.line_col_to_char_idx(line, col) {
             log::debug!(
@@ -125,6 +273,9 @@ impl Buffer {
                 ch as u32,
                 char_idx
             );
+            if ch == '\n' {
+                self.shift_for_insert(char_idx, "\n");
+            }
             self.content.insert_char(char_idx, ch);
             self.is_modified = true;
 
@@ -143,6 +294,7 @@ impl Buffer {
 
     pub fn insert_str(&mut self, line: usize, col: usize, s: &str) {
         if let Some(char_idx) = self.line_col_to_char_idx(line, col) {
+            self.shift_for_insert(char_idx, s);
             self.content.insert(char_idx, s);
             self.is_modified = true;
         }
@@ -151,6 +303,7 @@ impl Buffer {
     pub fn delete_char(&mut self, line: usize, col: usize) {
         if let Some(char_idx) = self.line_col_to_char_idx(line, col) {
             if char_idx < self.content.len_chars() {
+                self.shift_for_remove(char_idx, char_idx + 1);
                 self.content.remove(char_idx..char_idx + 1);
                 self.is_modified = true;
             }
@@ -169,16 +322,70 @@ impl Buffer {
             self.line_col_to_char_idx(end_line, end_col),
         ) {
             if start_idx < end_idx && end_idx <= self.content.len_chars() {
+                self.shift_for_remove(start_idx, end_idx);
                 self.content.remove(start_idx..end_idx);
                 self.is_modified = true;
             }
         }
     }
 
+    pub fn set_line_indent(&mut self, line: usize, indent: &str) {
+        if let Some(line_content) = self.line(line) {
+            let current = crate::editor::indent::leading_indent(&line_content);
+            if current == indent {
+                return;
+            }
+            let current_len = current.chars().count();
+            self.delete_range(line, 0, line, current_len);
+            self.insert_str(line, 0, indent);
+        }
+    }
+
+    pub fn replace_line(&mut self, line: usize, text: &str) {
+        if let Some(line_content) = self.line(line) {
+            if line_content == text {
+                return;
+            }
+            self.delete_range(line, 0, line, line_content.chars().count());
+            self.insert_str(line, 0, text);
+        }
+    }
+
+    /// Replaces lines `start..=end` with `lines`, which may differ in number.
+    pub fn replace_lines(&mut self, start: usize, end: usize, lines: &[String]) {
+        let end = end.min(self.line_count().saturating_sub(1));
+        let end_col = self.line_len(end);
+        self.delete_range(start, 0, end, end_col);
+        self.insert_str(start, 0, &lines.join("\n"));
+    }
+
+    /// The slot of mark `name` in `marks`, if it is a named mark.
+    pub fn mark_slot(name: char) -> Option<usize> {
+        name.is_ascii_lowercase()
+            .then(|| (name as u8 - b'a') as usize)
+    }
+
+    /// Sets mark `name` to `line`; names other than `a`-`z` are ignored.
+    pub fn set_mark(&mut self, name: char, line: usize) {
+        if let Some(slot) = Self::mark_slot(name) {
+            self.marks[slot] = Some(line);
+        }
+    }
+
     pub fn line_count(&self) -> usize {
         self.content.len_lines()
     }
 
+    /// Lines of text, not counting the empty line after a final newline.
+    pub fn text_line_count(&self) -> usize {
+        let count = self.line_count();
+        if count > 1 && self.content.line(count - 1).len_chars() == 0 {
+            count - 1
+        } else {
+            count
+        }
+    }
+
     pub fn get_selected_text(&self, selection: &Selection) -> String {
         if let Some((start, end)) = selection.get_range() {
             let start_char = self.cursor_to_char_idx(&start);
@@ -201,6 +408,7 @@ impl Buffer {
 
             if start_char <= end_char && end_char <= self.content.len_chars() {
                 let deleted_text = self.content.slice(start_char..end_char).to_string();
+                self.shift_for_remove(start_char, end_char);
                 self.content.remove(start_char..end_char);
                 self.is_modified = true;
                 return deleted_text;
@@ -212,6 +420,7 @@ impl Buffer {
     pub fn insert_text_at_cursor(&mut self, cursor: &Cursor, text: &str) {
         let char_idx = self.cursor_to_char_idx(cursor);
         if char_idx <= self.content.len_chars() {
+            self.shift_for_insert(char_idx, text);
             self.content.insert(char_idx, text);
             self.is_modified = true;
         }
@@ -310,10 +519,14 @@ impl Buffer {
     pub fn reload_from_disk(&mut self) -> Result<()> {
         if let Some(ref path) = self.file_path {
             if path.exists() {
-                let content_str = std::fs::read_to_string(path)
-                    .with_context(|| format!("Failed to read file: {}", path.display()))?;
+                let (content_str, format) = Self::read_file(path)?;
 
                 self.content = Rope::from_str(&content_str);
+                self.line_marks.clear();
+                self.format.charset = format.charset;
+                if format.line_ending.is_some() {
+                    self.format.line_ending = format.line_ending;
+                }
                 self.is_modified = false;
 
                 log::info!("Reloaded file from disk: {}", path.display());
@@ -412,6 +625,82 @@ impl Buffer {
         self.redo_stack.clear();
     }
 
+    /// Starts collecting the following edits into a single undo step.
+    /// Returns the undo history to hand back to [`Buffer::end_undo_group`].
+    pub fn begin_undo_group(&mut self, cursor: &Cursor) -> VecDeque<UndoState> {
+        self.save_state(cursor);
+        self.undo_stack.clone()
+    }
+
+    /// Replaces the undo states pushed since `begin_undo_group` with the one
+    /// taken there, or drops that too if nothing changed.
+    pub fn end_undo_group(&mut self, undo_stack: VecDeque<UndoState>) {
+        let changed = undo_stack
+            .back()
+            .is_some_and(|state| state.content != self.content);
+        self.undo_stack = undo_stack;
+        if !changed {
+            self.undo_stack.pop_back();
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Whether any marks or folds have to follow lines as they move.
+    fn tracks_lines(&self) -> bool {
+        !self.line_marks.is_empty()
+            || self.marks.iter().any(Option::is_some)
+            || !self.folds.is_empty()
+    }
+
+    /// Moves marks and folds below an insertion of `text` at `char_idx` down
+    /// by the number of lines it adds. Lines inserted at the start of a line
+    /// push that line down too.
+    fn shift_for_insert(&mut self, char_idx: usize, text: &str) {
+        let added = text.matches('\n').count();
+        if added == 0 || !self.tracks_lines() {
+            return;
+        }
+        let line = self.content.char_to_line(char_idx);
+        let whole_lines = char_idx == self.content.line_to_char(line) && text.ends_with('\n');
+        let first_moved = if whole_lines { line } else { line + 1 };
+        for mark in self.line_marks.iter_mut().chain(&mut self.marks).flatten() {
+            if *mark >= first_moved {
+                *mark += added;
+            }
+        }
+        self.folds.shift_for_insert(first_moved, added);
+    }
+
+    /// Drops the marks of lines removed by deleting `start..end`, moves the
+    /// marks below it up and shrinks or moves folds to match.
+    fn shift_for_remove(&mut self, start: usize, end: usize) {
+        if !self.tracks_lines() {
+            return;
+        }
+        let start_line = self.content.char_to_line(start);
+        let end_line = self.content.char_to_line(end);
+        if start_line == end_line {
+            return;
+        }
+        // Removing whole lines deletes `start_line` itself; otherwise the
+        // lines after it are joined onto it.
+        let whole_lines = start == self.content.line_to_char(start_line)
+            && end == self.content.line_to_char(end_line);
+        let (first_removed, last_removed) = if whole_lines {
+            (start_line, end_line - 1)
+        } else {
+            (start_line + 1, end_line)
+        };
+        for mark in self.line_marks.iter_mut().chain(&mut self.marks) {
+            match *mark {
+                Some(line) if (first_removed..=last_removed).contains(&line) => *mark = None,
+                Some(line) if line > last_removed => *mark = Some(line - (end_line - start_line)),
+                _ => {}
+            }
+        }
+        self.folds.shift_for_remove(first_removed, last_removed);
+    }
+
     pub fn undo(&mut self) -> Option<Cursor> {
         if let Some(undo_state) = self.undo_stack.pop_back() {
             let current_state = UndoState {
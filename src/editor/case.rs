@@ -0,0 +1,50 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaseOp {
+    Lower,
+    Upper,
+    Toggle,
+}
+
+impl CaseOp {
+    /// Maps the operator key that follows `g` (`gu`, `gU`, `g~`).
+    pub fn from_key(key: char) -> Option<Self> {
+        match key {
+            'u' => Some(Self::Lower),
+            'U' => Some(Self::Upper),
+            '~' => Some(Self::Toggle),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Self::Lower => text.to_lowercase(),
+            Self::Upper => text.to_uppercase(),
+            Self::Toggle => text
+                .chars()
+                .flat_map(|ch| {
+                    if ch.is_uppercase() {
+                        ch.to_lowercase().collect::<Vec<_>>()
+                    } else {
+                        ch.to_uppercase().collect::<Vec<_>>()
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_ops() {
+        assert_eq!(CaseOp::from_key('U'), Some(CaseOp::Upper));
+        assert_eq!(CaseOp::from_key('x'), None);
+        assert_eq!(CaseOp::Lower.apply("Hello World"), "hello world");
+        assert_eq!(CaseOp::Upper.apply("straße 1"), "STRASSE 1");
+        assert_eq!(CaseOp::Toggle.apply("Hello, wORLD"), "hELLO, World");
+        assert_eq!(CaseOp::Toggle.apply("ßA"), "SSa");
+    }
+}
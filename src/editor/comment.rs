@@ -0,0 +1,186 @@
+use crate::editor::indent::leading_indent;
+use crate::utils::TextUtils;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommentTokens {
+    pub line: Option<String>,
+    pub block: Option<(String, String)>,
+}
+
+impl CommentTokens {
+    fn line(token: &str) -> Self {
+        Self {
+            line: Some(token.to_string()),
+            block: None,
+        }
+    }
+
+    fn block(open: &str, close: &str) -> Self {
+        Self {
+            line: None,
+            block: Some((open.to_string(), close.to_string())),
+        }
+    }
+
+    fn line_and_block(token: &str, open: &str, close: &str) -> Self {
+        Self {
+            line: Some(token.to_string()),
+            block: Some((open.to_string(), close.to_string())),
+        }
+    }
+
+    /// Parses a config override: a single line token such as `#`, or an
+    /// opening and closing block token separated by whitespace, e.g. `<!-- -->`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        match parts.as_slice() {
+            [token] => Some(Self::line(token)),
+            [open, close] => Some(Self::block(open, close)),
+            _ => None,
+        }
+    }
+
+    /// Picks comment tokens from a syntect syntax name such as `Rust` or `Python`.
+    pub fn for_syntax(syntax_name: &str) -> Option<Self> {
+        let tokens = match syntax_name {
+            "Rust" | "C" | "C++" | "C#" | "Java" | "JavaScript" | "TypeScript" | "Go"
+            | "Objective-C" | "Scala" | "Swift" | "Kotlin" | "PHP" | "D" | "Groovy" => {
+                Self::line_and_block("//", "/*", "*/")
+            }
+            "Python"
+            | "Ruby"
+            | "Perl"
+            | "R"
+            | "YAML"
+            | "TOML"
+            | "Makefile"
+            | "Bourne Again Shell (bash)"
+            | "Shell-Unix-Generic"
+            | "Dockerfile"
+            | "Nim" => Self::line("#"),
+            "Lua" | "SQL" | "Haskell" | "Ada" => Self::line("--"),
+            "Lisp" | "Clojure" | "Scheme" => Self::line(";"),
+            "Erlang" | "LaTeX" | "TeX" | "MATLAB" => Self::line("%"),
+            "VimL" => Self::line("\""),
+            "Batch File" => Self::line("REM"),
+            "CSS" | "SCSS" | "Sass" | "LESS" => Self::block("/*", "*/"),
+            "HTML" | "XML" | "Markdown" | "MultiMarkdown" => Self::block("<!--", "-->"),
+            "OCaml" => Self::block("(*", "*)"),
+            _ => return None,
+        };
+        Some(tokens)
+    }
+}
+
+/// Toggles comments on `lines`. If every non-blank line is already commented
+/// they are all uncommented; otherwise every non-blank line is commented, with
+/// the token aligned to the smallest indentation in the block.
+pub fn toggle_lines(lines: &[String], tokens: &CommentTokens, tab_size: usize) -> Vec<String> {
+    let non_blank: Vec<&String> = lines
+        .iter()
+        .filter(|line| !TextUtils::is_blank_line(line))
+        .collect();
+    if non_blank.is_empty() {
+        return lines.to_vec();
+    }
+
+    let all_commented = non_blank
+        .iter()
+        .all(|line| uncomment_line(line, tokens).is_some());
+    if all_commented {
+        return lines
+            .iter()
+            .map(|line| uncomment_line(line, tokens).unwrap_or_else(|| line.clone()))
+            .collect();
+    }
+
+    let min_width = non_blank
+        .iter()
+        .map(|line| TextUtils::display_column(line, leading_indent(line).chars().count(), tab_size))
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| {
+            if TextUtils::is_blank_line(line) {
+                return line.clone();
+            }
+            let col = TextUtils::char_column(line, min_width, tab_size);
+            let split = TextUtils::char_to_byte(line, col);
+            let (indent, text) = line.split_at(split);
+            match (&tokens.line, &tokens.block) {
+                (Some(token), _) => format!("{indent}{token} {text}"),
+                (None, Some((open, close))) => format!("{indent}{open} {text} {close}"),
+                (None, None) => line.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Removes a comment from `line`, returning `None` if it is not commented.
+fn uncomment_line(line: &str, tokens: &CommentTokens) -> Option<String> {
+    let indent = leading_indent(line);
+    let text = &line[indent.len()..];
+
+    if let Some(token) = &tokens.line {
+        if let Some(rest) = text.strip_prefix(token.as_str()) {
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            return Some(format!("{indent}{rest}"));
+        }
+    }
+
+    if let Some((open, close)) = &tokens.block {
+        let inner = text
+            .trim_end()
+            .strip_prefix(open.as_str())?
+            .strip_suffix(close.as_str())?;
+        let inner = inner.strip_prefix(' ').unwrap_or(inner);
+        let inner = inner.strip_suffix(' ').unwrap_or(inner);
+        return Some(format!("{indent}{inner}"));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_comment_aligns_to_smallest_indent() {
+        let tokens = CommentTokens::for_syntax("Rust").unwrap();
+        let result = toggle_lines(
+            &lines(&["    if x {", "", "        y();", "    }"]),
+            &tokens,
+            4,
+        );
+        assert_eq!(
+            result,
+            lines(&["    // if x {", "", "    //     y();", "    // }"])
+        );
+        assert_eq!(
+            toggle_lines(&result, &tokens, 4),
+            lines(&["    if x {", "", "        y();", "    }"])
+        );
+    }
+
+    #[test]
+    fn test_mixed_selection_is_commented() {
+        let tokens = CommentTokens::parse("#").unwrap();
+        let result = toggle_lines(&lines(&["# a", "b"]), &tokens, 4);
+        assert_eq!(result, lines(&["# # a", "# b"]));
+    }
+
+    #[test]
+    fn test_block_only_tokens_wrap_each_line() {
+        let tokens = CommentTokens::for_syntax("HTML").unwrap();
+        let result = toggle_lines(&lines(&["  <p>hi</p>"]), &tokens, 4);
+        assert_eq!(result, lines(&["  <!-- <p>hi</p> -->"]));
+        assert_eq!(toggle_lines(&result, &tokens, 4), lines(&["  <p>hi</p>"]));
+    }
+}
@@ -1,4 +1,6 @@
+use crate::editor::wrap::{self, WrapOptions, WrapSegment};
 use crate::editor::Buffer;
+use crate::utils::TextUtils;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Cursor {
@@ -6,6 +8,8 @@ pub struct Cursor {
     pub col: usize,
     pub desired_col: usize,
     pub visual_line_offset: usize,
+    /// `(line, col, screen column)` left by the last visual-row move.
+    pub visual_anchor: Option<(usize, usize, usize)>,
 }
 
 impl Cursor {
@@ -15,6 +19,7 @@ impl Cursor {
             col: 0,
             desired_col: 0,
             visual_line_offset: 0,
+            visual_anchor: None,
         }
     }
 
@@ -33,86 +38,82 @@ impl Cursor {
         }
     }
 
-    pub fn move_down_visual(&mut self, buffer: &Buffer, viewport_width: usize) {
-        if viewport_width == 0 {
+    pub fn move_down_visual(&mut self, buffer: &Buffer, options: &WrapOptions) {
+        if buffer.folds.closed_range(self.line).is_some() {
             self.move_down(buffer);
             return;
         }
 
-        let current_line_content = match buffer.line(self.line) {
-            Some(content) => content,
-            None => {
-                self.move_down(buffer);
+        let line = buffer.line(self.line).unwrap_or_default();
+        let segments = wrap::wrap_line(&line, options);
+        let idx = wrap::segment_index(&segments, self.col);
+        let screen_col = self.wanted_screen_col(&line, &segments[idx], options.tab_size);
+
+        if idx + 1 < segments.len() {
+            self.place_on_segment(&line, &segments, idx + 1, screen_col, options.tab_size);
+        } else {
+            let next_line = buffer.folds.next_visible_line(self.line);
+            if next_line >= buffer.line_count() {
                 return;
             }
-        };
-
-        let line_len = current_line_content.len();
-
-        if line_len <= viewport_width {
-            self.move_down(buffer);
-            return;
+            self.line = next_line;
+            let line = buffer.line(next_line).unwrap_or_default();
+            let segments = wrap::wrap_line(&line, options);
+            self.place_on_segment(&line, &segments, 0, screen_col, options.tab_size);
         }
-
-        let current_visual_line = self.col / viewport_width;
-        let max_visual_lines = line_len.div_ceil(viewport_width);
-
-        if current_visual_line + 1 < max_visual_lines {
-            let next_line_start = (current_visual_line + 1) * viewport_width;
-            let column_offset = self.col % viewport_width;
-            self.col = (next_line_start + column_offset).min(line_len);
-        } else if self.line + 1 < buffer.line_count() {
-            self.line += 1;
-            let column_offset = self.col % viewport_width;
-            let new_line_len = buffer.line_len(self.line);
-            self.col = column_offset.min(new_line_len);
-        }
-
-        self.desired_col = self.col % viewport_width;
     }
 
-    pub fn move_up_visual(&mut self, buffer: &Buffer, viewport_width: usize) {
-        if viewport_width == 0 {
+    pub fn move_up_visual(&mut self, buffer: &Buffer, options: &WrapOptions) {
+        if buffer.folds.closed_range(self.line).is_some() {
             self.move_up(buffer);
             return;
         }
 
-        let current_line_content = match buffer.line(self.line) {
-            Some(content) => content,
-            None => {
-                self.move_up(buffer);
-                return;
-            }
-        };
-
-        let line_len = current_line_content.len();
-
-        if line_len <= viewport_width {
-            self.move_up(buffer);
-            return;
+        let line = buffer.line(self.line).unwrap_or_default();
+        let segments = wrap::wrap_line(&line, options);
+        let idx = wrap::segment_index(&segments, self.col);
+        let screen_col = self.wanted_screen_col(&line, &segments[idx], options.tab_size);
+
+        if idx > 0 {
+            self.place_on_segment(&line, &segments, idx - 1, screen_col, options.tab_size);
+        } else if let Some(prev_line) = buffer.folds.prev_visible_line(self.line) {
+            self.line = prev_line;
+            let line = buffer.line(prev_line).unwrap_or_default();
+            let segments = wrap::wrap_line(&line, options);
+            self.place_on_segment(
+                &line,
+                &segments,
+                segments.len() - 1,
+                screen_col,
+                options.tab_size,
+            );
         }
+    }
 
-        let current_visual_line = self.col / viewport_width;
-
-        if current_visual_line > 0 {
-            let prev_line_start = (current_visual_line - 1) * viewport_width;
-            let column_offset = self.col % viewport_width;
-            self.col = prev_line_start + column_offset;
-        } else if self.line > 0 {
-            self.line -= 1;
-            let prev_line_len = buffer.line_len(self.line);
-            let column_offset = self.col % viewport_width;
-
-            if prev_line_len > viewport_width {
-                let prev_max_visual_lines = prev_line_len.div_ceil(viewport_width);
-                let target_line_start = (prev_max_visual_lines - 1) * viewport_width;
-                self.col = (target_line_start + column_offset).min(prev_line_len);
-            } else {
-                self.col = column_offset.min(prev_line_len);
+    /// Screen column to aim for on the next visual row: the one remembered
+    /// from the previous visual move if the cursor has not moved since,
+    /// otherwise the cursor's own column.
+    fn wanted_screen_col(&self, line: &str, segment: &WrapSegment, tab_size: usize) -> usize {
+        match self.visual_anchor {
+            Some((line_idx, col, screen_col)) if line_idx == self.line && col == self.col => {
+                screen_col
             }
+            _ => segment.screen_col(TextUtils::display_column(line, self.col, tab_size)),
         }
+    }
 
-        self.desired_col = self.col % viewport_width;
+    fn place_on_segment(
+        &mut self,
+        line: &str,
+        segments: &[WrapSegment],
+        idx: usize,
+        screen_col: usize,
+        tab_size: usize,
+    ) {
+        let is_last = idx + 1 == segments.len();
+        self.col = wrap::col_at_screen_col(line, &segments[idx], screen_col, is_last, tab_size);
+        self.desired_col = self.col;
+        self.visual_anchor = Some((self.line, self.col, screen_col));
     }
 
     pub fn move_left(&mut self, buffer: &Buffer) {
@@ -139,16 +140,17 @@ impl Cursor {
     }
 
     pub fn move_up(&mut self, buffer: &Buffer) {
-        if self.line > 0 {
-            self.line -= 1;
+        if let Some(prev_line) = buffer.folds.prev_visible_line(self.line) {
+            self.line = prev_line;
             let line_len = buffer.line_len(self.line);
             self.col = self.desired_col.min(line_len);
         }
     }
 
     pub fn move_down(&mut self, buffer: &Buffer) {
-        if self.line + 1 < buffer.line_count() {
-            self.line += 1;
+        let next_line = buffer.folds.next_visible_line(self.line);
+        if next_line < buffer.line_count() {
+            self.line = next_line;
             let line_len = buffer.line_len(self.line);
             self.col = self.desired_col.min(line_len);
         }
@@ -280,8 +282,8 @@ impl Cursor {
     }
 
     pub fn move_up_insert_mode(&mut self, buffer: &Buffer) {
-        if self.line > 0 {
-            self.line -= 1;
+        if let Some(prev_line) = buffer.folds.prev_visible_line(self.line) {
+            self.line = prev_line;
             let line_len = buffer.line_len(self.line);
             if self.col > line_len {
                 self.col = line_len;
@@ -291,8 +293,9 @@ impl Cursor {
     }
 
     pub fn move_down_insert_mode(&mut self, buffer: &Buffer) {
-        if self.line + 1 < buffer.line_count() {
-            self.line += 1;
+        let next_line = buffer.folds.next_visible_line(self.line);
+        if next_line < buffer.line_count() {
+            self.line = next_line;
             let line_len = buffer.line_len(self.line);
             if self.col > line_len {
                 self.col = line_len;
@@ -0,0 +1,346 @@
+use crate::editor::indent::leading_indent;
+use crate::utils::TextUtils;
+use crate::Result;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+pub const FOLD_METHODS: &[&str] = &["manual", "indent", "marker", "syntax"];
+
+pub const FOLD_MARKER_OPEN: &str = "{{{";
+pub const FOLD_MARKER_CLOSE: &str = "}}}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub closed: bool,
+}
+
+impl Fold {
+    pub fn contains(&self, line: usize) -> bool {
+        self.start <= line && line <= self.end
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.end + 1 - self.start
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FoldSet {
+    pub folds: Vec<Fold>,
+    /// Buffer content the folds were last computed from, for automatic methods.
+    computed_from: Option<Rope>,
+}
+
+impl FoldSet {
+    pub fn validate_method(method: &str) -> Result<()> {
+        if FOLD_METHODS.contains(&method) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Invalid foldmethod: {} (use {})",
+                method,
+                FOLD_METHODS.join("/")
+            ))
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    pub fn needs_refresh(&self, content: &Rope) -> bool {
+        self.computed_from.as_ref() != Some(content)
+    }
+
+    /// Replaces the folds with freshly computed ranges, keeping closed folds
+    /// closed when a new fold starts on the same line.
+    pub fn replace(&mut self, ranges: Vec<(usize, usize)>, content: &Rope) {
+        let closed_starts: Vec<usize> = self
+            .folds
+            .iter()
+            .filter(|fold| fold.closed)
+            .map(|fold| fold.start)
+            .collect();
+
+        self.folds = ranges
+            .into_iter()
+            .map(|(start, end)| Fold {
+                start,
+                end,
+                closed: closed_starts.contains(&start),
+            })
+            .collect();
+        self.computed_from = Some(content.clone());
+    }
+
+    /// Restores saved folds, dropping any that no longer fit the buffer.
+    pub fn restore(&mut self, folds: &[Fold], line_count: usize) {
+        self.folds = folds
+            .iter()
+            .filter(|fold| fold.start < fold.end && fold.end < line_count)
+            .copied()
+            .collect();
+        self.computed_from = None;
+    }
+
+    /// Adds a closed manual fold, replacing any fold with the same range.
+    pub fn create(&mut self, start: usize, end: usize) {
+        self.folds
+            .retain(|fold| !(fold.start == start && fold.end == end));
+        self.folds.push(Fold {
+            start,
+            end,
+            closed: true,
+        });
+        self.folds
+            .sort_by_key(|fold| (fold.start, std::cmp::Reverse(fold.end)));
+    }
+
+    /// Moves fold boundaries at or below `line` down by `added` lines, so a
+    /// fold with lines inserted inside it grows.
+    pub fn shift_for_insert(&mut self, line: usize, added: usize) {
+        for fold in self.folds.iter_mut() {
+            if fold.start >= line {
+                fold.start += added;
+            }
+            if fold.end >= line {
+                fold.end += added;
+            }
+        }
+    }
+
+    /// Follows the removal of lines `first..=last`: folds below move up,
+    /// folds overlapping the removed lines shrink, and folds left with fewer
+    /// than two lines are dropped.
+    pub fn shift_for_remove(&mut self, first: usize, last: usize) {
+        let removed = last + 1 - first;
+        self.folds.retain_mut(|fold| {
+            let start = match fold.start {
+                line if line > last => line - removed,
+                line if line >= first => first,
+                line => line,
+            };
+            let end = match fold.end {
+                line if line > last => Some(line - removed),
+                line if line >= first => first.checked_sub(1),
+                line => Some(line),
+            };
+            match end {
+                Some(end) if start < end => {
+                    fold.start = start;
+                    fold.end = end;
+                    true
+                }
+                _ => false,
+            }
+        });
+    }
+
+    /// Returns the outermost closed fold containing `line`.
+    pub fn closed_range(&self, line: usize) -> Option<(usize, usize)> {
+        self.folds
+            .iter()
+            .filter(|fold| fold.closed && fold.contains(line))
+            .max_by_key(|fold| fold.line_count())
+            .map(|fold| (fold.start, fold.end))
+    }
+
+    /// Whether `line` is folded away behind the summary line of a closed fold.
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.closed_range(line)
+            .is_some_and(|(start, _)| start != line)
+    }
+
+    /// Returns the first line below `line` that is not inside the same closed fold.
+    pub fn next_visible_line(&self, line: usize) -> usize {
+        self.closed_range(line).map_or(line, |(_, end)| end) + 1
+    }
+
+    /// Returns the first visible line above `line`, landing on fold summary lines.
+    pub fn prev_visible_line(&self, line: usize) -> Option<usize> {
+        let start = self.closed_range(line).map_or(line, |(start, _)| start);
+        let prev = start.checked_sub(1)?;
+        Some(self.closed_range(prev).map_or(prev, |(start, _)| start))
+    }
+
+    /// Opens the closed folds containing `line` (`zo`).
+    pub fn open_at(&mut self, line: usize) -> bool {
+        let mut opened = false;
+        for fold in self.folds.iter_mut() {
+            if fold.closed && fold.contains(line) {
+                fold.closed = false;
+                opened = true;
+            }
+        }
+        opened
+    }
+
+    /// Closes the innermost open fold containing `line` (`zc`).
+    pub fn close_at(&mut self, line: usize) -> bool {
+        let innermost = self
+            .folds
+            .iter_mut()
+            .filter(|fold| !fold.closed && fold.contains(line))
+            .min_by_key(|fold| fold.line_count());
+        match innermost {
+            Some(fold) => {
+                fold.closed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Opens the fold under `line` if it is closed, otherwise closes it (`za`).
+    pub fn toggle_at(&mut self, line: usize) -> bool {
+        if self.closed_range(line).is_some() {
+            self.open_at(line)
+        } else {
+            self.close_at(line)
+        }
+    }
+
+    pub fn set_all(&mut self, closed: bool) {
+        for fold in self.folds.iter_mut() {
+            fold.closed = closed;
+        }
+    }
+}
+
+/// Computes folds over runs of lines indented deeper than their neighbours,
+/// the way Vim's `foldmethod=indent` does. Blank lines join the shallower of
+/// the surrounding blocks.
+pub fn indent_folds(lines: &[String], tab_size: usize) -> Vec<(usize, usize)> {
+    let shift = tab_size.max(1);
+    let levels: Vec<Option<usize>> = lines
+        .iter()
+        .map(|line| {
+            if TextUtils::is_blank_line(line) {
+                None
+            } else {
+                let indent = leading_indent(line).chars().count();
+                Some(TextUtils::display_column(line, indent, tab_size) / shift)
+            }
+        })
+        .collect();
+
+    let mut resolved = vec![0; levels.len()];
+    for (idx, level) in levels.iter().enumerate() {
+        resolved[idx] = match level {
+            Some(level) => *level,
+            None => {
+                let prev = levels[..idx].iter().rev().flatten().next().copied();
+                let next = levels[idx + 1..].iter().flatten().next().copied();
+                prev.unwrap_or(0).min(next.unwrap_or(0))
+            }
+        };
+    }
+
+    let mut folds = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    for (idx, &level) in resolved.iter().chain(std::iter::once(&0)).enumerate() {
+        while open.len() > level {
+            if let Some(start) = open.pop() {
+                if idx - 1 > start {
+                    folds.push((start, idx - 1));
+                }
+            }
+        }
+        while open.len() < level {
+            open.push(idx);
+        }
+    }
+
+    folds.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+    folds
+}
+
+/// Computes folds between `{{{` and `}}}` markers, which may nest.
+pub fn marker_folds(lines: &[String]) -> Vec<(usize, usize)> {
+    let mut folds = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let opens = line.matches(FOLD_MARKER_OPEN).count();
+        let closes = line.matches(FOLD_MARKER_CLOSE).count();
+        for _ in 0..opens {
+            open.push(idx);
+        }
+        for _ in 0..closes {
+            if let Some(start) = open.pop() {
+                if idx > start {
+                    folds.push((start, idx));
+                }
+            }
+        }
+    }
+
+    folds.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+    folds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_indent_folds_nest_and_absorb_blank_lines() {
+        let text = "fn a() {\n    let x = 1;\n\n    if x {\n        y();\n    }\n}\nfn b() {}";
+        assert_eq!(indent_folds(&lines(text), 4), vec![(1, 5)]);
+
+        let text = "a:\n  b:\n    c\n    d\n  e";
+        assert_eq!(indent_folds(&lines(text), 2), vec![(1, 4), (2, 3)]);
+    }
+
+    #[test]
+    fn test_marker_folds() {
+        let text = "// {{{\nfn a() {}\n// {{{\nfn b() {}\n// }}}\n// }}}";
+        assert_eq!(marker_folds(&lines(text)), vec![(0, 5), (2, 4)]);
+    }
+
+    #[test]
+    fn test_folds_follow_buffer_edits() {
+        let mut buffer = crate::editor::Buffer::empty();
+        buffer.content = Rope::from_str("a\nb\nc\nd\ne\nf\n");
+        buffer.folds.create(2, 4);
+
+        buffer.insert_str(0, 0, "x\ny\n");
+        assert_eq!(buffer.folds.closed_range(4), Some((4, 6)));
+        buffer.insert_str(5, 1, "\n");
+        assert_eq!(buffer.folds.closed_range(4), Some((4, 7)));
+
+        buffer.delete_range(0, 0, 2, 0);
+        assert_eq!(buffer.folds.closed_range(2), Some((2, 5)));
+        buffer.delete_range(1, 0, 3, 0);
+        assert_eq!(buffer.folds.closed_range(1), Some((1, 3)));
+        buffer.delete_range(2, 0, 4, 0);
+        assert!(buffer.folds.is_empty());
+    }
+
+    #[test]
+    fn test_fold_navigation() {
+        let mut folds = FoldSet::default();
+        folds.create(2, 5);
+        folds.create(3, 4);
+        assert!(folds.is_hidden(3));
+        assert!(!folds.is_hidden(2));
+        assert_eq!(folds.next_visible_line(2), 6);
+        assert_eq!(folds.prev_visible_line(6), Some(2));
+
+        assert!(folds.open_at(2));
+        assert_eq!(folds.closed_range(2), None);
+        assert_eq!(folds.closed_range(3), Some((3, 4)));
+        assert!(folds.toggle_at(3));
+        assert_eq!(folds.closed_range(3), None);
+        assert!(folds.close_at(3));
+        assert_eq!(folds.closed_range(3), Some((3, 4)));
+        assert!(folds.close_at(3));
+        assert_eq!(folds.closed_range(3), Some((2, 5)));
+    }
+}
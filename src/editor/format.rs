@@ -0,0 +1,330 @@
+use crate::editor::comment::CommentTokens;
+use crate::editor::indent::leading_indent;
+use crate::utils::TextUtils;
+
+/// Width `gq` formats to when `textwidth` is 0, as in Vim.
+pub const DEFAULT_FORMAT_WIDTH: usize = 79;
+
+/// Comment leaders recognised when the file type has no comment tokens.
+const DEFAULT_LEADERS: &[&str] = &["//", "#"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    pub width: usize,
+    pub tab_size: usize,
+    /// Comment leaders repeated on continuation lines, longest first.
+    pub leaders: Vec<String>,
+}
+
+impl FormatOptions {
+    pub fn new(width: usize, tab_size: usize, tokens: Option<&CommentTokens>) -> Self {
+        let mut leaders: Vec<String> = match tokens {
+            Some(tokens) => {
+                let mut leaders = Vec::new();
+                if let Some(line) = &tokens.line {
+                    if line == "//" {
+                        leaders.push("///".to_string());
+                        leaders.push("//!".to_string());
+                    }
+                    leaders.push(line.clone());
+                }
+                if let Some((open, _)) = &tokens.block {
+                    if open == "/*" {
+                        leaders.push("*".to_string());
+                    }
+                }
+                leaders
+            }
+            None => DEFAULT_LEADERS.iter().map(|s| s.to_string()).collect(),
+        };
+        leaders.sort_by_key(|leader| std::cmp::Reverse(leader.len()));
+
+        Self {
+            width: width.max(1),
+            tab_size: tab_size.max(1),
+            leaders,
+        }
+    }
+}
+
+/// A line split into its indent, comment leader, list bullet and text.
+#[derive(Debug, Clone, PartialEq)]
+struct LineParts<'a> {
+    indent: &'a str,
+    /// Comment leader including the whitespace after it, e.g. `// `.
+    leader: &'a str,
+    /// List bullet including the whitespace after it, e.g. `- ` or `2. `.
+    bullet: &'a str,
+    text: &'a str,
+}
+
+impl<'a> LineParts<'a> {
+    fn parse(line: &'a str, options: &FormatOptions) -> Self {
+        let indent = leading_indent(line);
+        let rest = &line[indent.len()..];
+
+        let leader_len = options
+            .leaders
+            .iter()
+            .find(|leader| {
+                rest.strip_prefix(leader.as_str())
+                    .is_some_and(|after| after.is_empty() || after.starts_with([' ', '\t']))
+            })
+            .map_or(0, |leader| {
+                leader.len() + leading_indent(&rest[leader.len()..]).len()
+            });
+        let (leader, rest) = rest.split_at(leader_len);
+
+        let bullet_len = bullet_len(rest);
+        let (bullet, text) = rest.split_at(bullet_len);
+
+        Self {
+            indent,
+            leader,
+            bullet,
+            text,
+        }
+    }
+
+    /// Prefix for the first line of a formatted paragraph.
+    fn first_prefix(&self) -> String {
+        format!("{}{}{}", self.indent, self.leader, self.bullet)
+    }
+
+    /// Prefix for continuation lines: the leader is repeated and the bullet
+    /// replaced by spaces so the text lines up.
+    fn continuation_prefix(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.indent,
+            self.leader,
+            " ".repeat(TextUtils::display_width(self.bullet))
+        )
+    }
+
+    /// Text before the body, ignoring the bullet, used to decide whether a
+    /// line continues the paragraph above it.
+    fn key(&self, tab_size: usize) -> (usize, &'a str) {
+        let indent = TextUtils::display_column(self.indent, self.indent.chars().count(), tab_size);
+        (indent, self.leader.trim_end())
+    }
+}
+
+fn bullet_len(text: &str) -> usize {
+    let marker_len = if text.starts_with(['-', '+', '*']) {
+        1
+    } else {
+        let digits = text.chars().take_while(|ch| ch.is_ascii_digit()).count();
+        if digits > 0 && text[digits..].starts_with(['.', ')']) {
+            digits + 1
+        } else {
+            return 0;
+        }
+    };
+
+    let after = &text[marker_len..];
+    let spaces = leading_indent(after).len();
+    if spaces == 0 || spaces == after.len() {
+        0
+    } else {
+        marker_len + spaces
+    }
+}
+
+struct Paragraph {
+    first_prefix: String,
+    continuation_prefix: String,
+    keys: [(usize, String); 2],
+    words: Vec<String>,
+}
+
+impl Paragraph {
+    fn wrap(&self, options: &FormatOptions, out: &mut Vec<String>) {
+        let mut line = self.first_prefix.clone();
+        let mut line_width = TextUtils::display_width(&line);
+        let mut has_words = false;
+
+        for word in &self.words {
+            let word_width = TextUtils::display_width(word);
+            let needed = if has_words {
+                word_width + 1
+            } else {
+                word_width
+            };
+            if has_words && line_width + needed > options.width {
+                out.push(line.trim_end().to_string());
+                line = self.continuation_prefix.clone();
+                line_width = TextUtils::display_width(&line);
+                has_words = false;
+            }
+            if has_words {
+                line.push(' ');
+                line_width += 1;
+            }
+            line.push_str(word);
+            line_width += word_width;
+            has_words = true;
+        }
+
+        out.push(line.trim_end().to_string());
+    }
+}
+
+/// Rejoins and re-wraps paragraphs in `lines` to `options.width` columns
+/// (`gq`). Blank lines, list bullets and changes of indent or comment leader
+/// start a new paragraph.
+pub fn reflow(lines: &[String], options: &FormatOptions) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut paragraph: Option<Paragraph> = None;
+
+    for line in lines {
+        let parts = LineParts::parse(line, options);
+        if parts.text.trim().is_empty() {
+            if let Some(paragraph) = paragraph.take() {
+                paragraph.wrap(options, &mut out);
+            }
+            out.push(line.trim_end().to_string());
+            continue;
+        }
+
+        let (indent, leader) = parts.key(options.tab_size);
+        let continues = parts.bullet.is_empty()
+            && paragraph.as_ref().is_some_and(|paragraph| {
+                paragraph
+                    .keys
+                    .iter()
+                    .any(|(width, key)| *width == indent && key == leader)
+            });
+
+        if continues {
+            if let Some(paragraph) = paragraph.as_mut() {
+                paragraph
+                    .words
+                    .extend(parts.text.split_whitespace().map(String::from));
+            }
+            continue;
+        }
+
+        if let Some(paragraph) = paragraph.take() {
+            paragraph.wrap(options, &mut out);
+        }
+        let continuation_prefix = parts.continuation_prefix();
+        let continuation = LineParts::parse(&continuation_prefix, options);
+        let (continuation_indent, continuation_leader) = continuation.key(options.tab_size);
+        paragraph = Some(Paragraph {
+            first_prefix: parts.first_prefix(),
+            keys: [
+                (indent, leader.to_string()),
+                (
+                    continuation_indent + TextUtils::display_width(continuation.text),
+                    continuation_leader.to_string(),
+                ),
+            ],
+            continuation_prefix,
+            words: parts.text.split_whitespace().map(String::from).collect(),
+        });
+    }
+
+    if let Some(paragraph) = paragraph {
+        paragraph.wrap(options, &mut out);
+    }
+    out
+}
+
+/// Result of breaking a line that grew past `textwidth` while typing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineBreak {
+    pub head: String,
+    pub tail: String,
+    /// Cursor column on the tail line.
+    pub cursor_col: usize,
+}
+
+/// Breaks `line` at the last whitespace that keeps the head within
+/// `options.width`, continuing any comment leader or bullet indent on the new
+/// line. Returns `None` if the line fits, has no usable break or the cursor is
+/// before the break.
+pub fn break_line(line: &str, cursor_col: usize, options: &FormatOptions) -> Option<LineBreak> {
+    let chars: Vec<char> = line.chars().collect();
+    if TextUtils::display_column(line, chars.len(), options.tab_size) <= options.width {
+        return None;
+    }
+
+    let parts = LineParts::parse(line, options);
+    let body_start = parts.first_prefix().chars().count();
+
+    let break_at = (body_start + 1..chars.len())
+        .rev()
+        .filter(|&idx| chars[idx].is_whitespace() && !chars[idx - 1].is_whitespace())
+        .find(|&idx| TextUtils::display_column(line, idx, options.tab_size) <= options.width)?;
+
+    let tail_start = (break_at..chars.len())
+        .find(|&idx| !chars[idx].is_whitespace())
+        .unwrap_or(chars.len());
+    if cursor_col < tail_start {
+        return None;
+    }
+
+    let prefix = parts.continuation_prefix();
+    let tail: String = chars[tail_start..].iter().collect();
+    Some(LineBreak {
+        head: chars[..break_at].iter().collect(),
+        cursor_col: prefix.chars().count() + cursor_col - tail_start,
+        tail: format!("{prefix}{tail}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_reflow_keeps_comment_leaders_and_bullets() {
+        let tokens = CommentTokens::for_syntax("Rust");
+        let options = FormatOptions::new(24, 4, tokens.as_ref());
+        let result = reflow(
+            &lines(&[
+                "    // one two three four five six",
+                "    // seven",
+                "    //",
+                "    // - alpha beta gamma delta",
+                "    //   epsilon",
+            ]),
+            &options,
+        );
+        assert_eq!(
+            result,
+            lines(&[
+                "    // one two three",
+                "    // four five six",
+                "    // seven",
+                "    //",
+                "    // - alpha beta",
+                "    //   gamma delta",
+                "    //   epsilon",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_reflow_splits_list_items() {
+        let options = FormatOptions::new(72, 4, None);
+        let result = reflow(&lines(&["- a", "  b", "- c", "1. d", "e"]), &options);
+        assert_eq!(result, lines(&["- a b", "- c", "1. d e"]));
+    }
+
+    #[test]
+    fn test_break_line_while_typing() {
+        let options = FormatOptions::new(10, 4, None);
+        let line = "# abc defgh";
+        let result = break_line(line, 11, &options).unwrap();
+        assert_eq!(result.head, "# abc");
+        assert_eq!(result.tail, "# defgh");
+        assert_eq!(result.cursor_col, 7);
+        assert_eq!(break_line("# abc", 5, &options), None);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::editor::substitute::{is_delimiter, split_field};
+use crate::editor::{Buffer, SearchPattern};
+use crate::Result;
+
+/// A parsed `:g/pattern/command` or `:v/pattern/command`.
+#[derive(Debug, Clone)]
+pub struct GlobalCommand {
+    pub pattern: SearchPattern,
+    /// `:v` or `:g!`: run on the lines that do not match.
+    pub invert: bool,
+    /// The ex command to run on each line; empty means `p`.
+    pub command: String,
+}
+
+impl GlobalCommand {
+    /// Parses `/pattern/command` (any punctuation works as the delimiter). An
+    /// empty pattern reuses `last_pattern`, the last search.
+    pub fn parse(
+        args: &str,
+        invert: bool,
+        last_pattern: Option<&SearchPattern>,
+        ignorecase: bool,
+        smartcase: bool,
+    ) -> Result<Self> {
+        let delimiter = args
+            .chars()
+            .next()
+            .filter(|&ch| is_delimiter(ch))
+            .ok_or_else(|| anyhow::anyhow!("Usage: :[range]g/pattern/command"))?;
+        let (pattern, command) = split_field(&args[delimiter.len_utf8()..], delimiter);
+
+        let pattern = if pattern.is_empty() {
+            last_pattern
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("No previous search pattern"))?
+        } else {
+            SearchPattern::parse(pattern, delimiter, ignorecase, smartcase)?
+        };
+
+        Ok(Self {
+            pattern,
+            invert,
+            command: command.unwrap_or("").trim().to_string(),
+        })
+    }
+
+    /// The lines in `start..=end` of `buffer` the command runs on. The empty
+    /// line after a final newline is never one of them.
+    pub fn matching_lines(&self, buffer: &Buffer, start: usize, end: usize) -> Vec<usize> {
+        (start..=end.min(buffer.text_line_count().saturating_sub(1)))
+            .filter(|&line_idx| {
+                let line = buffer.line(line_idx).unwrap_or_default();
+                self.pattern.regex.is_match(&line) != self.invert
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    #[test]
+    fn test_parse_and_matching_lines() {
+        let mut buffer = Buffer::empty();
+        buffer.content = Rope::from_str("TODO one\n\ntwo\ntodo: a/b");
+        let global = GlobalCommand::parse("/TODO/normal A;", false, None, false, false).unwrap();
+        assert_eq!(global.command, "normal A;");
+        assert_eq!(global.matching_lines(&buffer, 0, 3), vec![0]);
+
+        let global = GlobalCommand::parse("/a\\/b/", true, None, false, false).unwrap();
+        assert_eq!(global.command, "");
+        assert_eq!(global.matching_lines(&buffer, 0, 3), vec![0, 1, 2]);
+
+        // `:g/^$/d` on a file that ends with a newline.
+        buffer.content = Rope::from_str("a\n\nb\n");
+        let global = GlobalCommand::parse("/^$/d", false, None, false, false).unwrap();
+        assert_eq!(global.matching_lines(&buffer, 0, 3), vec![1]);
+        buffer.delete_range(1, 0, 2, 0);
+        assert_eq!(buffer.content.to_string(), "a\nb\n");
+    }
+
+    #[test]
+    fn test_marks_follow_edits() {
+        let mut buffer = Buffer::empty();
+        buffer.content = Rope::from_str("a\nb\nc\nd\ne");
+        buffer.line_marks = vec![Some(1), Some(2), Some(3), Some(4)];
+        buffer.set_mark('a', 4);
+
+        // Deleting line 1 drops its mark and moves the rest up.
+        buffer.delete_range(1, 0, 2, 0);
+        assert_eq!(buffer.line_marks, vec![None, Some(1), Some(2), Some(3)]);
+
+        // Joining lines 1 and 2 drops the mark of the joined line.
+        buffer.delete_range(1, 1, 2, 0);
+        assert_eq!(buffer.line_marks, vec![None, Some(1), None, Some(2)]);
+
+        // Opening a line above line 1 moves its mark down.
+        buffer.insert_str(1, 0, "new\n");
+        assert_eq!(buffer.line_marks, vec![None, Some(2), None, Some(3)]);
+        assert_eq!(buffer.marks[0], Some(3));
+    }
+}
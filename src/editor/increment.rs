@@ -0,0 +1,145 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Radix {
+    Binary,
+    Decimal,
+    Hex,
+}
+
+/// A number found on a line, as character columns `start..end`.
+#[derive(Debug, Clone, PartialEq)]
+struct NumberSpan {
+    start: usize,
+    end: usize,
+    radix: Radix,
+    negative: bool,
+    /// Digits without sign or `0x`/`0b` prefix.
+    digits: String,
+}
+
+/// Scans `chars` for numbers the way Vim's `nrformats=bin,hex` does.
+fn numbers(chars: &[char]) -> Vec<NumberSpan> {
+    let mut spans = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        if !chars[idx].is_ascii_digit() {
+            idx += 1;
+            continue;
+        }
+
+        let start = idx;
+        let prefixed = |marker: char, is_digit: fn(&char) -> bool| {
+            chars[idx] == '0'
+                && chars
+                    .get(idx + 1)
+                    .is_some_and(|ch| ch.eq_ignore_ascii_case(&marker))
+                && chars.get(idx + 2).is_some_and(is_digit)
+        };
+        let (radix, digits_start) = if prefixed('x', char::is_ascii_hexdigit) {
+            (Radix::Hex, idx + 2)
+        } else if prefixed('b', |ch| *ch == '0' || *ch == '1') {
+            (Radix::Binary, idx + 2)
+        } else {
+            (Radix::Decimal, idx)
+        };
+
+        let mut end = digits_start;
+        while end < chars.len()
+            && match radix {
+                Radix::Hex => chars[end].is_ascii_hexdigit(),
+                Radix::Binary => chars[end] == '0' || chars[end] == '1',
+                Radix::Decimal => chars[end].is_ascii_digit(),
+            }
+        {
+            end += 1;
+        }
+
+        let negative = radix == Radix::Decimal
+            && start > 0
+            && chars[start - 1] == '-'
+            && !(start > 1 && chars[start - 2].is_alphanumeric());
+        spans.push(NumberSpan {
+            start: if negative { start - 1 } else { start },
+            end,
+            radix,
+            negative,
+            digits: chars[digits_start..end].iter().collect(),
+        });
+        idx = end;
+    }
+
+    spans
+}
+
+impl NumberSpan {
+    fn add(&self, delta: i64) -> String {
+        let width = self.digits.len();
+        match self.radix {
+            Radix::Decimal => {
+                let magnitude = self.digits.parse::<i128>().unwrap_or(i128::MAX);
+                let value = if self.negative { -magnitude } else { magnitude };
+                let value = value.saturating_add(delta as i128);
+                let sign = if value < 0 { "-" } else { "" };
+                if self.digits.starts_with('0') && width > 1 {
+                    format!("{sign}{:0width$}", value.unsigned_abs())
+                } else {
+                    format!("{value}")
+                }
+            }
+            Radix::Hex => {
+                let value = u64::from_str_radix(&self.digits, 16)
+                    .unwrap_or(u64::MAX)
+                    .wrapping_add(delta as u64);
+                if self.digits.chars().any(|ch| ch.is_ascii_uppercase()) {
+                    format!("0x{value:0width$X}")
+                } else {
+                    format!("0x{value:0width$x}")
+                }
+            }
+            Radix::Binary => {
+                let value = u64::from_str_radix(&self.digits, 2)
+                    .unwrap_or(u64::MAX)
+                    .wrapping_add(delta as u64);
+                format!("0b{value:0width$b}")
+            }
+        }
+    }
+}
+
+/// Adds `delta` to the number under or after character `col` of `line`
+/// (`Ctrl-A` / `Ctrl-X`). Returns the new line and the column of the last
+/// character of the changed number, or `None` if there is no number.
+pub fn increment(line: &str, col: usize, delta: i64) -> Option<(String, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let span = numbers(&chars).into_iter().find(|span| span.end > col)?;
+
+    let replacement = span.add(delta);
+    let mut result: String = chars[..span.start].iter().collect();
+    result.push_str(&replacement);
+    result.extend(&chars[span.end..]);
+
+    let cursor = span.start + replacement.chars().count() - 1;
+    Some((result, cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment_formats() {
+        assert_eq!(increment("x = 9;", 0, 1), Some(("x = 10;".to_string(), 5)));
+        assert_eq!(increment("a -1 b", 0, 3), Some(("a 2 b".to_string(), 2)));
+        assert_eq!(increment("v1-2", 3, -1), Some(("v1-1".to_string(), 3)));
+        assert_eq!(increment("007", 2, 1), Some(("008".to_string(), 2)));
+        assert_eq!(increment("-001", 0, 5), Some(("004".to_string(), 2)));
+        assert_eq!(increment("0xFF", 1, 1), Some(("0x100".to_string(), 4)));
+        assert_eq!(increment("0x0f", 3, 1), Some(("0x10".to_string(), 3)));
+        assert_eq!(increment("0b0111", 0, 1), Some(("0b1000".to_string(), 5)));
+        assert_eq!(
+            increment("12 and 34", 3, 1),
+            Some(("12 and 35".to_string(), 8))
+        );
+        assert_eq!(increment("none", 0, 1), None);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::config::settings::EditorConfig;
+use crate::utils::TextUtils;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndentRules {
+    pub indent_after: &'static [char],
+    pub dedent_on: &'static [char],
+}
+
+const C_LIKE_RULES: IndentRules = IndentRules {
+    indent_after: &['{', '(', '['],
+    dedent_on: &['}', ')', ']'],
+};
+
+const PYTHON_RULES: IndentRules = IndentRules {
+    indent_after: &[':', '{', '(', '['],
+    dedent_on: &['}', ')', ']'],
+};
+
+const YAML_RULES: IndentRules = IndentRules {
+    indent_after: &[':', '{', '['],
+    dedent_on: &['}', ']'],
+};
+
+impl IndentRules {
+    /// Picks smart-indent rules from a syntect syntax name such as `Rust` or `Python`.
+    pub fn for_syntax(syntax_name: &str) -> Option<Self> {
+        match syntax_name {
+            "Plain Text" | "Markdown" | "MultiMarkdown" | "Git Commit" | "reStructuredText" => None,
+            "Python" => Some(PYTHON_RULES),
+            "YAML" => Some(YAML_RULES),
+            _ => Some(C_LIKE_RULES),
+        }
+    }
+
+    pub fn opens_block(&self, text_before_cursor: &str) -> bool {
+        text_before_cursor
+            .trim_end()
+            .chars()
+            .last()
+            .is_some_and(|ch| self.indent_after.contains(&ch))
+    }
+
+    pub fn closes_block(&self, ch: char) -> bool {
+        self.dedent_on.contains(&ch)
+    }
+}
+
+pub fn indent_unit(config: &EditorConfig) -> String {
+    if config.expandtab {
+        " ".repeat(config.tab_size)
+    } else {
+        "\t".to_string()
+    }
+}
+
+pub fn leading_indent(line: &str) -> &str {
+    let end = line
+        .find(|ch: char| ch != ' ' && ch != '\t')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Builds an indentation string that spans `width` display columns.
+pub fn indent_for_width(width: usize, config: &EditorConfig) -> String {
+    if config.expandtab {
+        " ".repeat(width)
+    } else {
+        let tab_size = config.tab_size.max(1);
+        format!(
+            "{}{}",
+            "\t".repeat(width / tab_size),
+            " ".repeat(width % tab_size)
+        )
+    }
+}
+
+/// Returns the indentation of `line` after shifting it by `levels` shift widths.
+pub fn shifted_indent(line: &str, levels: isize, config: &EditorConfig) -> String {
+    let tab_size = config.tab_size.max(1);
+    let current = TextUtils::display_column(line, leading_indent(line).chars().count(), tab_size);
+    let delta = levels.unsigned_abs() * tab_size;
+    let width = if levels >= 0 {
+        current + delta
+    } else {
+        current.saturating_sub(delta)
+    };
+    indent_for_width(width, config)
+}
+
+/// Computes the indentation of a line opened after `text_before_cursor`.
+pub fn newline_indent(
+    text_before_cursor: &str,
+    config: &EditorConfig,
+    rules: Option<IndentRules>,
+) -> String {
+    if !config.autoindent {
+        return String::new();
+    }
+
+    let mut indent = leading_indent(text_before_cursor).to_string();
+    if config.smartindent && rules.is_some_and(|rules| rules.opens_block(text_before_cursor)) {
+        indent.push_str(&indent_unit(config));
+    }
+    indent
+}
+
+/// Returns the dedented indentation for a line on which `ch` is typed, if the
+/// closer is the first non-blank character on that line.
+pub fn closing_indent(
+    text_before_cursor: &str,
+    ch: char,
+    config: &EditorConfig,
+    rules: Option<IndentRules>,
+) -> Option<String> {
+    if !config.smartindent || !rules.is_some_and(|rules| rules.closes_block(ch)) {
+        return None;
+    }
+
+    if text_before_cursor.is_empty() || !text_before_cursor.chars().all(|c| c == ' ' || c == '\t') {
+        return None;
+    }
+
+    Some(shifted_indent(text_before_cursor, -1, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn config(expandtab: bool) -> EditorConfig {
+        let mut config = Config::default().editor;
+        config.tab_size = 4;
+        config.expandtab = expandtab;
+        config.autoindent = true;
+        config.smartindent = true;
+        config
+    }
+
+    #[test]
+    fn test_indent_unit_and_shift() {
+        let spaces = config(true);
+        assert_eq!(indent_unit(&spaces), "    ");
+        assert_eq!(shifted_indent("  x", 1, &spaces), "      ");
+        assert_eq!(shifted_indent("\tx", -1, &spaces), "");
+        assert_eq!(shifted_indent("x", -1, &spaces), "");
+
+        let tabs = config(false);
+        assert_eq!(indent_unit(&tabs), "\t");
+        assert_eq!(shifted_indent("    x", 1, &tabs), "\t\t");
+        assert_eq!(shifted_indent("\t  x", 0, &tabs), "\t  ");
+    }
+
+    #[test]
+    fn test_smartindent() {
+        let config = config(true);
+        let rust = IndentRules::for_syntax("Rust");
+        let python = IndentRules::for_syntax("Python");
+        assert_eq!(newline_indent("    fn main() {", &config, rust), "        ");
+        assert_eq!(newline_indent("    let x = 1;", &config, rust), "    ");
+        assert_eq!(newline_indent("if x:", &config, python), "    ");
+        assert_eq!(newline_indent("  note:", &config, None), "  ");
+
+        assert_eq!(
+            closing_indent("        ", '}', &config, rust),
+            Some("    ".into())
+        );
+        assert_eq!(closing_indent("    x", '}', &config, rust), None);
+        assert_eq!(closing_indent("    ", ')', &config, None), None);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::editor::indent::leading_indent;
+use crate::utils::TextUtils;
+use crate::Result;
+use regex::Regex;
+use std::cmp::Ordering;
+
+/// Options for `:sort[!] [i][n][u][r] [/pattern/]`.
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    pub reverse: bool,
+    pub numeric: bool,
+    pub ignore_case: bool,
+    pub unique: bool,
+    /// Sort on the text after the first match of this pattern, or on the
+    /// match itself with `use_match`.
+    pub pattern: Option<Regex>,
+    pub use_match: bool,
+}
+
+impl SortOptions {
+    /// Parses the arguments of `:sort`; `bang` is true for `:sort!`.
+    pub fn parse(args: &str, bang: bool) -> Result<Self> {
+        let mut options = Self {
+            reverse: bang,
+            ..Self::default()
+        };
+
+        let mut rest = args.trim();
+        while let Some(ch) = rest.chars().next() {
+            match ch {
+                'n' => options.numeric = true,
+                'i' => options.ignore_case = true,
+                'u' => options.unique = true,
+                'r' => options.use_match = true,
+                ' ' | '\t' => {}
+                '/' => {
+                    let body = &rest[1..];
+                    let end = body.find('/').unwrap_or(body.len());
+                    let pattern = &body[..end];
+                    if !pattern.is_empty() {
+                        options.pattern = Some(
+                            Regex::new(pattern)
+                                .map_err(|e| anyhow::anyhow!("Invalid sort pattern: {e}"))?,
+                        );
+                    }
+                    rest = body.get(end + 1..).unwrap_or("");
+                    continue;
+                }
+                _ => return Err(anyhow::anyhow!("Invalid sort flag: {ch}")),
+            }
+            rest = &rest[ch.len_utf8()..];
+        }
+
+        Ok(options)
+    }
+
+    fn key<'a>(&self, line: &'a str) -> &'a str {
+        match &self.pattern {
+            Some(pattern) => match pattern.find(line) {
+                Some(found) if self.use_match => found.as_str(),
+                Some(found) => &line[found.end()..],
+                None if self.use_match => "",
+                None => line,
+            },
+            None => line,
+        }
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        let (a, b) = (self.key(a), self.key(b));
+        if self.numeric {
+            first_number(a).cmp(&first_number(b))
+        } else if self.ignore_case {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        } else {
+            a.cmp(b)
+        }
+    }
+}
+
+/// First decimal number in `text`, with lines without one sorting first.
+fn first_number(text: &str) -> Option<i128> {
+    let start = text.find(|ch: char| ch.is_ascii_digit())?;
+    let end = text[start..]
+        .find(|ch: char| !ch.is_ascii_digit())
+        .map_or(text.len(), |len| start + len);
+    let value = text[start..end].parse::<i128>().ok()?;
+    Some(if text[..start].ends_with('-') {
+        -value
+    } else {
+        value
+    })
+}
+
+pub fn sort_lines(lines: &[String], options: &SortOptions) -> Vec<String> {
+    let mut sorted = lines.to_vec();
+    sorted.sort_by(|a, b| options.compare(a, b));
+    if options.reverse {
+        sorted.reverse();
+    }
+    if options.unique {
+        sorted.dedup_by(|a, b| options.compare(a, b) == Ordering::Equal);
+    }
+    sorted
+}
+
+/// Removes adjacent duplicate lines, like `uniq(1)`.
+pub fn uniq_lines(lines: &[String]) -> Vec<String> {
+    let mut unique = lines.to_vec();
+    unique.dedup();
+    unique
+}
+
+pub fn reverse_lines(lines: &[String]) -> Vec<String> {
+    lines.iter().rev().cloned().collect()
+}
+
+/// Pads the fields between occurrences of `delimiter` so that the delimiters
+/// line up in columns. Lines without the delimiter are left alone.
+pub fn align_lines(lines: &[String], delimiter: &str) -> Vec<String> {
+    if delimiter.is_empty() {
+        return lines.to_vec();
+    }
+
+    let rows: Vec<Option<(&str, Vec<&str>)>> = lines
+        .iter()
+        .map(|line| {
+            if !line.contains(delimiter) {
+                return None;
+            }
+            let indent = leading_indent(line);
+            let cells = line[indent.len()..]
+                .split(delimiter)
+                .map(str::trim)
+                .collect();
+            Some((indent, cells))
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = Vec::new();
+    for (_, cells) in rows.iter().flatten() {
+        for (idx, cell) in cells.iter().enumerate() {
+            if idx >= widths.len() {
+                widths.push(0);
+            }
+            widths[idx] = widths[idx].max(TextUtils::display_width(cell));
+        }
+    }
+
+    lines
+        .iter()
+        .zip(rows)
+        .map(|(line, row)| {
+            let Some((indent, cells)) = row else {
+                return line.clone();
+            };
+
+            let mut out = String::new();
+            for (idx, cell) in cells.iter().enumerate() {
+                if idx > 0 {
+                    if !out.is_empty() {
+                        out.push(' ');
+                    }
+                    out.push_str(delimiter);
+                    out.push(' ');
+                }
+                out.push_str(cell);
+                if idx + 1 < cells.len() {
+                    let padding = widths[idx] - TextUtils::display_width(cell);
+                    out.push_str(&" ".repeat(padding));
+                }
+            }
+            format!("{indent}{}", out.trim_end())
+        })
+        .collect()
+}
+
+/// Joins `lines` into one (`J`), returning it with the column where the last
+/// line was attached. With `adjust_spaces` leading whitespace of each joined
+/// line is replaced by a single space, omitted before `)` and after existing
+/// trailing whitespace; otherwise lines are concatenated as-is (`gJ`).
+pub fn join_lines(lines: &[String], adjust_spaces: bool) -> (String, usize) {
+    let mut joined = lines.first().cloned().unwrap_or_default();
+    let mut join_col = 0;
+
+    for line in lines.iter().skip(1) {
+        if !adjust_spaces {
+            join_col = joined.chars().count();
+            joined.push_str(line);
+            continue;
+        }
+
+        let text = line.trim_start();
+        join_col = joined.chars().count();
+        if !text.is_empty()
+            && !joined.is_empty()
+            && !joined.ends_with(char::is_whitespace)
+            && !text.starts_with(')')
+        {
+            joined.push(' ');
+        }
+        joined.push_str(text);
+    }
+
+    (joined, join_col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sort_flags() {
+        let input = lines(&["b10", "a2", "B1", "a2"]);
+        let options = SortOptions::parse("", false).unwrap();
+        assert_eq!(
+            sort_lines(&input, &options),
+            lines(&["B1", "a2", "a2", "b10"])
+        );
+
+        let options = SortOptions::parse("n u", true).unwrap();
+        assert_eq!(sort_lines(&input, &options), lines(&["b10", "a2", "B1"]));
+
+        let options = SortOptions::parse("i", false).unwrap();
+        assert_eq!(
+            sort_lines(&input, &options),
+            lines(&["a2", "a2", "B1", "b10"])
+        );
+
+        let input = lines(&["x=3 c", "y=1 a", "z=2 b"]);
+        let options = SortOptions::parse(r"/=\d /", false).unwrap();
+        assert_eq!(
+            sort_lines(&input, &options),
+            lines(&["y=1 a", "z=2 b", "x=3 c"])
+        );
+        let options = SortOptions::parse(r"r /\d/", true).unwrap();
+        assert_eq!(
+            sort_lines(&input, &options),
+            lines(&["x=3 c", "z=2 b", "y=1 a"])
+        );
+    }
+
+    #[test]
+    fn test_align_on_delimiter() {
+        let input = lines(&["  a = 1", "  long = 2", "none", "|x|yy|", "|zzz|w|"]);
+        assert_eq!(
+            align_lines(&input[..3], "="),
+            lines(&["  a    = 1", "  long = 2", "none"])
+        );
+        assert_eq!(
+            align_lines(&input[3..], "|"),
+            lines(&["| x   | yy |", "| zzz | w  |"])
+        );
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let input = lines(&["foo(", "    bar", "  )", ""]);
+        assert_eq!(join_lines(&input, true), ("foo( bar)".to_string(), 9));
+        assert_eq!(
+            join_lines(&input[..2], false),
+            ("foo(    bar".to_string(), 4)
+        );
+    }
+}
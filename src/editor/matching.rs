@@ -0,0 +1,524 @@
+use crate::editor::Buffer;
+use crate::syntax::{self, LineState, SyntaxHighlighter};
+use crate::Result;
+use std::cell::RefCell;
+use syntect::parsing::SyntaxReference;
+
+pub const DEFAULT_MATCHPAIRS: &str = "(:),[:],{:}";
+
+/// Lines scanned in each direction when looking for a match to highlight.
+pub const HIGHLIGHT_SCAN_LINES: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchPairs {
+    pairs: Vec<(char, char)>,
+}
+
+impl MatchPairs {
+    /// Parses a Vim-style `matchpairs` value, e.g. `(:),[:],{:},<:>`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut pairs = Vec::new();
+
+        for item in value.split(',').filter(|item| !item.is_empty()) {
+            let chars: Vec<char> = item.chars().collect();
+            if chars.len() != 3 || chars[1] != ':' || chars[0] == chars[2] {
+                return Err(anyhow::anyhow!("Invalid matchpairs item: {}", item));
+            }
+            pairs.push((chars[0], chars[2]));
+        }
+
+        Ok(Self { pairs })
+    }
+
+    /// Returns `(open, close, forward)` for a bracket character.
+    fn lookup(&self, ch: char) -> Option<(char, char, bool)> {
+        self.pairs.iter().find_map(|&(open, close)| {
+            if ch == open {
+                Some((open, close, true))
+            } else if ch == close {
+                Some((open, close, false))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl Default for MatchPairs {
+    fn default() -> Self {
+        Self::parse(DEFAULT_MATCHPAIRS).unwrap_or(Self { pairs: Vec::new() })
+    }
+}
+
+/// A block keyword family such as `if`/`do` ... `end`.
+#[derive(Debug)]
+struct KeywordPair {
+    /// Openers that start a block wherever they appear.
+    openers: &'static [&'static str],
+    /// Openers that only start a block as the first word of a line, so that
+    /// modifiers like Ruby's `return if done` are not counted.
+    leading_openers: &'static [&'static str],
+    closer: &'static str,
+}
+
+impl KeywordPair {
+    fn is_opener(&self, word: &str, leading: bool) -> bool {
+        self.openers.contains(&word) || (leading && self.leading_openers.contains(&word))
+    }
+}
+
+const RUBY_KEYWORDS: &[KeywordPair] = &[KeywordPair {
+    openers: &["do"],
+    leading_openers: &[
+        "if", "unless", "while", "until", "for", "def", "class", "module", "case", "begin",
+    ],
+    closer: "end",
+}];
+
+const LUA_KEYWORDS: &[KeywordPair] = &[
+    KeywordPair {
+        openers: &["function", "if", "do"],
+        leading_openers: &[],
+        closer: "end",
+    },
+    KeywordPair {
+        openers: &["repeat"],
+        leading_openers: &[],
+        closer: "until",
+    },
+];
+
+const SHELL_KEYWORDS: &[KeywordPair] = &[
+    KeywordPair {
+        openers: &["if"],
+        leading_openers: &[],
+        closer: "fi",
+    },
+    KeywordPair {
+        openers: &["case"],
+        leading_openers: &[],
+        closer: "esac",
+    },
+    KeywordPair {
+        openers: &["do"],
+        leading_openers: &[],
+        closer: "done",
+    },
+];
+
+fn keyword_pairs(syntax_name: &str) -> &'static [KeywordPair] {
+    match syntax_name {
+        "Ruby" => RUBY_KEYWORDS,
+        "Lua" => LUA_KEYWORDS,
+        "Bourne Again Shell (bash)" | "Shell-Unix-Generic" => SHELL_KEYWORDS,
+        _ => &[],
+    }
+}
+
+struct Word {
+    start: usize,
+    end: usize,
+    text: String,
+    leading: bool,
+}
+
+/// String-and-comment masks of consecutive lines starting at `first`, and
+/// the parser state after the last of them.
+struct MaskCache {
+    first: usize,
+    masks: Vec<Vec<bool>>,
+    state: LineState,
+}
+
+/// Finds matching brackets and block keywords in a buffer, skipping anything
+/// the syntax highlighter reports as a string or comment.
+pub struct BracketMatcher<'a> {
+    buffer: &'a Buffer,
+    syntax: Option<(&'a SyntaxHighlighter, &'a SyntaxReference)>,
+    pairs: MatchPairs,
+    keywords: &'static [KeywordPair],
+    max_lines: usize,
+    masks: RefCell<Option<MaskCache>>,
+}
+
+impl<'a> BracketMatcher<'a> {
+    pub fn new(
+        buffer: &'a Buffer,
+        syntax: Option<(&'a SyntaxHighlighter, &'a SyntaxReference)>,
+        pairs: MatchPairs,
+    ) -> Self {
+        let keywords = syntax
+            .map(|(_, syntax)| keyword_pairs(&syntax.name))
+            .unwrap_or_default();
+        Self {
+            buffer,
+            syntax,
+            pairs,
+            keywords,
+            max_lines: usize::MAX,
+            masks: RefCell::new(None),
+        }
+    }
+
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Returns the characters of a line and, for each, whether it is code
+    /// (as opposed to part of a string or comment).
+    fn load_line(&self, line_idx: usize) -> (Vec<char>, Vec<bool>) {
+        let line = self.buffer.line(line_idx).unwrap_or_default();
+        let chars: Vec<char> = line.chars().collect();
+        let code = match self.syntax {
+            Some((highlighter, syntax)) => self
+                .literal_mask(highlighter, syntax, line_idx)
+                .into_iter()
+                .map(|is_literal| !is_literal)
+                .collect(),
+            None => vec![true; chars.len()],
+        };
+        (chars, code)
+    }
+
+    /// The string-and-comment mask of a line, parsed together with the lines
+    /// before it so that multi-line comments and strings are seen. Lines are
+    /// parsed once and kept while scanning moves on from them.
+    fn literal_mask(
+        &self,
+        highlighter: &SyntaxHighlighter,
+        syntax: &SyntaxReference,
+        line_idx: usize,
+    ) -> Vec<bool> {
+        let mut cache = self.masks.borrow_mut();
+        if cache.as_ref().is_none_or(|cache| line_idx < cache.first) {
+            *cache = Some(MaskCache {
+                first: line_idx.saturating_sub(syntax::CONTEXT_LINES),
+                masks: Vec::new(),
+                state: highlighter.line_state(syntax),
+            });
+        }
+        let Some(cache) = cache.as_mut() else {
+            return Vec::new();
+        };
+        while cache.first + cache.masks.len() <= line_idx {
+            let line = self
+                .buffer
+                .line(cache.first + cache.masks.len())
+                .unwrap_or_default();
+            let mask = highlighter.literal_mask(&mut cache.state, &line);
+            cache.masks.push(mask);
+        }
+        cache.masks[line_idx - cache.first].clone()
+    }
+
+    /// Returns the bracket matching the one at `(line, col)`, if any.
+    pub fn match_at(&self, line: usize, col: usize) -> Option<(usize, usize)> {
+        let (chars, code) = self.load_line(line);
+        let ch = *chars.get(col)?;
+        if !code.get(col).copied().unwrap_or(true) {
+            return None;
+        }
+        let (open, close, forward) = self.pairs.lookup(ch)?;
+        self.scan_bracket((line, col), open, close, forward)
+    }
+
+    /// Returns the bracket pair under the cursor or immediately before it.
+    pub fn highlight_pair(
+        &self,
+        line: usize,
+        col: usize,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        if let Some(target) = self.match_at(line, col) {
+            return Some(((line, col), target));
+        }
+        let before = col.checked_sub(1)?;
+        self.match_at(line, before)
+            .map(|target| ((line, before), target))
+    }
+
+    /// Computes the destination of `%`: the match of the bracket or keyword
+    /// under the cursor, or of the first bracket after the cursor on the line.
+    pub fn jump_target(&self, line: usize, col: usize) -> Option<(usize, usize)> {
+        if let Some(target) = self.match_at(line, col) {
+            return Some(target);
+        }
+        if let Some(target) = self.keyword_match(line, col) {
+            return Some(target);
+        }
+
+        let (chars, code) = self.load_line(line);
+        (col..chars.len())
+            .find(|&idx| code[idx] && self.pairs.lookup(chars[idx]).is_some())
+            .and_then(|idx| self.match_at(line, idx))
+    }
+
+    /// Returns every bracket region that spans more than one line, as
+    /// `(opening line, closing line)` pairs, for syntax-based folding.
+    pub fn regions(&self) -> Vec<(usize, usize)> {
+        let mut regions = Vec::new();
+        let mut open: Vec<(char, usize)> = Vec::new();
+
+        for line_idx in 0..self.buffer.line_count() {
+            let (chars, code) = self.load_line(line_idx);
+            for (col, &ch) in chars.iter().enumerate() {
+                if !code[col] {
+                    continue;
+                }
+                match self.pairs.lookup(ch) {
+                    Some((_, _, true)) => open.push((ch, line_idx)),
+                    Some((opener, _, false)) => {
+                        if let Some(pos) = open.iter().rposition(|(c, _)| *c == opener) {
+                            let (_, start) = open[pos];
+                            open.truncate(pos);
+                            if line_idx > start && !regions.contains(&(start, line_idx)) {
+                                regions.push((start, line_idx));
+                            }
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        regions.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+        regions.dedup_by_key(|(start, _)| *start);
+        regions
+    }
+
+    fn scan_bracket(
+        &self,
+        start: (usize, usize),
+        open: char,
+        close: char,
+        forward: bool,
+    ) -> Option<(usize, usize)> {
+        let line_count = self.buffer.line_count();
+        let last_line = start
+            .0
+            .saturating_add(self.max_lines)
+            .min(line_count.saturating_sub(1));
+        let first_line = start.0.saturating_sub(self.max_lines);
+        let (push, pop) = if forward {
+            (open, close)
+        } else {
+            (close, open)
+        };
+        let mut depth = 0usize;
+        let mut line_idx = start.0;
+
+        loop {
+            let (chars, code) = self.load_line(line_idx);
+            let columns: Box<dyn Iterator<Item = usize>> = match (forward, line_idx == start.0) {
+                (true, true) => Box::new(start.1..chars.len()),
+                (true, false) => Box::new(0..chars.len()),
+                (false, true) => Box::new((0..=start.1.min(chars.len().saturating_sub(1))).rev()),
+                (false, false) => Box::new((0..chars.len()).rev()),
+            };
+
+            for col in columns {
+                if !code[col] {
+                    continue;
+                }
+                if chars[col] == push {
+                    depth += 1;
+                } else if chars[col] == pop {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return Some((line_idx, col));
+                    }
+                }
+            }
+
+            if forward {
+                if line_idx >= last_line {
+                    return None;
+                }
+                line_idx += 1;
+            } else {
+                if line_idx <= first_line {
+                    return None;
+                }
+                line_idx -= 1;
+            }
+        }
+    }
+
+    fn words(&self, line_idx: usize) -> Vec<Word> {
+        let (chars, code) = self.load_line(line_idx);
+        let mut words = Vec::new();
+        let mut col = 0;
+
+        while col < chars.len() {
+            if !is_word_char(chars[col]) {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < chars.len() && is_word_char(chars[col]) {
+                col += 1;
+            }
+            if code[start] {
+                words.push(Word {
+                    start,
+                    end: col,
+                    text: chars[start..col].iter().collect(),
+                    leading: chars[..start].iter().all(|ch| ch.is_whitespace()),
+                });
+            }
+        }
+
+        words
+    }
+
+    fn keyword_match(&self, line: usize, col: usize) -> Option<(usize, usize)> {
+        if self.keywords.is_empty() {
+            return None;
+        }
+
+        let words = self.words(line);
+        let word_idx = words
+            .iter()
+            .position(|word| word.start <= col && col < word.end)?;
+        let word = &words[word_idx];
+        let family = self.keywords.iter().find(|family| {
+            family.closer == word.text || family.is_opener(&word.text, word.leading)
+        })?;
+        let forward = family.closer != word.text;
+
+        let mut depth = 1usize;
+        let mut line_idx = line;
+        let mut words = words;
+        let mut skip = word_idx + 1;
+
+        loop {
+            let candidates: Vec<&Word> = if forward {
+                words.iter().skip(skip).collect()
+            } else {
+                words.iter().take(skip.saturating_sub(1)).rev().collect()
+            };
+
+            for candidate in candidates {
+                if candidate.text == family.closer {
+                    if forward {
+                        depth -= 1;
+                    } else {
+                        depth += 1;
+                    }
+                } else if family.is_opener(&candidate.text, candidate.leading) {
+                    if forward {
+                        depth += 1;
+                    } else {
+                        depth -= 1;
+                    }
+                }
+                if depth == 0 {
+                    return Some((line_idx, candidate.start));
+                }
+            }
+
+            if forward {
+                line_idx += 1;
+                if line_idx >= self.buffer.line_count() {
+                    return None;
+                }
+                words = self.words(line_idx);
+                skip = 0;
+            } else {
+                line_idx = line_idx.checked_sub(1)?;
+                words = self.words(line_idx);
+                skip = words.len() + 1;
+            }
+        }
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::empty();
+        buffer.content = Rope::from_str(text);
+        buffer
+    }
+
+    #[test]
+    fn test_parse_matchpairs() {
+        let pairs = MatchPairs::parse("(:),<:>").unwrap();
+        assert_eq!(pairs.lookup('>'), Some(('<', '>', false)));
+        assert_eq!(pairs.lookup('['), None);
+        assert!(MatchPairs::parse("(-)").is_err());
+        assert!(MatchPairs::parse("\":\"").is_err());
+    }
+
+    #[test]
+    fn test_match_nested_brackets_across_lines() {
+        let buffer = buffer("fn main() {\n    let v = [(1, 2)];\n}\n");
+        let matcher = BracketMatcher::new(&buffer, None, MatchPairs::default());
+        assert_eq!(matcher.match_at(0, 10), Some((2, 0)));
+        assert_eq!(matcher.match_at(2, 0), Some((0, 10)));
+        assert_eq!(matcher.match_at(1, 12), Some((1, 19)));
+        assert_eq!(matcher.jump_target(1, 0), Some((1, 19)));
+        assert_eq!(matcher.highlight_pair(0, 9), Some(((0, 8), (0, 7))));
+    }
+
+    #[test]
+    fn test_brackets_in_strings_are_ignored() {
+        let highlighter = SyntaxHighlighter::new();
+        let syntax = highlighter
+            .detect_language(Some(std::path::Path::new("main.rs")))
+            .unwrap();
+        let buffer = buffer("foo(\")\", x) // )\n");
+        let matcher =
+            BracketMatcher::new(&buffer, Some((&highlighter, syntax)), MatchPairs::default());
+        assert_eq!(matcher.match_at(0, 3), Some((0, 10)));
+        assert_eq!(matcher.match_at(0, 15), None);
+    }
+
+    #[test]
+    fn test_brackets_in_block_comments_are_ignored() {
+        let highlighter = SyntaxHighlighter::new();
+        let syntax = highlighter
+            .detect_language(Some(std::path::Path::new("main.rs")))
+            .unwrap();
+        let buffer = buffer("fn f() {\n    /* {\n    } */\n}\n");
+        let matcher =
+            BracketMatcher::new(&buffer, Some((&highlighter, syntax)), MatchPairs::default());
+        assert_eq!(matcher.jump_target(0, 7), Some((3, 0)));
+        assert_eq!(matcher.jump_target(3, 0), Some((0, 7)));
+        assert_eq!(matcher.match_at(2, 4), None);
+        assert_eq!(matcher.regions(), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn test_ruby_keywords_skip_modifiers() {
+        let highlighter = SyntaxHighlighter::new();
+        let syntax = highlighter
+            .detect_language(Some(std::path::Path::new("app.rb")))
+            .unwrap();
+        let buffer = buffer("def run\n  return if done\n  items.each do |i|\n  end\nend\n");
+        let matcher =
+            BracketMatcher::new(&buffer, Some((&highlighter, syntax)), MatchPairs::default());
+        assert_eq!(matcher.jump_target(0, 0), Some((4, 0)));
+        assert_eq!(matcher.jump_target(4, 1), Some((0, 0)));
+        assert_eq!(matcher.jump_target(2, 13), Some((3, 2)));
+    }
+
+    #[test]
+    fn test_multi_line_regions() {
+        let highlighter = SyntaxHighlighter::new();
+        let syntax = highlighter
+            .detect_language(Some(std::path::Path::new("main.rs")))
+            .unwrap();
+        let buffer = buffer("fn main() {\n    call(a,\n         b);\n    let s = \"{\";\n}\n");
+        let matcher =
+            BracketMatcher::new(&buffer, Some((&highlighter, syntax)), MatchPairs::default());
+        assert_eq!(matcher.regions(), vec![(0, 4), (1, 2)]);
+    }
+}
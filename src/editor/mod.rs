@@ -1,11 +1,33 @@
 pub mod buffer;
+pub mod case;
 pub mod clipboard;
+pub mod comment;
 pub mod cursor;
+pub mod fold;
+pub mod format;
+pub mod global;
+pub mod increment;
+pub mod indent;
+pub mod lines;
+pub mod matching;
 pub mod modes;
+pub mod pairs;
+pub mod search;
 pub mod selection;
+pub mod substitute;
+pub mod wrap;
 
 pub use buffer::Buffer;
+pub use case::CaseOp;
 pub use clipboard::Clipboard;
+pub use comment::CommentTokens;
 pub use cursor::Cursor;
+pub use fold::FoldSet;
+pub use format::FormatOptions;
+pub use indent::IndentRules;
+pub use matching::{BracketMatcher, MatchPairs};
 pub use modes::Mode;
+pub use pairs::AutoPairs;
+pub use search::{SearchMatch, SearchPattern};
 pub use selection::Selection;
+pub use wrap::{WrapOptions, WrapSegment};
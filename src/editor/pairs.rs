@@ -0,0 +1,82 @@
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AutoPairs {
+    pairs: Vec<(char, char)>,
+}
+
+impl AutoPairs {
+    /// Parses a pair specification made of consecutive opener/closer characters,
+    /// e.g. `()[]{}""`.
+    pub fn parse(spec: &str) -> Self {
+        let chars: Vec<char> = spec.chars().collect();
+        let pairs = chars
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+        Self { pairs }
+    }
+
+    pub fn closer_for(&self, opener: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|(open, _)| *open == opener)
+            .map(|(_, close)| *close)
+    }
+
+    pub fn is_closer(&self, ch: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == ch)
+    }
+
+    pub fn is_quote(&self, ch: char) -> bool {
+        self.pairs
+            .iter()
+            .any(|(open, close)| *open == ch && *close == ch)
+    }
+
+    pub fn is_empty_pair(&self, before: char, after: char) -> bool {
+        self.closer_for(before) == Some(after)
+    }
+
+    /// Decides whether typing `opener` between `before` and `after` should
+    /// insert the matching closer as well.
+    pub fn should_pair(&self, opener: char, before: Option<char>, after: Option<char>) -> bool {
+        if self.closer_for(opener).is_none() {
+            return false;
+        }
+
+        let next_is_free = after.is_none_or(|ch| ch.is_whitespace() || self.is_closer(ch));
+        if !self.is_quote(opener) {
+            return next_is_free;
+        }
+
+        let inside_word = before.is_some_and(is_word_char) || after.is_some_and(is_word_char);
+        next_is_free && !inside_word && before != Some(opener)
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brackets_pair_before_whitespace_or_closer() {
+        let pairs = AutoPairs::parse("()[]{}\"\"");
+        assert!(pairs.should_pair('(', Some('f'), None));
+        assert!(pairs.should_pair('[', None, Some(')')));
+        assert!(!pairs.should_pair('{', None, Some('x')));
+        assert!(!pairs.should_pair('<', None, None));
+    }
+
+    #[test]
+    fn test_quotes_do_not_pair_inside_words() {
+        let pairs = AutoPairs::parse("()\"\"''");
+        assert!(pairs.should_pair('"', Some(' '), None));
+        assert!(!pairs.should_pair('\'', Some('n'), Some(' ')));
+        assert!(!pairs.should_pair('"', Some('"'), None));
+        assert!(pairs.is_empty_pair('(', ')'));
+        assert!(!pairs.is_empty_pair('(', '"'));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::Result;
+use regex::{Regex, RegexBuilder};
+use ropey::Rope;
+
+/// Where the cursor lands relative to a match, as in `/foo/e+1`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchOffset {
+    #[default]
+    None,
+    /// `+n` / `-n`: lines below or above the match, at its first column.
+    Line(isize),
+    /// `s+n` / `b+n`: characters from the start of the match.
+    Start(isize),
+    /// `e+n`: characters from the last character of the match.
+    End(isize),
+}
+
+impl SearchOffset {
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid search offset: {}", text);
+        let number = |text: &str, default: isize| -> Result<isize> {
+            match text {
+                "" => Ok(default),
+                "+" => Ok(1),
+                "-" => Ok(-1),
+                _ => text.parse().map_err(|_| invalid()),
+            }
+        };
+
+        match text.chars().next() {
+            None => Ok(Self::None),
+            Some('s' | 'b') => Ok(Self::Start(number(&text[1..], 0)?)),
+            Some('e') => Ok(Self::End(number(&text[1..], 0)?)),
+            Some('+' | '-' | '0'..='9') => Ok(Self::Line(number(text, 0)?)),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+/// A match as character positions; the end is exclusive and may be on a
+/// later line for patterns containing `\n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub line: usize,
+    pub col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchPattern {
+    /// The pattern as typed, including any `\c` / `\C`.
+    pub source: String,
+    pub regex: Regex,
+    pub offset: SearchOffset,
+}
+
+impl SearchPattern {
+    /// Parses `pattern[<delimiter>offset]`, e.g. `foo/e+1` for `/`. A
+    /// `\<delimiter>` in the pattern stands for the delimiter itself. `\c` or
+    /// `\C` anywhere in the pattern force case-insensitive or case-sensitive
+    /// matching; otherwise `ignorecase` applies, unless `smartcase` is set and
+    /// the pattern contains an uppercase letter.
+    pub fn parse(input: &str, delimiter: char, ignorecase: bool, smartcase: bool) -> Result<Self> {
+        let mut pattern = String::new();
+        let mut case_override = None;
+        let mut has_upper = false;
+        let mut source_end = input.len();
+        let mut offset = "";
+
+        let mut chars = input.char_indices();
+        while let Some((idx, ch)) = chars.next() {
+            if ch == delimiter {
+                source_end = idx;
+                offset = &input[idx + ch.len_utf8()..];
+                break;
+            }
+            if ch != '\\' {
+                has_upper |= ch.is_uppercase();
+                pattern.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some((_, 'c')) => case_override = Some(true),
+                Some((_, 'C')) => case_override = Some(false),
+                Some((_, escaped)) if escaped == delimiter => {
+                    pattern.push_str(&regex::escape(&escaped.to_string()))
+                }
+                Some((_, escaped)) => {
+                    pattern.push('\\');
+                    pattern.push(escaped);
+                }
+                None => pattern.push('\\'),
+            }
+        }
+
+        if pattern.is_empty() {
+            return Err(anyhow::anyhow!("Empty search pattern"));
+        }
+
+        let ignore_case = case_override.unwrap_or(ignorecase && !(smartcase && has_upper));
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .multi_line(true)
+            .build()
+            .map_err(|e| {
+                let reason = e
+                    .to_string()
+                    .lines()
+                    .find_map(|line| line.strip_prefix("error: ").map(String::from))
+                    .unwrap_or_else(|| e.to_string());
+                anyhow::anyhow!("Invalid pattern: {reason}")
+            })?;
+
+        Ok(Self {
+            source: input[..source_end].to_string(),
+            regex,
+            offset: SearchOffset::parse(offset)?,
+        })
+    }
+
+    /// Whether matches can span lines, so they cannot be found line by line.
+    pub fn is_multiline(&self) -> bool {
+        self.regex.as_str().contains("\\n")
+    }
+
+    /// All matches in `text`, in order.
+    pub fn find_all(&self, text: &Rope) -> Vec<SearchMatch> {
+        let content = text.to_string();
+        let position = |byte: usize| {
+            let char_idx = text.byte_to_char(byte);
+            let line = text.char_to_line(char_idx);
+            (line, char_idx - text.line_to_char(line))
+        };
+
+        self.regex
+            .find_iter(&content)
+            .map(|found| {
+                let (line, col) = position(found.start());
+                let (end_line, end_col) = position(found.end());
+                SearchMatch {
+                    line,
+                    col,
+                    end_line,
+                    end_col,
+                }
+            })
+            .collect()
+    }
+
+    /// Byte ranges of the matches on one line, for highlighting.
+    pub fn find_in_line(&self, line: &str) -> Vec<(usize, usize)> {
+        self.regex
+            .find_iter(line)
+            .filter(|found| !found.is_empty())
+            .map(|found| (found.start(), found.end()))
+            .collect()
+    }
+
+    /// Cursor position for a match after applying the search offset.
+    pub fn target(&self, found: &SearchMatch, text: &Rope) -> (usize, usize) {
+        let last_line = text.len_lines().saturating_sub(1);
+        let char_idx = |line: usize, col: usize| text.line_to_char(line) as isize + col as isize;
+        let char_target = match self.offset {
+            SearchOffset::None => return (found.line, found.col),
+            SearchOffset::Line(lines) => {
+                let line = (found.line as isize + lines).clamp(0, last_line as isize);
+                return (line as usize, 0);
+            }
+            SearchOffset::Start(chars) => char_idx(found.line, found.col) + chars,
+            SearchOffset::End(chars) => {
+                let end = char_idx(found.end_line, found.end_col);
+                let start = char_idx(found.line, found.col);
+                (end - 1).max(start) + chars
+            }
+        };
+
+        let char_target =
+            char_target.clamp(0, text.len_chars().saturating_sub(1) as isize) as usize;
+        let line = text.char_to_line(char_target);
+        (line, char_target - text.line_to_char(line))
+    }
+}
+
+/// The word under the cursor, or the next one on the line, as its start
+/// column and text.
+pub fn word_at(line: &str, col: usize) -> Option<(usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_word = |ch: &char| ch.is_alphanumeric() || *ch == '_';
+    let mut start = col.min(chars.len());
+    if chars.get(start).is_some_and(is_word) {
+        while start > 0 && is_word(&chars[start - 1]) {
+            start -= 1;
+        }
+    } else {
+        start += chars[start..].iter().position(is_word)?;
+    }
+    let len = chars[start..].iter().take_while(|ch| is_word(ch)).count();
+    Some((start, chars[start..start + len].iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_case_and_offsets() {
+        let pattern = SearchPattern::parse(r"fn \w+_test/e+1", '/', false, false).unwrap();
+        assert_eq!(pattern.source, r"fn \w+_test");
+        assert_eq!(pattern.offset, SearchOffset::End(1));
+        assert!(pattern.regex.is_match("fn foo_test()"));
+
+        assert!(SearchPattern::parse("Foo", '/', true, true)
+            .unwrap()
+            .find_in_line("foo")
+            .is_empty());
+        assert!(!SearchPattern::parse("foo", '/', true, true)
+            .unwrap()
+            .find_in_line("FOO")
+            .is_empty());
+        assert!(!SearchPattern::parse(r"Foo\c", '/', false, false)
+            .unwrap()
+            .find_in_line("foo")
+            .is_empty());
+        assert!(SearchPattern::parse(r"foo\C", '/', true, false)
+            .unwrap()
+            .find_in_line("FOO")
+            .is_empty());
+        assert_eq!(
+            SearchPattern::parse(r"a\/b?-2", '?', false, false)
+                .unwrap()
+                .offset,
+            SearchOffset::Line(-2)
+        );
+        assert_eq!(
+            SearchPattern::parse(r"a\?b", '?', false, false)
+                .unwrap()
+                .find_in_line("b a?b")
+                .len(),
+            1
+        );
+        assert!(SearchPattern::parse(r"a\?b", '?', false, false)
+            .unwrap()
+            .find_in_line("ab b")
+            .is_empty());
+
+        let error = SearchPattern::parse("fn (", '/', false, false).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid pattern: unclosed group");
+    }
+
+    #[test]
+    fn test_multiline_matches_and_targets() {
+        let text = Rope::from_str("let a = 1;\nlet b =\n  2;\n");
+        let pattern = SearchPattern::parse(r"=\n\s*2", '/', false, false).unwrap();
+        assert!(pattern.is_multiline());
+        let matches = pattern.find_all(&text);
+        assert_eq!(
+            matches,
+            vec![SearchMatch {
+                line: 1,
+                col: 6,
+                end_line: 2,
+                end_col: 3,
+            }]
+        );
+
+        let pattern = SearchPattern::parse(r"\bb\b/e+2", '/', false, false).unwrap();
+        let found = pattern.find_all(&text)[0];
+        assert_eq!(pattern.target(&found, &text), (1, 6));
+        let pattern = SearchPattern::parse("let/+1", '/', false, false).unwrap();
+        assert_eq!(pattern.target(&found, &text), (2, 0));
+    }
+
+    #[test]
+    fn test_word_at() {
+        assert_eq!(
+            word_at("let foo_bar = 1;", 6),
+            Some((4, "foo_bar".to_string()))
+        );
+        assert_eq!(word_at("  = value", 0), Some((4, "value".to_string())));
+        assert_eq!(word_at("a + ", 2), None);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::editor::SearchPattern;
+use crate::Result;
+use regex::Captures;
+
+/// Flags given after `:s/pat/rep/`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SubstituteFlags {
+    /// `g`: replace every match on a line, not just the first.
+    pub global: bool,
+    /// `c`: ask before each replacement.
+    pub confirm: bool,
+    /// `n`: only count the matches.
+    pub count_only: bool,
+    /// `e`: no error when the pattern is not found.
+    pub quiet: bool,
+}
+
+/// A parsed `:s/pattern/replacement/flags` command.
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    pub pattern: SearchPattern,
+    pub replacement: String,
+    pub flags: SubstituteFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CaseChange {
+    Upper,
+    Lower,
+}
+
+/// Replacement text being built, with the pending `\u`/`\l` and `\U`/`\L`
+/// case changes.
+#[derive(Default)]
+struct Replacement {
+    text: String,
+    next_case: Option<CaseChange>,
+    case: Option<CaseChange>,
+}
+
+impl Replacement {
+    fn push(&mut self, ch: char) {
+        match self.next_case.take().or(self.case) {
+            Some(CaseChange::Upper) => self.text.extend(ch.to_uppercase()),
+            Some(CaseChange::Lower) => self.text.extend(ch.to_lowercase()),
+            None => self.text.push(ch),
+        }
+    }
+
+    fn push_str(&mut self, text: &str) {
+        text.chars().for_each(|ch| self.push(ch));
+    }
+}
+
+pub(crate) fn is_delimiter(ch: char) -> bool {
+    !ch.is_alphanumeric() && !ch.is_whitespace() && !matches!(ch, '\\' | '"' | '|')
+}
+
+/// Splits `text` at the first `delimiter` not preceded by a backslash,
+/// keeping escapes as typed.
+pub(crate) fn split_field(text: &str, delimiter: char) -> (&str, Option<&str>) {
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == delimiter {
+            return (&text[..idx], Some(&text[idx + ch.len_utf8()..]));
+        }
+    }
+    (text, None)
+}
+
+impl Substitution {
+    /// Parses `/pattern/replacement/flags` (any punctuation works as the
+    /// delimiter). An empty pattern reuses `last_pattern`, the last search.
+    pub fn parse(
+        args: &str,
+        last_pattern: Option<&SearchPattern>,
+        ignorecase: bool,
+        smartcase: bool,
+    ) -> Result<Self> {
+        let args = args.trim_start();
+        let delimiter = args
+            .chars()
+            .next()
+            .filter(|&ch| is_delimiter(ch))
+            .ok_or_else(|| anyhow::anyhow!("Usage: :[range]s/pattern/replacement/[flags]"))?;
+
+        let (pattern, rest) = split_field(&args[delimiter.len_utf8()..], delimiter);
+        let (replacement, flags_text) = match rest {
+            Some(rest) => split_field(rest, delimiter),
+            None => ("", None),
+        };
+
+        let mut flags = SubstituteFlags::default();
+        let mut case_suffix = "";
+        for ch in flags_text.unwrap_or("").trim().chars() {
+            match ch {
+                'g' => flags.global = true,
+                'c' => flags.confirm = true,
+                'n' => flags.count_only = true,
+                'e' => flags.quiet = true,
+                'i' => case_suffix = "\\c",
+                'I' => case_suffix = "\\C",
+                _ => return Err(anyhow::anyhow!("Invalid substitute flag: {ch}")),
+            }
+        }
+
+        let pattern = if pattern.is_empty() {
+            last_pattern
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("No previous search pattern"))?
+        } else {
+            SearchPattern::parse(
+                &format!("{pattern}{case_suffix}"),
+                delimiter,
+                ignorecase,
+                smartcase,
+            )?
+        };
+
+        Ok(Self {
+            pattern,
+            replacement: replacement.to_string(),
+            flags,
+        })
+    }
+
+    /// Builds the replacement text for one match. `&`, `\0`, `\1`..`\9` and
+    /// `$1`..`$9` insert the match or a group; `\u`/`\l` change the case of
+    /// the next character and `\U`/`\L` of everything up to `\E`; `\r` and
+    /// `\n` insert a line break.
+    pub fn expand(&self, caps: &Captures) -> String {
+        let mut out = Replacement::default();
+        let group = |index: usize| caps.get(index).map_or("", |m| m.as_str());
+
+        let mut chars = self.replacement.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '&' => out.push_str(group(0)),
+                '$' if chars.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                    let digit = chars.next().and_then(|c| c.to_digit(10)).unwrap_or(0);
+                    out.push_str(group(digit as usize));
+                }
+                '\\' => match chars.next() {
+                    Some(digit @ '0'..='9') => {
+                        out.push_str(group(digit.to_digit(10).unwrap_or(0) as usize));
+                    }
+                    Some('u') => out.next_case = Some(CaseChange::Upper),
+                    Some('l') => out.next_case = Some(CaseChange::Lower),
+                    Some('U') => out.case = Some(CaseChange::Upper),
+                    Some('L') => out.case = Some(CaseChange::Lower),
+                    Some('E' | 'e') => out.case = None,
+                    Some('r' | 'n') => out.text.push('\n'),
+                    Some('t') => out.text.push('\t'),
+                    Some(other) => out.push(other),
+                    None => out.text.push('\\'),
+                },
+                _ => out.push(ch),
+            }
+        }
+        out.text
+    }
+
+    /// Where to look for the next match after a match (or its replacement)
+    /// ending at `end` in `text`; `empty` is true for an empty match. Without
+    /// `g` that is the following line.
+    pub fn resume_at(&self, text: &str, end: usize, empty: bool) -> Option<usize> {
+        if !self.flags.global {
+            return text[end..].find('\n').map(|idx| end + idx + 1);
+        }
+        if !empty {
+            return Some(end);
+        }
+        text[end..].chars().next().map(|ch| end + ch.len_utf8())
+    }
+
+    /// Replaces the matches in `text` (lines joined with `\n`). Returns the
+    /// new text and, for each replacement, the line it starts on.
+    pub fn apply(&self, text: &str) -> (String, Vec<usize>) {
+        let mut result = String::new();
+        let mut lines = Vec::new();
+        let mut copied = 0;
+        let mut from = Some(0);
+        let mut last_end = None;
+
+        while let Some(caps) = from.and_then(|from| self.pattern.regex.captures_at(text, from)) {
+            let Some(found) = caps.get(0) else {
+                break;
+            };
+            from = self.resume_at(text, found.end(), found.is_empty());
+            // An empty match right after a replaced one is not a new match.
+            if found.is_empty() && last_end == Some(found.start()) {
+                continue;
+            }
+
+            result.push_str(&text[copied..found.start()]);
+            lines.push(result.matches('\n').count());
+            result.push_str(&self.expand(&caps));
+            copied = found.end();
+            last_end = Some(found.end());
+        }
+
+        result.push_str(&text[copied..]);
+        (result, lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn substitute(args: &str, text: &str) -> String {
+        Substitution::parse(args, None, false, false)
+            .unwrap()
+            .apply(text)
+            .0
+    }
+
+    #[test]
+    fn test_flags_and_delimiters() {
+        assert_eq!(substitute("/a/b/", "aaa\naa"), "baa\nba");
+        assert_eq!(substitute("/a/b/g", "aaa\naa"), "bbb\nbb");
+        assert_eq!(substitute("/A/b/gi", "aA"), "bb");
+        assert_eq!(substitute("#/#\\##", "a/b"), "a#b");
+        assert_eq!(substitute("+a\\+b+X+", "a+b aab"), "X aab");
+        assert_eq!(substitute("/^/# /", "a\nb"), "# a\n# b");
+        assert_eq!(substitute("/,\\s*/\\r/g", "a, b,c"), "a\nb\nc");
+
+        let (_, lines) = Substitution::parse("/x/y/g", None, false, false)
+            .unwrap()
+            .apply("x\n\nxx");
+        assert_eq!(lines, vec![0, 2, 2]);
+
+        assert!(Substitution::parse("/a/b/z", None, false, false).is_err());
+    }
+
+    #[test]
+    fn test_replacement_groups_and_case() {
+        assert_eq!(
+            substitute(r"/(\w+) (\w+)/\2 \1 [&] $1/", "hello world"),
+            "world hello [hello world] hello"
+        );
+        assert_eq!(substitute(r"/(\w+)/\u\1/g", "foo bar"), "Foo Bar");
+        assert_eq!(substitute(r"/(\w+) (\w+)/\U\1\E \2/", "foo bar"), "FOO bar");
+        assert_eq!(substitute(r"/\w+/\L\u&/", "hELLO"), "Hello");
+    }
+
+    #[test]
+    fn test_whole_file_with_trailing_newline() {
+        use crate::editor::Buffer;
+        use crate::input::range::{Range, RangeContext};
+        use ropey::Rope;
+
+        for (args, expected) in [("/^/# /", "# b\n# a\n"), ("/$/;/", "b;\na;\n")] {
+            let mut buffer = Buffer::empty();
+            buffer.content = Rope::from_str("b\na\n");
+            let range = Range::Whole
+                .resolve(&RangeContext::new(&buffer, 0, None))
+                .unwrap();
+
+            // What `:%s` does with the range.
+            let text = (range.start..=range.end)
+                .map(|line| buffer.line(line).unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let new_lines: Vec<String> = substitute(args, &text)
+                .split('\n')
+                .map(String::from)
+                .collect();
+            buffer.replace_lines(range.start, range.end, &new_lines);
+            assert_eq!(buffer.content.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::config::settings::EditorConfig;
+use crate::editor::indent::leading_indent;
+use crate::utils::TextUtils;
+
+/// Narrowest text area kept on continuation lines before `breakindent` is dropped.
+const MIN_BREAK_WIDTH: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrapOptions {
+    pub width: usize,
+    pub tab_size: usize,
+    pub linebreak: bool,
+    pub breakindent: bool,
+    pub showbreak: String,
+    /// Columns reserved after the last character, e.g. for the `eol` listchar.
+    pub eol_width: usize,
+}
+
+impl WrapOptions {
+    pub fn new(config: &EditorConfig, width: usize, eol_width: usize) -> Self {
+        Self {
+            width,
+            tab_size: config.tab_size.max(1),
+            linebreak: config.linebreak,
+            breakindent: config.breakindent,
+            showbreak: config.showbreak.clone(),
+            eol_width,
+        }
+    }
+}
+
+/// One screen row of a wrapped line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WrapSegment {
+    /// First character of the row.
+    pub start: usize,
+    /// One past the last character of the row.
+    pub end: usize,
+    /// Display column of `start` within the unwrapped line.
+    pub start_col: usize,
+    /// Display column of `end` within the unwrapped line.
+    pub end_col: usize,
+    /// Columns of break indent and `showbreak` drawn before the text.
+    pub prefix_width: usize,
+}
+
+impl WrapSegment {
+    /// Screen column of display column `col` within this row.
+    pub fn screen_col(&self, col: usize) -> usize {
+        self.prefix_width + col.saturating_sub(self.start_col)
+    }
+}
+
+/// Splits `line` into screen rows of at most `options.width` columns,
+/// preferring to break after whitespace when `linebreak` is set.
+pub fn wrap_line(line: &str, options: &WrapOptions) -> Vec<WrapSegment> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut widths = Vec::with_capacity(chars.len());
+    let mut total = 0;
+    for &ch in &chars {
+        let width = if ch == '\t' {
+            TextUtils::tab_width(total, options.tab_size)
+        } else {
+            TextUtils::char_width(ch)
+        };
+        widths.push(width);
+        total += width;
+    }
+
+    let width = options.width.max(1);
+    if total + options.eol_width <= width {
+        return vec![WrapSegment {
+            start: 0,
+            end: chars.len(),
+            start_col: 0,
+            end_col: total,
+            prefix_width: 0,
+        }];
+    }
+
+    let prefix_width = continuation_prefix(line, options);
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut col = 0;
+
+    while start < chars.len() {
+        let prefix = if segments.is_empty() { 0 } else { prefix_width };
+        let available = width - prefix;
+
+        let mut end = start;
+        let mut used = 0;
+        while end < chars.len() && used + widths[end] <= available {
+            used += widths[end];
+            end += 1;
+        }
+        if end == start {
+            used = widths[start];
+            end = start + 1;
+        }
+
+        if options.linebreak && end < chars.len() {
+            if let Some(brk) = (start + 1..=end)
+                .rev()
+                .find(|&idx| chars[idx - 1].is_whitespace())
+            {
+                used -= widths[brk..end].iter().sum::<usize>();
+                end = brk;
+            }
+        }
+
+        segments.push(WrapSegment {
+            start,
+            end,
+            start_col: col,
+            end_col: col + used,
+            prefix_width: prefix,
+        });
+        start = end;
+        col += used;
+    }
+
+    if let Some(last) = segments.last().copied() {
+        let available = width - last.prefix_width;
+        if last.end_col - last.start_col + options.eol_width > available {
+            segments.push(WrapSegment {
+                start: last.end,
+                end: last.end,
+                start_col: last.end_col,
+                end_col: last.end_col,
+                prefix_width,
+            });
+        }
+    }
+
+    segments
+}
+
+fn continuation_prefix(line: &str, options: &WrapOptions) -> usize {
+    let width = options.width.max(1);
+    let showbreak = TextUtils::display_width(&options.showbreak);
+    let indent = if options.breakindent {
+        let indent_chars = leading_indent(line).chars().count();
+        TextUtils::display_column(line, indent_chars, options.tab_size)
+    } else {
+        0
+    };
+
+    if indent + showbreak + MIN_BREAK_WIDTH.min(width / 2).max(1) <= width {
+        indent + showbreak
+    } else if showbreak < width {
+        showbreak
+    } else {
+        0
+    }
+}
+
+/// Returns the row of `segments` that holds the cursor at character `col`.
+pub fn segment_index(segments: &[WrapSegment], col: usize) -> usize {
+    segments
+        .iter()
+        .position(|segment| segment.start <= col && col < segment.end)
+        .unwrap_or(segments.len().saturating_sub(1))
+}
+
+/// Picks the character of `segment` drawn at or just before screen column
+/// `screen_col`. Only the last row of a line may place the cursor after its
+/// final character.
+pub fn col_at_screen_col(
+    line: &str,
+    segment: &WrapSegment,
+    screen_col: usize,
+    is_last: bool,
+    tab_size: usize,
+) -> usize {
+    let mut col = segment.start;
+    let mut display = segment.start_col;
+
+    for (idx, ch) in line
+        .chars()
+        .enumerate()
+        .skip(segment.start)
+        .take(segment.end - segment.start)
+    {
+        if segment.screen_col(display) > screen_col {
+            return col;
+        }
+        col = idx;
+        display += if ch == '\t' {
+            TextUtils::tab_width(display, tab_size)
+        } else {
+            TextUtils::char_width(ch)
+        };
+    }
+
+    if is_last && segment.screen_col(display) <= screen_col {
+        segment.end
+    } else {
+        col
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(width: usize) -> WrapOptions {
+        WrapOptions {
+            width,
+            tab_size: 4,
+            linebreak: true,
+            breakindent: true,
+            showbreak: String::new(),
+            eol_width: 0,
+        }
+    }
+
+    fn rows(line: &str, options: &WrapOptions) -> Vec<String> {
+        wrap_line(line, options)
+            .iter()
+            .map(|segment| {
+                line.chars()
+                    .skip(segment.start)
+                    .take(segment.end - segment.start)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_wraps_at_word_boundaries() {
+        let line = "the quick brown fox jumps";
+        assert_eq!(
+            rows(line, &options(12)),
+            vec!["the quick ", "brown fox ", "jumps"]
+        );
+
+        let mut hard = options(12);
+        hard.linebreak = false;
+        assert_eq!(rows(line, &hard), vec!["the quick br", "own fox jump", "s"]);
+    }
+
+    #[test]
+    fn test_continuation_rows_keep_indent_and_showbreak() {
+        let mut options = options(40);
+        options.showbreak = "↪ ".to_string();
+        let line = "    // a long comment that needs to wrap onto another row";
+        let segments = wrap_line(line, &options);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].prefix_width, 0);
+        assert_eq!(segments[1].prefix_width, 6);
+        assert!(segments[1].end_col - segments[1].start_col <= 34);
+    }
+
+    #[test]
+    fn test_cursor_columns_map_between_rows() {
+        let line = "the quick brown fox jumps";
+        let segments = wrap_line(line, &options(12));
+        assert_eq!(segment_index(&segments, 4), 0);
+        assert_eq!(segment_index(&segments, 10), 1);
+        assert_eq!(segment_index(&segments, 25), 2);
+        assert_eq!(col_at_screen_col(line, &segments[1], 4, false, 4), 14);
+        assert_eq!(col_at_screen_col(line, &segments[2], 9, true, 4), 25);
+        assert_eq!(col_at_screen_col(line, &segments[0], 11, false, 4), 9);
+    }
+}
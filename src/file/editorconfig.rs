@@ -0,0 +1,325 @@
+use globset::GlobBuilder;
+use std::path::Path;
+
+pub const EDITORCONFIG_FILE: &str = ".editorconfig";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "lf" => Some(Self::Lf),
+            "crlf" => Some(Self::Crlf),
+            "cr" => Some(Self::Cr),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Latin1,
+    Utf16Be,
+    Utf16Le,
+}
+
+impl Charset {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "utf-8" => Some(Self::Utf8),
+            "utf-8-bom" => Some(Self::Utf8Bom),
+            "latin1" => Some(Self::Latin1),
+            "utf-16be" => Some(Self::Utf16Be),
+            "utf-16le" => Some(Self::Utf16Le),
+            _ => None,
+        }
+    }
+
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf8Bom => [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat(),
+            Self::Latin1 => text
+                .chars()
+                .map(|ch| u8::try_from(u32::from(ch)).unwrap_or(b'?'))
+                .collect(),
+            Self::Utf16Be => [0xFEFF]
+                .into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            Self::Utf16Le => [0xFEFF]
+                .into_iter()
+                .chain(text.encode_utf16())
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+        }
+    }
+
+    /// Decodes file contents, detecting byte order marks and falling back to
+    /// Latin-1 for bytes that are not valid UTF-8.
+    pub fn decode(bytes: &[u8]) -> (String, Self) {
+        let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| from_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        };
+
+        match bytes {
+            [0xEF, 0xBB, 0xBF, rest @ ..] => {
+                (String::from_utf8_lossy(rest).into_owned(), Self::Utf8Bom)
+            }
+            [0xFE, 0xFF, rest @ ..] => (utf16(rest, u16::from_be_bytes), Self::Utf16Be),
+            [0xFF, 0xFE, rest @ ..] => (utf16(rest, u16::from_le_bytes), Self::Utf16Le),
+            _ => match std::str::from_utf8(bytes) {
+                Ok(text) => (text.to_string(), Self::Utf8),
+                Err(_) => (
+                    bytes.iter().map(|&byte| char::from(byte)).collect(),
+                    Self::Latin1,
+                ),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndentSize {
+    Columns(usize),
+    /// `indent_size = tab`: indent by one tab of `tab_width` columns.
+    Tab,
+}
+
+/// Properties from the `.editorconfig` files that apply to one file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EditorConfigProperties {
+    /// `true` for `indent_style = space`.
+    pub indent_with_spaces: Option<bool>,
+    pub indent_size: Option<IndentSize>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<Charset>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    /// `max_line_length`, with `off` stored as 0.
+    pub max_line_length: Option<usize>,
+}
+
+impl EditorConfigProperties {
+    /// Collects the properties for `path` from `.editorconfig` files in its
+    /// directory and every parent up to the first one marked `root = true`.
+    /// Files closer to `path` take precedence.
+    pub fn for_file(path: &Path) -> Self {
+        let path = path
+            .canonicalize()
+            .unwrap_or_else(|_| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let config_path = dir.join(EDITORCONFIG_FILE);
+            let Ok(content) = std::fs::read_to_string(&config_path) else {
+                continue;
+            };
+            let is_root = is_root(&content);
+            files.push((dir.to_path_buf(), content));
+            if is_root {
+                break;
+            }
+        }
+
+        let mut properties = Self::default();
+        for (dir, content) in files.iter().rev() {
+            if let Ok(relative) = path.strip_prefix(dir) {
+                properties.apply_file(content, &relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        properties
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies the sections of one `.editorconfig` file matching `relative_path`.
+    fn apply_file(&mut self, content: &str, relative_path: &str) {
+        let mut matches = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                matches = section_matches(section, relative_path);
+                continue;
+            }
+
+            if matches {
+                if let Some((key, value)) = line.split_once('=') {
+                    self.set(&key.trim().to_lowercase(), &value.trim().to_lowercase());
+                }
+            }
+        }
+    }
+
+    /// Sets one property. Unknown values, including `unset`, clear it.
+    fn set(&mut self, key: &str, value: &str) {
+        let number = || value.parse::<usize>().ok().filter(|n| *n > 0);
+        let flag = || match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+        match key {
+            "indent_style" => {
+                self.indent_with_spaces = match value {
+                    "space" => Some(true),
+                    "tab" => Some(false),
+                    _ => None,
+                }
+            }
+            "indent_size" => {
+                self.indent_size = if value == "tab" {
+                    Some(IndentSize::Tab)
+                } else {
+                    number().map(IndentSize::Columns)
+                }
+            }
+            "tab_width" => self.tab_width = number(),
+            "end_of_line" => self.end_of_line = LineEnding::parse(value),
+            "charset" => self.charset = Charset::parse(value),
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag(),
+            "insert_final_newline" => self.insert_final_newline = flag(),
+            "max_line_length" => {
+                self.max_line_length = if value == "off" { Some(0) } else { number() }
+            }
+            _ => {}
+        }
+    }
+
+    /// Width used both for tabs and indentation, since the editor has one
+    /// `tab_size` setting.
+    pub fn tab_size(&self) -> Option<usize> {
+        match self.indent_size {
+            Some(IndentSize::Columns(size)) => Some(size),
+            Some(IndentSize::Tab) | None => self.tab_width,
+        }
+    }
+}
+
+fn is_root(content: &str) -> bool {
+    content
+        .lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .any(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true")
+        })
+}
+
+/// Matches a section glob against a path relative to the `.editorconfig`
+/// directory. Globs without a `/` match the file name at any depth.
+fn section_matches(section: &str, relative_path: &str) -> bool {
+    let pattern = expand_numeric_ranges(section);
+    let pattern = if let Some(anchored) = pattern.strip_prefix('/') {
+        anchored.to_string()
+    } else if pattern.contains('/') {
+        pattern
+    } else {
+        format!("**/{pattern}")
+    };
+
+    GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .empty_alternates(true)
+        .build()
+        .map(|glob| glob.compile_matcher().is_match(relative_path))
+        .unwrap_or(false)
+}
+
+/// Rewrites `{1..3}` as `{1,2,3}`, which the glob matcher understands.
+fn expand_numeric_ranges(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut rest = pattern;
+
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+        out.push_str(&rest[..open]);
+        let inner = &rest[open + 1..close];
+        let range = inner
+            .split_once("..")
+            .and_then(|(from, to)| Some((from.parse::<i64>().ok()?, to.parse::<i64>().ok()?)))
+            .filter(|(from, to)| to.abs_diff(*from) <= 1000);
+        match range {
+            Some((from, to)) => {
+                let values: Vec<String> = (from.min(to)..=from.max(to))
+                    .map(|n| n.to_string())
+                    .collect();
+                out.push_str(&format!("{{{}}}", values.join(",")));
+            }
+            None => out.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_and_precedence() {
+        let content = "root = true\n\n[*]\nindent_style = space\nindent_size = 4\n\n\
+                       [*.{md,txt}]\ntrim_trailing_whitespace = false\nmax_line_length = 80\n\n\
+                       [Makefile]\nindent_style = tab\n\n[/docs/v{1..3}.md]\nindent_size = 2\n";
+        assert!(is_root(content));
+
+        let mut properties = EditorConfigProperties::default();
+        properties.apply_file(content, "src/Makefile");
+        assert_eq!(properties.indent_with_spaces, Some(false));
+        assert_eq!(properties.tab_size(), Some(4));
+
+        let mut properties = EditorConfigProperties::default();
+        properties.apply_file(content, "docs/v2.md");
+        assert_eq!(properties.indent_size, Some(IndentSize::Columns(2)));
+        assert_eq!(properties.trim_trailing_whitespace, Some(false));
+        assert_eq!(properties.max_line_length, Some(80));
+
+        let mut properties = EditorConfigProperties::default();
+        properties.apply_file(content, "notes/v2.md");
+        assert_eq!(properties.indent_size, Some(IndentSize::Columns(4)));
+    }
+
+    #[test]
+    fn test_charset_round_trip() {
+        for charset in [Charset::Utf8Bom, Charset::Utf16Le, Charset::Utf16Be] {
+            let bytes = charset.encode("héllo\n");
+            assert_eq!(Charset::decode(&bytes), ("héllo\n".to_string(), charset));
+        }
+        assert_eq!(Charset::Latin1.encode("é"), vec![0xE9]);
+        assert_eq!(Charset::decode(&[0xE9]), ("é".to_string(), Charset::Latin1));
+    }
+}
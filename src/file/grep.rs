@@ -0,0 +1,132 @@
+use crate::file::index::project_walker;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Files larger than this are skipped.
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// One matching line found by `:grep`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrepMatch {
+    pub path: PathBuf,
+    /// Zero-based line and character column of the match.
+    pub line: usize,
+    pub col: usize,
+    /// The matching line, trimmed, for the quickfix preview.
+    pub text: String,
+}
+
+enum GrepMessage {
+    Matches(Vec<GrepMatch>),
+    Done,
+}
+
+/// A `:grep` running on a background thread. Dropping it stops the search
+/// once the thread next tries to report.
+pub struct GrepSearch {
+    receiver: Receiver<GrepMessage>,
+    pub finished: bool,
+}
+
+impl GrepSearch {
+    /// Searches the files under `root` that [`project_walker`] visits.
+    pub fn start(regex: Regex, root: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || walk(&regex, &root, &sender));
+        Self {
+            receiver,
+            finished: false,
+        }
+    }
+
+    /// Matches found since the last poll.
+    pub fn poll(&mut self) -> Vec<GrepMatch> {
+        let mut matches = Vec::new();
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                GrepMessage::Matches(found) => matches.extend(found),
+                GrepMessage::Done => self.finished = true,
+            }
+        }
+        matches
+    }
+}
+
+fn walk(regex: &Regex, root: &Path, sender: &Sender<GrepMessage>) {
+    for entry in project_walker(root).build().flatten() {
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        if entry
+            .metadata()
+            .is_ok_and(|metadata| metadata.len() > MAX_FILE_SIZE)
+        {
+            continue;
+        }
+        let matches = search_file(regex, entry.path());
+        if !matches.is_empty() && sender.send(GrepMessage::Matches(matches)).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(GrepMessage::Done);
+}
+
+/// The first match on each line of `path`; binary and unreadable files
+/// have none.
+pub fn search_file(regex: &Regex, path: &Path) -> Vec<GrepMatch> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    if content.contains('\0') {
+        return Vec::new();
+    }
+
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let found = regex.find(text)?;
+            Some(GrepMatch {
+                path: path.to_path_buf(),
+                line,
+                col: text[..found.start()].chars().count(),
+                text: text.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_grep_respects_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {\n    todo!()\n}\n").unwrap();
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "todo!()\n").unwrap();
+
+        let mut search = GrepSearch::start(Regex::new("todo").unwrap(), dir.path().into());
+        let mut matches = Vec::new();
+        let started = Instant::now();
+        while !search.finished && started.elapsed() < Duration::from_secs(5) {
+            matches.extend(search.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(
+            matches,
+            vec![GrepMatch {
+                path: dir.path().join("main.rs"),
+                line: 1,
+                col: 4,
+                text: "todo!()".to_string(),
+            }]
+        );
+    }
+}
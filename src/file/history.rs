@@ -0,0 +1,135 @@
+use crate::Result;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Entries kept in each history; older ones are dropped.
+pub const HISTORY_SIZE: usize = 200;
+
+/// Which prompt a history belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryKind {
+    /// `:` command lines.
+    Command,
+    /// `/` and `?` search patterns.
+    Search,
+}
+
+/// Command-line and search history that survives restarts. Entries are
+/// oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub searches: Vec<String>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let Some(path) = Self::history_file_path() else {
+            return Self::default();
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| toml::from_str(&content).map_err(anyhow::Error::from))
+        {
+            Ok(history) => history,
+            Err(e) => {
+                log::warn!("Failed to load history from {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(path) = Self::history_file_path() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create history directory: {}", parent.display())
+                })?;
+            }
+
+            let content =
+                toml::to_string_pretty(self).with_context(|| "Failed to serialize history")?;
+            std::fs::write(&path, content)
+                .with_context(|| format!("Failed to write history: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    pub fn history_file_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("neocrim").join("history.toml"))
+    }
+
+    pub fn entries(&self, kind: HistoryKind) -> &[String] {
+        match kind {
+            HistoryKind::Command => &self.commands,
+            HistoryKind::Search => &self.searches,
+        }
+    }
+
+    /// Adds `entry` as the newest entry, moving it there if it is already
+    /// in the history.
+    pub fn add(&mut self, kind: HistoryKind, entry: &str) {
+        if entry.trim().is_empty() {
+            return;
+        }
+        let entries = match kind {
+            HistoryKind::Command => &mut self.commands,
+            HistoryKind::Search => &mut self.searches,
+        };
+        entries.retain(|existing| existing != entry);
+        entries.push(entry.to_string());
+        if entries.len() > HISTORY_SIZE {
+            entries.drain(..entries.len() - HISTORY_SIZE);
+        }
+    }
+
+    /// The index of the next entry older (or, with `older` false, newer)
+    /// than `from` that starts with `prefix`. Browsing starts from `None`,
+    /// past the newest entry.
+    pub fn find(
+        &self,
+        kind: HistoryKind,
+        from: Option<usize>,
+        prefix: &str,
+        older: bool,
+    ) -> Option<usize> {
+        let entries = self.entries(kind);
+        let from = from.unwrap_or(entries.len());
+        let matches = |&idx: &usize| entries[idx].starts_with(prefix);
+        if older {
+            (0..from).rev().find(matches)
+        } else {
+            (from + 1..entries.len()).find(matches)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_find_by_prefix() {
+        let mut history = History::default();
+        for entry in ["set nu", "w", "set list", "w"] {
+            history.add(HistoryKind::Command, entry);
+        }
+        history.add(HistoryKind::Search, "foo");
+        assert_eq!(history.commands, ["set nu", "set list", "w"]);
+
+        let kind = HistoryKind::Command;
+        assert_eq!(history.find(kind, None, "set", true), Some(1));
+        assert_eq!(history.find(kind, Some(1), "set", true), Some(0));
+        assert_eq!(history.find(kind, Some(0), "set", true), None);
+        assert_eq!(history.find(kind, Some(0), "set", false), Some(1));
+        assert_eq!(history.find(kind, Some(1), "set", false), None);
+        assert_eq!(history.find(HistoryKind::Search, None, "", true), Some(0));
+    }
+}
@@ -0,0 +1,73 @@
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Paths are sent to the UI in batches of this size.
+const BATCH_SIZE: usize = 512;
+
+/// Walks the project under `root` the way `git` sees it: hidden files and
+/// anything excluded by `.gitignore` or `.ignore` files are skipped.
+pub fn project_walker(root: &Path) -> WalkBuilder {
+    let mut walker = WalkBuilder::new(root);
+    walker.require_git(false).sort_by_file_path(|a, b| a.cmp(b));
+    walker
+}
+
+enum IndexMessage {
+    Files(Vec<PathBuf>),
+    Done,
+}
+
+/// The files of a project, listed on a background thread for the fuzzy
+/// finder. Dropping it stops the walk.
+pub struct FileIndex {
+    pub root: PathBuf,
+    receiver: Receiver<IndexMessage>,
+    pub finished: bool,
+}
+
+impl FileIndex {
+    pub fn start(root: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let walk_root = root.clone();
+        thread::spawn(move || walk(&walk_root, &sender));
+        Self {
+            root,
+            receiver,
+            finished: false,
+        }
+    }
+
+    /// Paths, relative to the root, found since the last poll.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                IndexMessage::Files(batch) => files.extend(batch),
+                IndexMessage::Done => self.finished = true,
+            }
+        }
+        files
+    }
+}
+
+fn walk(root: &Path, sender: &Sender<IndexMessage>) {
+    let mut batch = Vec::new();
+    for entry in project_walker(root).build().flatten() {
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        batch.push(path.to_path_buf());
+        if batch.len() == BATCH_SIZE
+            && sender
+                .send(IndexMessage::Files(std::mem::take(&mut batch)))
+                .is_err()
+        {
+            return;
+        }
+    }
+    let _ = sender.send(IndexMessage::Files(batch));
+    let _ = sender.send(IndexMessage::Done);
+}
@@ -1,5 +1,15 @@
+pub mod editorconfig;
+pub mod grep;
+pub mod history;
+pub mod index;
 pub mod io;
+pub mod view_state;
 pub mod watcher;
 
+pub use editorconfig::EditorConfigProperties;
+pub use grep::{GrepMatch, GrepSearch};
+pub use history::{History, HistoryKind};
+pub use index::FileIndex;
 pub use io::FileManager;
+pub use view_state::{ViewState, ViewStore};
 pub use watcher::FileWatcher;
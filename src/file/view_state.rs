@@ -0,0 +1,78 @@
+use crate::editor::fold::Fold;
+use crate::Result;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Per-file view state that survives restarts: cursor position and folds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewState {
+    pub cursor_line: usize,
+    pub cursor_col: usize,
+    #[serde(default)]
+    pub folds: Vec<Fold>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewStore {
+    #[serde(default)]
+    views: HashMap<String, ViewState>,
+}
+
+impl ViewStore {
+    pub fn load() -> Self {
+        let Some(path) = Self::view_file_path() else {
+            return Self::default();
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| toml::from_str(&content).map_err(anyhow::Error::from))
+        {
+            Ok(store) => store,
+            Err(e) => {
+                log::warn!("Failed to load view state from {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(path) = Self::view_file_path() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create view directory: {}", parent.display())
+                })?;
+            }
+
+            let content =
+                toml::to_string_pretty(self).with_context(|| "Failed to serialize view state")?;
+            std::fs::write(&path, content)
+                .with_context(|| format!("Failed to write view state: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    pub fn view_file_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("neocrim").join("views.toml"))
+    }
+
+    pub fn get(&self, file: &Path) -> Option<&ViewState> {
+        self.views.get(&Self::key(file))
+    }
+
+    pub fn set(&mut self, file: &Path, state: ViewState) {
+        self.views.insert(Self::key(file), state);
+    }
+
+    fn key(file: &Path) -> String {
+        file.canonicalize()
+            .unwrap_or_else(|_| file.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
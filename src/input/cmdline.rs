@@ -0,0 +1,167 @@
+/// The text typed after `:`, `/` or `?`, with a cursor that can move
+/// within it.
+#[derive(Debug, Clone, Default)]
+pub struct CommandLine {
+    pub text: String,
+    /// Byte offset of the cursor in `text`.
+    pub cursor: usize,
+    /// Position in the history while browsing it with Up/Down.
+    pub history_index: Option<usize>,
+    /// The text typed before browsing started; only entries starting with it
+    /// are shown.
+    pub history_prefix: String,
+}
+
+fn is_keyword(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+impl CommandLine {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// The text before the cursor, which completion works on.
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// Replaces the text and puts the cursor at the end.
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+        self.history_index = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set("");
+    }
+
+    /// Takes the text, leaving the line empty.
+    pub fn take(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        self.clear();
+        text
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        self.text.insert(self.cursor, ch);
+        self.cursor += ch.len_utf8();
+        self.history_index = None;
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+        self.history_index = None;
+    }
+
+    /// Replaces the text from byte `start` up to the cursor with `text`.
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        self.text.replace_range(start..self.cursor, text);
+        self.cursor = start + text.len();
+        self.history_index = None;
+    }
+
+    /// Deletes the character before the cursor. Returns false when there is
+    /// none.
+    pub fn backspace(&mut self) -> bool {
+        let Some(ch) = self.before_cursor().chars().next_back() else {
+            return false;
+        };
+        self.cursor -= ch.len_utf8();
+        self.text.remove(self.cursor);
+        self.history_index = None;
+        true
+    }
+
+    /// Deletes the character under the cursor.
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+            self.history_index = None;
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(ch) = self.before_cursor().chars().next_back() {
+            self.cursor -= ch.len_utf8();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(ch) = self.text[self.cursor..].chars().next() {
+            self.cursor += ch.len_utf8();
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// `Ctrl-W`: deletes the word before the cursor, along with any spaces
+    /// after it.
+    pub fn delete_word_before(&mut self) {
+        let before = self.before_cursor();
+        let trimmed = before.trim_end();
+        let start = match trimmed.chars().next_back() {
+            Some(last) => {
+                let same_class =
+                    |ch: char| !ch.is_whitespace() && is_keyword(ch) == is_keyword(last);
+                trimmed
+                    .char_indices()
+                    .rev()
+                    .take_while(|&(_, ch)| same_class(ch))
+                    .last()
+                    .map_or(trimmed.len(), |(idx, _)| idx)
+            }
+            None => 0,
+        };
+        self.replace_before_cursor(start, "");
+    }
+
+    /// `Ctrl-U`: deletes everything before the cursor.
+    pub fn delete_to_start(&mut self) {
+        self.replace_before_cursor(0, "");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editing_at_cursor() {
+        let mut line = CommandLine::default();
+        line.set("s/foo/bar/g");
+        line.move_left();
+        line.move_left();
+        line.insert('X');
+        assert_eq!(line.as_str(), "s/foo/barX/g");
+        line.delete_word_before();
+        assert_eq!(line.as_str(), "s/foo//g");
+        line.delete_word_before();
+        assert_eq!(line.as_str(), "s/foo/g");
+        line.move_end();
+        line.delete_word_before();
+        assert_eq!(line.as_str(), "s/foo/");
+        line.move_home();
+        line.move_right();
+        line.delete_to_start();
+        assert_eq!(line.as_str(), "/foo/");
+        assert_eq!(line.cursor, 0);
+        assert!(!line.backspace());
+
+        line.set("e  ");
+        line.delete_word_before();
+        assert_eq!(line.as_str(), "");
+    }
+}
@@ -29,10 +29,8 @@ pub enum Command {
 
     // File operations
     Save,
-    SaveAs(String),
     Open(String),
     Quit,
-    ForceQuit,
 
     // Command execution
     ExecuteCommand,
@@ -56,24 +54,6 @@ pub enum Command {
     SetThemeByIndex(usize),
     ListThemes,
 
-    // Set commands for editor configuration
-    SetLineNumbers(bool),
-    SetRelativeLineNumbers(bool),
-    SetTabSize(usize),
-    SetInsertTabs(bool),
-    SetAutoSave(bool),
-    SetWrapLines(bool),
-    SetScrollOffset(usize),
-    SetSyntaxHighlighting(bool),
-    SetCursorBlink(bool),
-    SetShowStatusLine(bool),
-    SetShowCommandLine(bool),
-    SetFastcmd(bool),
-
-    // Get commands to show current settings
-    ShowSettings,
-    ShowSetting(String),
-
     // No operation
     Noop,
 }
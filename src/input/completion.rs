@@ -0,0 +1,259 @@
+use crate::config::options;
+use crate::input::ex;
+use std::path::{Path, PathBuf};
+
+/// What the command line can be completed from.
+pub struct CompletionSource<'a> {
+    /// Relative paths are completed from here.
+    pub current_dir: &'a Path,
+    pub themes: &'a [String],
+    pub buffers: &'a [String],
+    /// Names of the commands from `[commands]`.
+    pub user_commands: &'a [String],
+}
+
+/// Candidates for the word at the end of a command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct Completion {
+    /// Byte offset of the word the candidates replace.
+    pub start: usize,
+    pub candidates: Vec<String>,
+}
+
+/// Byte offset just past the last `|` not escaped with a backslash.
+fn last_command_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '|' => start = idx + 1,
+            _ => {}
+        }
+    }
+    start
+}
+
+/// Byte offset where the last argument starts: after the last whitespace
+/// not escaped with a backslash.
+fn last_word_start(text: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ch if ch.is_whitespace() => start = idx + ch.len_utf8(),
+            _ => {}
+        }
+    }
+    start
+}
+
+/// Length of the leading colons, whitespace and range, as in `:'<,'>`.
+fn range_len(text: &str) -> usize {
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(idx, ch)) = chars.peek() {
+        match ch {
+            '\'' => {
+                chars.next();
+                chars.next();
+            }
+            ':' | '%' | '.' | '$' | ',' | ';' | '+' | '-' => {
+                chars.next();
+            }
+            ch if ch.is_ascii_digit() || ch.is_whitespace() => {
+                chars.next();
+            }
+            _ => return idx,
+        }
+    }
+    text.len()
+}
+
+fn escape(candidate: &str) -> String {
+    candidate.replace('\\', "\\\\").replace(' ', "\\ ")
+}
+
+/// Completes the command name or argument being typed at the end of `line`.
+pub fn complete(line: &str, source: &CompletionSource) -> Completion {
+    let segment_start = last_command_start(line);
+    let segment = &line[segment_start..];
+    let name_start = range_len(segment);
+    // User command names may contain digits after the leading uppercase.
+    let user = segment[name_start..].starts_with(|ch: char| ch.is_ascii_uppercase());
+    let name_len = segment[name_start..]
+        .find(|ch: char| !(ch.is_ascii_alphabetic() || user && ch.is_ascii_digit()))
+        .unwrap_or(segment.len() - name_start);
+    let name = &segment[name_start..name_start + name_len];
+
+    let rest = &segment[name_start + name_len..];
+    if rest.is_empty() {
+        let mut candidates = ex::complete_names(name);
+        let mut user_commands: Vec<String> = source
+            .user_commands
+            .iter()
+            .filter(|command| command.starts_with(name))
+            .cloned()
+            .collect();
+        user_commands.sort();
+        candidates.extend(user_commands);
+        return Completion {
+            start: segment_start + name_start,
+            candidates,
+        };
+    }
+    let Some(command) = ex::lookup(name) else {
+        return Completion::default();
+    };
+    let args = rest.strip_prefix('!').unwrap_or(rest);
+    if !args.starts_with(char::is_whitespace) {
+        return Completion::default();
+    }
+
+    // Buffer names are taken whole, spaces and all.
+    if command.name == "buffer" {
+        let word = args.trim_start();
+        return Completion {
+            start: line.len() - word.len(),
+            candidates: source
+                .buffers
+                .iter()
+                .filter(|buffer| buffer.contains(word))
+                .cloned()
+                .collect(),
+        };
+    }
+
+    let word_start = last_word_start(args);
+    let word = args[word_start..].replace("\\ ", " ").replace("\\\\", "\\");
+    let candidates = match command.name {
+        "edit" | "write" | "wq" | "xit" | "saveas" | "read" | "split" | "vsplit" | "tabnew" => {
+            complete_paths(&word, source.current_dir, false)
+        }
+        "cd" => complete_paths(&word, source.current_dir, true),
+        "theme" => source
+            .themes
+            .iter()
+            .filter(|theme| theme.starts_with(&word))
+            .map(|theme| escape(theme))
+            .collect(),
+        "set" | "setlocal" => complete_option(&word),
+        _ => Vec::new(),
+    };
+    Completion {
+        start: line.len() - args.len() + word_start,
+        candidates,
+    }
+}
+
+/// Option names, or the known values of the option before `=`.
+fn complete_option(word: &str) -> Vec<String> {
+    let Some((name, value)) = word.split_once('=') else {
+        return options::complete_names(word);
+    };
+    let Some(option) = options::lookup(name.trim_end_matches(['+', '-', '^'])) else {
+        return Vec::new();
+    };
+    option
+        .values
+        .iter()
+        .filter(|candidate| candidate.starts_with(value))
+        .map(|candidate| format!("{name}={candidate}"))
+        .collect()
+}
+
+/// Files and directories starting with `word`, relative to `current_dir`
+/// unless `word` is absolute. Directories end in `/`; dot files are only
+/// offered once a `.` is typed.
+fn complete_paths(word: &str, current_dir: &Path, dirs_only: bool) -> Vec<String> {
+    let (dir_part, prefix) = match word.rfind('/') {
+        Some(idx) => word.split_at(idx + 1),
+        None => ("", word),
+    };
+    let dir = if Path::new(dir_part).is_absolute() {
+        PathBuf::from(dir_part)
+    } else {
+        current_dir.join(dir_part)
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            if dirs_only && !is_dir {
+                return None;
+            }
+            let suffix = if is_dir { "/" } else { "" };
+            Some(escape(&format!("{dir_part}{name}{suffix}")))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete_in(line: &str, dir: &Path) -> Completion {
+        complete(
+            line,
+            &CompletionSource {
+                current_dir: dir,
+                themes: &["dark".to_string(), "default".to_string()],
+                buffers: &["src/main.rs".to_string(), "README.md".to_string()],
+                user_commands: &["Fmt".to_string(), "Fix".to_string()],
+            },
+        )
+    }
+
+    #[test]
+    fn test_commands_options_and_themes() {
+        let dir = Path::new(".");
+        let completion = complete_in("'<,'>so", dir);
+        assert_eq!(completion.start, 5);
+        assert_eq!(completion.candidates, ["sor", "sort"]);
+
+        let completion = complete_in("set nu | colorsch", dir);
+        assert!(completion.candidates.is_empty());
+
+        let completion = complete_in("theme d", dir);
+        assert_eq!(completion.start, 6);
+        assert_eq!(completion.candidates, ["dark", "default"]);
+
+        assert_eq!(complete_in("b main", dir).candidates, ["src/main.rs"]);
+        assert_eq!(complete_in("%F", dir).candidates, ["Fix", "Fmt"]);
+        assert!(complete_in("set nu", dir)
+            .candidates
+            .contains(&"number".to_string()));
+        assert_eq!(
+            complete_in("set fdm=ma", dir).candidates,
+            ["fdm=manual", "fdm=marker"]
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/my file.rs"), "").unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "").unwrap();
+
+        assert_eq!(complete_in("e ", dir.path()).candidates, ["src/"]);
+        assert_eq!(complete_in("cd s", dir.path()).candidates, ["src/"]);
+        let completion = complete_in("e src/m", dir.path());
+        assert_eq!(completion.start, 2);
+        assert_eq!(completion.candidates, ["src/main.rs", "src/my\\ file.rs"]);
+        assert_eq!(complete_in("e .h", dir.path()).candidates, [".hidden"]);
+    }
+}
@@ -1,13 +1,36 @@
-use crate::app::App;
-use crate::editor::{Cursor, Mode};
+use crate::app::{App, FileWrite};
+use crate::config::options::{self, OptionScope, OptionValue, SetAction};
+use crate::editor::global::GlobalCommand;
+use crate::editor::indent;
+use crate::editor::lines::{self, SortOptions};
+use crate::editor::{CaseOp, Clipboard, Cursor, Mode};
+use crate::file::HistoryKind;
+use crate::input::ex::{self, ExCommand};
+use crate::input::range::LineRange;
+use crate::input::user_commands;
+use crate::ui::components::OpenTarget;
 use crate::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use crossterm::terminal;
 use std::time::Duration;
 
+/// How deeply user commands may run other user commands.
+const MAX_USER_COMMAND_DEPTH: usize = 10;
+
+/// `Ctrl-W` as kept in the pending keys, starting a window command.
+const CTRL_W: char = '\u{17}';
+
 pub struct EventHandler {
     pub should_quit: bool,
     paste_mode_remaining: usize,
+    pending_keys: String,
+    pending_count: Option<usize>,
+    /// Whether a `:g` command is running, which cannot be nested.
+    in_global: bool,
+    /// Whether `Ctrl-R` on the command line is waiting for a register name.
+    pending_register: bool,
+    /// How many user commands are running inside each other.
+    user_command_depth: usize,
 }
 
 impl EventHandler {
@@ -15,16 +38,19 @@ impl EventHandler {
         Self {
             should_quit: false,
             paste_mode_remaining: 0,
+            pending_keys: String::new(),
+            pending_count: None,
+            in_global: false,
+            pending_register: false,
+            user_command_depth: 0,
         }
     }
 
     pub fn handle_events(&mut self, app: &mut App) -> Result<()> {
         if event::poll(Duration::from_millis(16))? {
             match event::read()? {
-                Event::Key(key_event) => {
-                    if key_event.kind == KeyEventKind::Press {
-                        self.handle_key_event(app, key_event)?;
-                    }
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                    self.handle_key_event(app, key_event)?;
                 }
                 Event::Resize(_, _) => {}
                 _ => {}
@@ -106,6 +132,32 @@ impl EventHandler {
             }
         }
 
+        if app.fuzzy_finder.visible {
+            return self.handle_fuzzy_finder(app, key_event);
+        }
+
+        if app.substitute_confirm.is_some() {
+            match key_event.code {
+                KeyCode::Char(c) => app.answer_substitute_confirm(c),
+                KeyCode::Esc => app.answer_substitute_confirm('q'),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if app.write_confirm.is_some() {
+            match key_event.code {
+                KeyCode::Char(c) => app.answer_write_confirm(c),
+                KeyCode::Esc => app.answer_write_confirm('c'),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if app.mode != Mode::Insert {
+            app.refresh_folds();
+        }
+
         match app.mode {
             Mode::Normal => self.handle_normal_mode(app, key_event),
             Mode::Insert => self.handle_insert_mode(app, key_event),
@@ -118,7 +170,7 @@ impl EventHandler {
         let (width, _) = terminal::size()?;
         let mut viewport_width = width as usize;
 
-        if app.config.editor.line_numbers || app.config.editor.relative_line_numbers {
+        if app.editor_config().line_numbers || app.editor_config().relative_line_numbers {
             viewport_width = viewport_width.saturating_sub(5);
         }
 
@@ -134,6 +186,10 @@ impl EventHandler {
             return self.handle_file_explorer_mode(app, key_event);
         }
 
+        if app.quickfix.focused {
+            return self.handle_quickfix_mode(app, key_event);
+        }
+
         if app.current_buffer().is_terminal() {
             match key_event.code {
                 KeyCode::Esc => {
@@ -166,8 +222,17 @@ impl EventHandler {
             }
         }
 
+        if app.command_window.is_some() && key_event.code == KeyCode::Enter {
+            return self.run_command_window_line(app);
+        }
+
         let viewport_width = self.get_viewport_width(app)?;
 
+        if self.push_count_digit(key_event) {
+            return Ok(());
+        }
+        let count = self.pending_count.take().unwrap_or(1);
+
         if key_event.modifiers.contains(KeyModifiers::CONTROL) {
             if key_event.modifiers.contains(KeyModifiers::SHIFT) {
                 match key_event.code {
@@ -205,6 +270,9 @@ impl EventHandler {
                     if app.selection.active {
                         app.cut_selection();
                         app.set_status_message("Cut selection".to_string());
+                    } else {
+                        app.increment_number(-(count as i64));
+                        app.update_horizontal_scroll(viewport_width);
                     }
                     return Ok(());
                 }
@@ -241,11 +309,24 @@ impl EventHandler {
                     app.undo();
                     return Ok(());
                 }
+                KeyCode::Char('p') => {
+                    app.open_fuzzy_finder();
+                    return Ok(());
+                }
+                KeyCode::Char('w') => {
+                    self.pending_keys.push(CTRL_W);
+                    return Ok(());
+                }
                 KeyCode::Char('y') => {
                     app.redo();
                     return Ok(());
                 }
-                KeyCode::Char('a') => {
+                KeyCode::Char('a') if !key_event.modifiers.contains(KeyModifiers::SHIFT) => {
+                    app.increment_number(count as i64);
+                    app.update_horizontal_scroll(viewport_width);
+                    return Ok(());
+                }
+                KeyCode::Char('a' | 'A') => {
                     let line_count = app.current_buffer().line_count();
                     let last_line_text = if line_count > 0 {
                         app.current_buffer().line(line_count.saturating_sub(1))
@@ -348,38 +429,50 @@ impl EventHandler {
             }
         }
 
+        if !self.pending_keys.is_empty() {
+            return self.handle_pending_keys(app, key_event, viewport_width, count);
+        }
+
         match key_event.code {
             KeyCode::Char(':') => {
                 app.mode = Mode::Command;
                 app.command_line.clear();
                 app.clear_error_message();
             }
+            KeyCode::Char(c @ ('/' | '?')) => {
+                app.start_search_prompt(c == '?');
+            }
+            KeyCode::Char(c @ ('n' | 'N')) => {
+                for _ in 0..count {
+                    app.search_repeat(c == 'N');
+                }
+            }
+            KeyCode::Char(c @ ('*' | '#')) => {
+                app.search_word_under_cursor(c == '#');
+                for _ in 1..count {
+                    app.search_repeat(false);
+                }
+            }
             KeyCode::Char('h') | KeyCode::Left => {
                 let buffer = app.current_buffer().clone();
                 app.cursor.move_left(&buffer);
                 app.update_horizontal_scroll(viewport_width);
             }
             KeyCode::Char('j') | KeyCode::Down => {
-                if app.search_state.is_active && !app.search_state.results.is_empty() {
-                    app.search_next();
-                    return Ok(());
-                }
                 let buffer = app.current_buffer().clone();
-                if app.config.editor.wrap_lines {
-                    app.cursor.move_down_visual(&buffer, viewport_width);
+                if app.editor_config().wrap_lines {
+                    let wrap = app.wrap_options(viewport_width);
+                    app.cursor.move_down_visual(&buffer, &wrap);
                 } else {
                     app.cursor.move_down(&buffer);
                 }
                 app.update_horizontal_scroll(viewport_width);
             }
             KeyCode::Char('k') | KeyCode::Up => {
-                if app.search_state.is_active && !app.search_state.results.is_empty() {
-                    app.search_previous();
-                    return Ok(());
-                }
                 let buffer = app.current_buffer().clone();
-                if app.config.editor.wrap_lines {
-                    app.cursor.move_up_visual(&buffer, viewport_width);
+                if app.editor_config().wrap_lines {
+                    let wrap = app.wrap_options(viewport_width);
+                    app.cursor.move_up_visual(&buffer, &wrap);
                 } else {
                     app.cursor.move_up(&buffer);
                 }
@@ -409,15 +502,19 @@ impl EventHandler {
                 app.cursor.move_line_end(&buffer);
                 app.update_horizontal_scroll(viewport_width);
             }
-            KeyCode::Char('g') => {
-                app.cursor.move_file_start();
-                app.update_horizontal_scroll(viewport_width);
+            KeyCode::Char(c @ ('g' | 'z' | '!' | 'm' | '\'')) => {
+                self.pending_keys.push(c);
+                self.pending_count = (count > 1).then_some(count);
             }
             KeyCode::Char('G') => {
                 let buffer = app.current_buffer().clone();
                 app.cursor.move_file_end(&buffer);
                 app.update_horizontal_scroll(viewport_width);
             }
+            KeyCode::Char('%') => {
+                app.jump_to_match();
+                app.update_horizontal_scroll(viewport_width);
+            }
 
             KeyCode::F(2) => {
                 app.file_explorer.toggle_visibility();
@@ -434,24 +531,56 @@ impl EventHandler {
                 app.update_horizontal_scroll(viewport_width);
                 app.mode = Mode::Insert;
             }
-            KeyCode::Char('o') => {
+            KeyCode::Char('A') => {
+                app.save_undo_state();
                 let buffer = app.current_buffer().clone();
                 app.cursor.move_line_end(&buffer);
                 app.update_horizontal_scroll(viewport_width);
-
-                let cursor_line = app.cursor.line;
-                let cursor_col = app.cursor.col;
-
+                app.mode = Mode::Insert;
+            }
+            KeyCode::Char('I') => {
                 app.save_undo_state();
-                let buffer = app.current_buffer_mut();
-                buffer.insert_char(cursor_line, cursor_col, '\n');
-
-                app.cursor.line += 1;
-                app.cursor.col = 0;
-                app.cursor.desired_col = 0;
+                let line = app
+                    .current_buffer()
+                    .line(app.cursor.line)
+                    .unwrap_or_default();
+                app.cursor.col = indent::leading_indent(&line).chars().count();
+                app.cursor.desired_col = app.cursor.col;
                 app.update_horizontal_scroll(viewport_width);
                 app.mode = Mode::Insert;
             }
+            KeyCode::Char('o') => {
+                app.save_undo_state();
+                app.open_line_below();
+                app.update_horizontal_scroll(viewport_width);
+                app.mode = Mode::Insert;
+            }
+            KeyCode::Char('O') => {
+                app.save_undo_state();
+                app.open_line_above();
+                app.update_horizontal_scroll(viewport_width);
+                app.mode = Mode::Insert;
+            }
+            KeyCode::Char(c @ ('>' | '<')) => {
+                self.pending_keys.push(c);
+            }
+            KeyCode::Char('J') => {
+                app.join_lines(app.cursor.line, count, true);
+                app.update_horizontal_scroll(viewport_width);
+            }
+            KeyCode::Char('~') => {
+                let line_len = app.current_buffer().line_len(app.cursor.line);
+                if app.cursor.col < line_len {
+                    let start = app.cursor;
+                    let mut end = start;
+                    end.col = (start.col + count).min(line_len);
+                    app.save_undo_state();
+                    app.change_case(start, end, CaseOp::Toggle);
+                    app.cursor.col = end.col.min(line_len);
+                    app.cursor.desired_col = app.cursor.col;
+                    app.update_horizontal_scroll(viewport_width);
+                }
+            }
             KeyCode::Char('v') => {
                 app.mode = Mode::Visual;
             }
@@ -463,19 +592,20 @@ impl EventHandler {
                 let buffer = app.current_buffer_mut();
                 buffer.delete_char(cursor_line, cursor_col);
             }
-            KeyCode::Char('d') => {
-                if key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                    let cursor_line = app.cursor.line;
-                    app.save_undo_state();
-                    let buffer = app.current_buffer_mut();
-                    if buffer.line_count() > 1 {
-                        buffer.delete_range(cursor_line, 0, cursor_line + 1, 0);
-                    }
+            KeyCode::Char('d') if key_event.modifiers.contains(KeyModifiers::SHIFT) => {
+                let cursor_line = app.cursor.line;
+                app.save_undo_state();
+                let buffer = app.current_buffer_mut();
+                if buffer.line_count() > 1 {
+                    buffer.delete_range(cursor_line, 0, cursor_line + 1, 0);
                 }
             }
 
             KeyCode::Char('q') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                app.quit();
+                app.quit_checked(false);
+            }
+            KeyCode::Char('q') => {
+                self.pending_keys.push('q');
             }
 
             _ => {}
@@ -485,6 +615,236 @@ impl EventHandler {
         Ok(())
     }
 
+    fn handle_pending_keys(
+        &mut self,
+        app: &mut App,
+        key_event: KeyEvent,
+        viewport_width: usize,
+        count: usize,
+    ) -> Result<()> {
+        let KeyCode::Char(c) = key_event.code else {
+            self.pending_keys.clear();
+            return Ok(());
+        };
+
+        let mut sequence = std::mem::take(&mut self.pending_keys);
+        sequence.push(c);
+
+        match sequence.as_str() {
+            ">>" | "<<" => {
+                let levels = if c == '>' { 1 } else { -1 };
+                let line = app.cursor.line;
+                app.save_undo_state();
+                app.shift_lines(line, line, levels);
+            }
+            "gg" => {
+                app.cursor.move_file_start();
+            }
+            "gc" | "gcg" | "gq" | "gqg" | "gqi" | "gqa" => {
+                self.pending_keys = sequence;
+                return Ok(());
+            }
+            "gJ" => {
+                app.join_lines(app.cursor.line, count, false);
+            }
+            "gcc" => {
+                let line = app.cursor.line;
+                app.save_undo_state();
+                app.toggle_comment(line, line);
+            }
+            operator if operator.starts_with("gc") => {
+                if let Some(target) = Self::motion_target(app, &operator[2..]) {
+                    let start = app.cursor.line.min(target.line);
+                    let end = app.cursor.line.max(target.line);
+                    app.save_undo_state();
+                    app.toggle_comment(start, end);
+                }
+            }
+            "gqq" => {
+                let line = app.cursor.line;
+                app.save_undo_state();
+                app.format_lines(line, line);
+            }
+            "gqip" | "gqap" => {
+                let (start, end) = app.paragraph_bounds(app.cursor.line);
+                app.save_undo_state();
+                app.format_lines(start, end);
+            }
+            operator if operator.starts_with("gq") => {
+                if let Some(target) = Self::motion_target(app, &operator[2..]) {
+                    let start = app.cursor.line.min(target.line);
+                    let end = app.cursor.line.max(target.line);
+                    app.save_undo_state();
+                    app.format_lines(start, end);
+                }
+            }
+            "gu" | "gU" | "g~" | "gug" | "gUg" | "g~g" => {
+                self.pending_keys = sequence;
+                return Ok(());
+            }
+            "guu" | "gUU" | "g~~" | "gugu" | "gUgU" | "g~g~" => {
+                if let Some(op) = CaseOp::from_key(c) {
+                    let line = app.cursor.line;
+                    let mut start = app.cursor;
+                    start.col = 0;
+                    let mut end = start;
+                    end.col = app.current_buffer().line_len(line);
+                    app.save_undo_state();
+                    app.change_case(start, end, op);
+                }
+            }
+            operator
+                if operator.starts_with("gu")
+                    || operator.starts_with("gU")
+                    || operator.starts_with("g~") =>
+            {
+                let op = operator[1..].chars().next().and_then(CaseOp::from_key);
+                if let (Some(op), Some((start, end))) =
+                    (op, Self::motion_range(app, &operator[2..]))
+                {
+                    app.save_undo_state();
+                    app.change_case(start, end, op);
+                }
+            }
+            "!g" | "!i" | "!a" => {
+                self.pending_keys = sequence;
+                return Ok(());
+            }
+            "!!" => {
+                let last_line = app.current_buffer().text_line_count().saturating_sub(1);
+                let line = app.cursor.line;
+                app.start_filter_prompt(line, (line + count - 1).min(last_line));
+            }
+            "!ip" | "!ap" => {
+                let (start, end) = app.paragraph_bounds(app.cursor.line);
+                app.start_filter_prompt(start, end);
+            }
+            operator if operator.starts_with('!') => {
+                if let Some(target) = Self::motion_target(app, &operator[1..]) {
+                    let start = app.cursor.line.min(target.line);
+                    let end = app.cursor.line.max(target.line);
+                    app.start_filter_prompt(start, end);
+                }
+            }
+            mark if mark.starts_with('m') => {
+                let line = app.cursor.line;
+                app.current_buffer_mut().set_mark(c, line);
+            }
+            mark if mark.starts_with('\'') => app.jump_to_mark(c),
+            "gt" | "gT" => app.switch_tab(c == 't'),
+            window if window.starts_with(CTRL_W) => match c {
+                'w' | 'p' | 'h' | 'j' | 'k' | 'l' => app.switch_window(),
+                's' | 'v' => {
+                    app.split_window(c == 'v');
+                }
+                'c' | 'q' if app.split.is_some() || !app.tabs.is_empty() => {
+                    app.close_window();
+                }
+                'c' => app.set_error_message("Cannot close last window".to_string()),
+                'q' => app.quit_checked(false),
+                _ => {}
+            },
+            "q:" => app.open_command_window(HistoryKind::Command),
+            "q/" | "q?" => app.open_command_window(HistoryKind::Search),
+            "zo" | "zc" | "za" | "zR" | "zM" => {
+                app.fold_command(c);
+            }
+            "zf" | "zfg" => {
+                self.pending_keys = sequence;
+                return Ok(());
+            }
+            operator if operator.starts_with("zf") => {
+                if let Some(target) = Self::motion_target(app, &operator[2..]) {
+                    let start = app.cursor.line.min(target.line);
+                    let end = app.cursor.line.max(target.line);
+                    app.create_fold(start, end);
+                }
+            }
+            _ => {}
+        }
+
+        app.update_horizontal_scroll(viewport_width);
+        Ok(())
+    }
+
+    /// Accumulates a `[count]` prefix. `0` only counts once a count has started,
+    /// since on its own it moves to the start of the line.
+    fn push_count_digit(&mut self, key_event: KeyEvent) -> bool {
+        let KeyCode::Char(c @ '0'..='9') = key_event.code else {
+            return false;
+        };
+        if !self.pending_keys.is_empty()
+            || key_event.modifiers.contains(KeyModifiers::CONTROL)
+            || (c == '0' && self.pending_count.is_none())
+        {
+            return false;
+        }
+
+        let digit = c as usize - '0' as usize;
+        self.pending_count = Some(
+            self.pending_count
+                .unwrap_or(0)
+                .saturating_mul(10)
+                .saturating_add(digit),
+        );
+        true
+    }
+
+    /// Returns the text an operator covers over `motion`, as a start and an
+    /// exclusive end. Line motions cover whole lines; `$` and `%` include the
+    /// character they land on.
+    fn motion_range(app: &App, motion: &str) -> Option<(Cursor, Cursor)> {
+        let target = Self::motion_target(app, motion)?;
+        let (mut start, mut end) = if (target.line, target.col) < (app.cursor.line, app.cursor.col)
+        {
+            (target, app.cursor)
+        } else {
+            (app.cursor, target)
+        };
+
+        let buffer = app.current_buffer();
+        match motion {
+            "j" | "k" | "gg" | "G" => {
+                start.col = 0;
+                end.col = buffer.line_len(end.line);
+            }
+            "$" | "%" => {
+                end.col = (end.col + 1).min(buffer.line_len(end.line));
+            }
+            _ => {}
+        }
+        Some((start, end))
+    }
+
+    /// Returns where `motion` would move the cursor, for use by operators.
+    fn motion_target(app: &App, motion: &str) -> Option<Cursor> {
+        let buffer = app.current_buffer();
+        let mut target = app.cursor;
+
+        match motion {
+            "j" => target.move_down(buffer),
+            "k" => target.move_up(buffer),
+            "h" => target.move_left(buffer),
+            "l" => target.move_right(buffer),
+            "w" => target.move_word_forward(buffer),
+            "b" => target.move_word_backward(buffer),
+            "0" => target.move_line_start(),
+            "$" => target.move_line_end(buffer),
+            "gg" => target.move_file_start(),
+            "G" => target.move_file_end(buffer),
+            "%" => {
+                let (line, col) = app
+                    .bracket_matcher()
+                    .jump_target(app.cursor.line, app.cursor.col)?;
+                target.line = line;
+                target.col = col;
+            }
+            _ => return None,
+        }
+
+        Some(target)
+    }
+
     fn handle_file_explorer_mode(&mut self, app: &mut App, key_event: KeyEvent) -> Result<()> {
         match key_event.code {
             KeyCode::Char('j') | KeyCode::Down => {
@@ -530,6 +890,61 @@ impl EventHandler {
         Ok(())
     }
 
+    fn handle_fuzzy_finder(&mut self, app: &mut App, key_event: KeyEvent) -> Result<()> {
+        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+            match key_event.code {
+                KeyCode::Char('n' | 'j') => app.fuzzy_finder.move_down(),
+                KeyCode::Char('p' | 'k') => app.fuzzy_finder.move_up(),
+                KeyCode::Char('u') => app.fuzzy_finder.clear_query(),
+                KeyCode::Char('s' | 'x') => app.open_finder_selection(OpenTarget::Split),
+                KeyCode::Char('v') => app.open_finder_selection(OpenTarget::VerticalSplit),
+                KeyCode::Char('t') => app.open_finder_selection(OpenTarget::Tab),
+                KeyCode::Char('c') => app.fuzzy_finder.close(),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match key_event.code {
+            KeyCode::Esc => app.fuzzy_finder.close(),
+            KeyCode::Enter => app.open_finder_selection(OpenTarget::Current),
+            KeyCode::Down => app.fuzzy_finder.move_down(),
+            KeyCode::Up => app.fuzzy_finder.move_up(),
+            KeyCode::Backspace => app.fuzzy_finder.pop_char(),
+            KeyCode::Char(c) if !c.is_control() => app.fuzzy_finder.push_char(c),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_quickfix_mode(&mut self, app: &mut App, key_event: KeyEvent) -> Result<()> {
+        match key_event.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                app.quickfix.move_down();
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                app.quickfix.move_up();
+            }
+            KeyCode::Enter => {
+                app.quickfix.focused = false;
+                app.quickfix_jump(app.quickfix.selected_index);
+            }
+            KeyCode::Esc | KeyCode::Tab => {
+                app.quickfix.focused = false;
+            }
+            KeyCode::Char('q') => {
+                app.quickfix.close();
+            }
+            KeyCode::Char(':') => {
+                app.mode = Mode::Command;
+                app.command_line.clear();
+                app.clear_error_message();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn handle_insert_mode(&mut self, app: &mut App, key_event: KeyEvent) -> Result<()> {
         let viewport_width = self.get_viewport_width(app).unwrap_or(80);
 
@@ -734,6 +1149,7 @@ impl EventHandler {
 
         match key_event.code {
             KeyCode::Esc => {
+                app.expand_abbreviation();
                 app.mode = Mode::Normal;
                 app.clear_selection();
 
@@ -760,8 +1176,9 @@ impl EventHandler {
             }
             KeyCode::Up => {
                 let buffer = app.current_buffer().clone();
-                if app.config.editor.wrap_lines {
-                    app.cursor.move_up_visual(&buffer, viewport_width);
+                if app.editor_config().wrap_lines {
+                    let wrap = app.wrap_options(viewport_width);
+                    app.cursor.move_up_visual(&buffer, &wrap);
                 } else {
                     app.cursor.move_up_insert_mode(&buffer);
                 }
@@ -770,8 +1187,9 @@ impl EventHandler {
             }
             KeyCode::Down => {
                 let buffer = app.current_buffer().clone();
-                if app.config.editor.wrap_lines {
-                    app.cursor.move_down_visual(&buffer, viewport_width);
+                if app.editor_config().wrap_lines {
+                    let wrap = app.wrap_options(viewport_width);
+                    app.cursor.move_down_visual(&buffer, &wrap);
                 } else {
                     app.cursor.move_down_insert_mode(&buffer);
                 }
@@ -854,14 +1272,10 @@ impl EventHandler {
 
                 app.delete_selection();
                 app.save_undo_state();
-
-                let cursor_line = app.cursor.line;
-                let cursor_col = app.cursor.col;
-                let buffer = app.current_buffer_mut();
-                buffer.insert_char(cursor_line, cursor_col, c);
-
-                app.cursor.col += 1;
-                app.cursor.desired_col = app.cursor.col;
+                if !(c.is_alphanumeric() || c == '_') {
+                    app.expand_abbreviation();
+                }
+                app.insert_typed_char(c);
                 app.reset_cursor_blink();
 
                 app.update_horizontal_scroll(viewport_width);
@@ -869,15 +1283,9 @@ impl EventHandler {
             KeyCode::Enter => {
                 app.delete_selection();
 
-                let cursor_line = app.cursor.line;
-                let cursor_col = app.cursor.col;
                 app.save_undo_state();
-                let buffer = app.current_buffer_mut();
-                buffer.insert_char(cursor_line, cursor_col, '\n');
-
-                let buffer = app.current_buffer().clone();
-                app.cursor.move_down(&buffer);
-                app.cursor.move_line_start();
+                app.expand_abbreviation();
+                app.insert_newline();
                 app.reset_cursor_blink();
                 app.update_horizontal_scroll(viewport_width);
             }
@@ -889,7 +1297,10 @@ impl EventHandler {
                 }
 
                 app.save_undo_state();
-                if app.cursor.col > 0 {
+                if app.delete_empty_pair() {
+                    app.reset_cursor_blink();
+                    app.update_horizontal_scroll(viewport_width);
+                } else if app.cursor.col > 0 {
                     let buffer = app.current_buffer().clone();
                     app.cursor.move_left(&buffer);
 
@@ -935,15 +1346,14 @@ impl EventHandler {
                 app.delete_selection();
 
                 app.save_undo_state();
-                let tab_size = app.config.editor.tab_size;
-                let spaces = " ".repeat(tab_size);
+                let indent = crate::editor::indent::indent_unit(&app.editor_config());
                 let cursor_line = app.cursor.line;
                 let cursor_col = app.cursor.col;
 
                 let buffer = app.current_buffer_mut();
-                buffer.insert_str(cursor_line, cursor_col, &spaces);
+                buffer.insert_str(cursor_line, cursor_col, &indent);
 
-                app.cursor.col += tab_size;
+                app.cursor.col += indent.chars().count();
                 app.cursor.desired_col = app.cursor.col;
                 app.reset_cursor_blink();
                 app.update_horizontal_scroll(viewport_width);
@@ -962,7 +1372,71 @@ impl EventHandler {
             app.start_selection();
         }
 
+        if self.push_count_digit(key_event) {
+            return Ok(());
+        }
+        let count = self.pending_count.take().unwrap_or(1);
+
+        if !self.pending_keys.is_empty() {
+            let sequence = std::mem::take(&mut self.pending_keys);
+            match (sequence.as_str(), key_event.code) {
+                ("g", KeyCode::Char(c @ ('a' | 'x')))
+                    if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    let delta = if c == 'a' {
+                        count as i64
+                    } else {
+                        -(count as i64)
+                    };
+                    Self::increment_selection(app, delta, true);
+                }
+                ("g", KeyCode::Char(c @ ('u' | 'U' | '~'))) => {
+                    Self::change_selection_case(app, c);
+                }
+                ("g", KeyCode::Char('g')) => {
+                    app.cursor.move_file_start();
+                    app.update_selection();
+                    app.update_horizontal_scroll(viewport_width);
+                }
+                ("z", KeyCode::Char('f')) => {
+                    if let Some((start, end)) = app.selection.get_range() {
+                        app.create_fold(start.line, end.line);
+                    }
+                    app.clear_selection();
+                    app.mode = Mode::Normal;
+                }
+                ("g", KeyCode::Char('c')) => {
+                    if let Some((start, end)) = app.selection.get_range() {
+                        app.save_undo_state();
+                        app.toggle_comment(start.line, end.line);
+                    }
+                    app.clear_selection();
+                    app.mode = Mode::Normal;
+                }
+                ("g", KeyCode::Char('q')) => {
+                    if let Some((start, end)) = app.selection.get_range() {
+                        app.save_undo_state();
+                        app.format_lines(start.line, end.line);
+                    }
+                    app.clear_selection();
+                    app.mode = Mode::Normal;
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
         match key_event.code {
+            KeyCode::Char(c @ ('a' | 'x'))
+                if key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                let delta = if c == 'a' {
+                    count as i64
+                } else {
+                    -(count as i64)
+                };
+                Self::increment_selection(app, delta, false);
+            }
             KeyCode::Esc => {
                 app.clear_selection();
                 app.mode = Mode::Normal;
@@ -975,8 +1449,9 @@ impl EventHandler {
             }
             KeyCode::Char('j') | KeyCode::Down => {
                 let buffer = app.current_buffer().clone();
-                if app.config.editor.wrap_lines {
-                    app.cursor.move_down_visual(&buffer, viewport_width);
+                if app.editor_config().wrap_lines {
+                    let wrap = app.wrap_options(viewport_width);
+                    app.cursor.move_down_visual(&buffer, &wrap);
                 } else {
                     app.cursor.move_down(&buffer);
                 }
@@ -985,8 +1460,9 @@ impl EventHandler {
             }
             KeyCode::Char('k') | KeyCode::Up => {
                 let buffer = app.current_buffer().clone();
-                if app.config.editor.wrap_lines {
-                    app.cursor.move_up_visual(&buffer, viewport_width);
+                if app.editor_config().wrap_lines {
+                    let wrap = app.wrap_options(viewport_width);
+                    app.cursor.move_up_visual(&buffer, &wrap);
                 } else {
                     app.cursor.move_up(&buffer);
                 }
@@ -1022,10 +1498,9 @@ impl EventHandler {
                 app.update_selection();
                 app.update_horizontal_scroll(viewport_width);
             }
-            KeyCode::Char('g') => {
-                app.cursor.move_file_start();
-                app.update_selection();
-                app.update_horizontal_scroll(viewport_width);
+            KeyCode::Char(c @ ('g' | 'z')) => {
+                self.pending_keys.push(c);
+                self.pending_count = (count > 1).then_some(count);
             }
             KeyCode::Char('G') => {
                 let buffer = app.current_buffer().clone();
@@ -1033,6 +1508,11 @@ impl EventHandler {
                 app.update_selection();
                 app.update_horizontal_scroll(viewport_width);
             }
+            KeyCode::Char('%') => {
+                app.jump_to_match();
+                app.update_selection();
+                app.update_horizontal_scroll(viewport_width);
+            }
             KeyCode::Char('y') => {
                 app.copy_selection();
                 app.set_status_message("Yanked selection".to_string());
@@ -1049,21 +1529,90 @@ impl EventHandler {
                 app.set_status_message("Cut selection".to_string());
                 app.mode = Mode::Normal;
             }
+            KeyCode::Char(c @ ('u' | 'U' | '~')) => {
+                Self::change_selection_case(app, c);
+            }
+            KeyCode::Char('J') => {
+                if let Some((start, end)) = app.selection.get_range() {
+                    app.join_lines(start.line, end.line + 1 - start.line, true);
+                }
+                app.clear_selection();
+                app.mode = Mode::Normal;
+            }
+            KeyCode::Char(c @ (':' | '!')) => {
+                if let Some((start, end)) = app.selection.get_range() {
+                    app.visual_marks = Some((start.line, end.line));
+                }
+                app.clear_selection();
+                app.mode = Mode::Command;
+                app.command_line
+                    .set(if c == '!' { "'<,'>!" } else { "'<,'>" });
+                app.clear_error_message();
+            }
+            KeyCode::Char(c @ ('>' | '<')) => {
+                if let Some((start, end)) = app.selection.get_range() {
+                    let levels = if c == '>' { 1 } else { -1 };
+                    app.save_undo_state();
+                    app.shift_lines(start.line, end.line, levels);
+                }
+                app.clear_selection();
+                app.mode = Mode::Normal;
+            }
             _ => {}
         }
         Ok(())
     }
 
+    fn increment_selection(app: &mut App, delta: i64, progressive: bool) {
+        if let Some((start, end)) = app.selection.get_range() {
+            app.save_undo_state();
+            app.increment_lines(start, end, delta, progressive);
+        }
+        app.clear_selection();
+        app.mode = Mode::Normal;
+    }
+
+    /// Applies `u`, `U` or `~` to the selection, including the character
+    /// under its end.
+    fn change_selection_case(app: &mut App, key: char) {
+        if let (Some(op), Some((start, mut end))) =
+            (CaseOp::from_key(key), app.selection.get_range())
+        {
+            end.col = (end.col + 1).min(app.current_buffer().line_len(end.line));
+            app.save_undo_state();
+            app.change_case(start, end, op);
+        }
+        app.clear_selection();
+        app.mode = Mode::Normal;
+    }
+
     fn handle_command_mode(&mut self, app: &mut App, key_event: KeyEvent) -> Result<()> {
+        if self.edit_command_line(app, key_event) {
+            app.wildmenu = None;
+            if app.search_prompt.is_some() {
+                app.update_search_prompt();
+            }
+            return Ok(());
+        }
+
+        if app.search_prompt.is_some() {
+            return self.handle_search_prompt(app, key_event);
+        }
+
+        if !matches!(key_event.code, KeyCode::Tab | KeyCode::BackTab) {
+            app.wildmenu = None;
+        }
+
         match key_event.code {
             KeyCode::Esc => {
                 app.mode = Mode::Normal;
                 app.command_line.clear();
                 app.clear_error_message();
             }
+            KeyCode::Tab => app.complete_command_line(true),
+            KeyCode::BackTab => app.complete_command_line(false),
             KeyCode::Enter => {
-                let command = app.command_line.clone();
-                app.command_line.clear();
+                let command = app.command_line.take();
                 app.mode = Mode::Normal;
 
                 if app.search_state.is_active && command.is_empty() {
@@ -1073,11 +1622,12 @@ impl EventHandler {
                         app.search_next();
                     }
                 } else {
+                    app.add_history(HistoryKind::Command, &command);
                     self.execute_command(app, &command)?;
                 }
             }
             KeyCode::Char(c) => {
-                if c.is_control() {
+                if c.is_control() || key_event.modifiers.contains(KeyModifiers::CONTROL) {
                     log::debug!(
                         "Rejecting control character in command mode: {:?} (code: {})",
                         c,
@@ -1086,21 +1636,16 @@ impl EventHandler {
                     return Ok(());
                 }
 
-                if c as u8 == 22 {
-                    log::debug!("Ctrl+V (ASCII 22) detected in command mode, ignoring");
-                    return Ok(());
-                }
-
                 log::debug!(
                     "Adding character to command line: '{}' (code: {})",
                     c,
                     c as u32
                 );
-                app.command_line.push(c);
+                app.command_line.insert(c);
                 app.clear_error_message();
             }
             KeyCode::Backspace => {
-                app.command_line.pop();
+                app.command_line.backspace();
                 app.clear_error_message();
             }
             _ => {}
@@ -1108,59 +1653,298 @@ impl EventHandler {
         Ok(())
     }
 
-    fn execute_command(&mut self, app: &mut App, command: &str) -> Result<()> {
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
-            return Ok(());
+    /// Cursor movement, deletion, history and `Ctrl-R {reg}`, shared by the
+    /// `:` and search prompts. Returns whether the key was handled.
+    fn edit_command_line(&mut self, app: &mut App, key_event: KeyEvent) -> bool {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        if self.pending_register {
+            self.pending_register = false;
+            let register = match key_event.code {
+                KeyCode::Char('w') if ctrl => '\u{17}',
+                KeyCode::Char(c) => c,
+                _ => return true,
+            };
+            if !app.insert_register(register) {
+                app.set_error_message(format!("Invalid register: {register}"));
+            }
+            return true;
         }
 
-        app.clear_error_message();
+        let line = &mut app.command_line;
+        match key_event.code {
+            KeyCode::Char('r') if ctrl => self.pending_register = true,
+            KeyCode::Char('w') if ctrl => line.delete_word_before(),
+            KeyCode::Char('u') if ctrl => line.delete_to_start(),
+            KeyCode::Char('b') if ctrl => line.move_home(),
+            KeyCode::Char('e') if ctrl => line.move_end(),
+            KeyCode::Left => line.move_left(),
+            KeyCode::Right => line.move_right(),
+            KeyCode::Home => line.move_home(),
+            KeyCode::End => line.move_end(),
+            KeyCode::Delete => line.delete(),
+            KeyCode::Up => app.browse_history(true),
+            KeyCode::Down => app.browse_history(false),
+            _ => return false,
+        }
+        true
+    }
 
-        match parts[0] {
-            "q" | "quit" => {
-                app.quit();
+    fn handle_search_prompt(&mut self, app: &mut App, key_event: KeyEvent) -> Result<()> {
+        match key_event.code {
+            KeyCode::Esc => {
+                app.cancel_search_prompt();
+                app.clear_error_message();
+            }
+            KeyCode::Enter => {
+                app.finish_search_prompt();
             }
-            "w" | "write" => {
-                if let Err(e) = app.current_buffer_mut().save() {
-                    app.set_error_message(format!("Error saving: {e}"));
+            KeyCode::Char(c)
+                if !c.is_control() && !key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                app.command_line.insert(c);
+                app.update_search_prompt();
+            }
+            KeyCode::Backspace => {
+                if app.command_line.is_empty() {
+                    app.cancel_search_prompt();
                 } else {
-                    app.set_status_message("File saved".to_string());
+                    app.command_line.backspace();
+                    app.update_search_prompt();
                 }
             }
-            "wq" => {
-                if let Err(e) = app.current_buffer_mut().save() {
-                    app.set_error_message(format!("Error saving: {e}"));
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Enter in the command-line window: closes it and runs the line under
+    /// the cursor as a command or search.
+    fn run_command_window_line(&mut self, app: &mut App) -> Result<()> {
+        let Some((kind, line)) = app.close_command_window() else {
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        match kind {
+            HistoryKind::Command => {
+                app.add_history(kind, &line);
+                self.execute_command(app, &line)?;
+            }
+            HistoryKind::Search => {
+                app.start_search_prompt(false);
+                app.command_line.set(&line);
+                app.finish_search_prompt();
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_command(&mut self, app: &mut App, command: &str) -> Result<()> {
+        let commands = match ex::parse(command) {
+            Ok(commands) => commands,
+            Err(e) => {
+                app.set_error_message(e.to_string());
+                return Ok(());
+            }
+        };
+        for command in &commands {
+            app.clear_error_message();
+            self.execute_ex_command(app, command)?;
+            if app.error_message.is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs one command of a parsed command line.
+    fn execute_ex_command(&mut self, app: &mut App, command: &ExCommand) -> Result<()> {
+        let range = match command.line_range(&app.range_context()) {
+            Ok(range) => range,
+            Err(e) => {
+                app.set_error_message(e.to_string());
+                return Ok(());
+            }
+        };
+        let args = command.text();
+        let words = command.words();
+
+        match command.name() {
+            "" => {
+                app.cursor.line = range.end;
+                app.cursor.col = 0;
+                app.cursor.desired_col = 0;
+            }
+            "substitute" => {
+                app.substitute(range.start, range.end, args);
+            }
+            "global" | "vglobal" => {
+                let invert = command.name() == "vglobal" || command.bang;
+                return self.execute_global(app, range, invert, args);
+            }
+            "sort" => match SortOptions::parse(args, command.bang) {
+                Ok(options) => {
+                    let count = app.transform_lines(range.start, range.end, |lines| {
+                        lines::sort_lines(lines, &options)
+                    });
+                    app.set_status_message(format!("{count} line(s) sorted"));
+                }
+                Err(e) => app.set_error_message(e.to_string()),
+            },
+            "uniq" => {
+                let before = range.end + 1 - range.start;
+                let count = app.transform_lines(range.start, range.end, lines::uniq_lines);
+                app.set_status_message(format!("{} duplicate line(s) removed", before - count));
+            }
+            "reverse" => {
+                let count = app.transform_lines(range.start, range.end, lines::reverse_lines);
+                app.set_status_message(format!("{count} line(s) reversed"));
+            }
+            "align" => {
+                if args.is_empty() {
+                    app.set_error_message("Usage: :[range]align <delimiter>".to_string());
+                } else {
+                    let count = app.transform_lines(range.start, range.end, |lines| {
+                        lines::align_lines(lines, args)
+                    });
+                    app.set_status_message(format!("{count} line(s) aligned on '{args}'"));
+                }
+            }
+            "delete" => {
+                app.delete_lines(range.start, range.end);
+            }
+            "yank" => {
+                let buffer = app.current_buffer();
+                let mut text = (range.start..=range.end)
+                    .map(|line_idx| buffer.line(line_idx).unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                text.push('\n');
+                Clipboard::set_text(text);
+                app.set_status_message(format!("{} line(s) yanked", range.end + 1 - range.start));
+            }
+            "normal" => {
+                if args.is_empty() {
+                    app.set_error_message("Usage: :[range]norm[al] {commands}".to_string());
                 } else {
+                    self.execute_normal(app, range, args)?;
+                }
+            }
+            "join" => {
+                let count = (range.end + 1 - range.start).max(2);
+                app.join_lines(range.start, count, !command.bang);
+            }
+            "quit" => {
+                if app.command_window.is_some() {
+                    app.close_command_window();
+                } else if !app.close_window() {
+                    app.quit_checked(command.bang);
+                }
+            }
+            "qall" => app.quit_checked(command.bang),
+            "write" if command.shell().is_some() => {
+                let input = app.range_text(range.start, range.end);
+                app.run_shell_command(command.shell().unwrap_or_default(), Some(&input));
+            }
+            "write" | "wq" | "xit" | "saveas" => {
+                let write = FileWrite {
+                    file: words.first().cloned(),
+                    lines: command.range.is_some().then_some((range.start, range.end)),
+                    append: command.appends(),
+                    rename: command.name() == "saveas",
+                };
+                if command.name() == "saveas" && write.file.is_none() {
+                    app.set_error_message("Argument required".to_string());
+                    return Ok(());
+                }
+                let quit = matches!(command.name(), "wq" | "xit");
+                // `:x` only writes when there is something to write.
+                let unchanged = command.name() == "xit"
+                    && write.file.is_none()
+                    && !app.current_buffer().is_modified;
+                let written = unchanged || app.write_file(&write, command.bang, quit);
+                if written && quit && !app.close_window() {
+                    app.quit_checked(command.bang);
+                }
+            }
+            "wall" => {
+                app.write_all();
+            }
+            "wqall" => {
+                if app.write_all() {
                     app.quit();
                 }
             }
-            "e" | "edit" => {
-                if parts.len() > 1 {
-                    let filename = parts[1];
-                    match app.open_or_create_file(filename) {
-                        Ok(()) => {
-                            let current_buffer = app.current_buffer();
-                            if current_buffer.is_modified {
-                                app.set_status_message(format!("Created new file: {filename}"));
-                            } else {
-                                app.set_status_message(format!("Opened: {filename}"));
-                            }
-                        }
-                        Err(e) => {
-                            app.set_error_message(format!("Error opening/creating file: {e}"));
-                        }
+            "edit" => match words.first() {
+                Some(filename) => edit_file(app, filename),
+                None => app.set_error_message("Usage: :e <filename>".to_string()),
+            },
+            "split" | "vsplit" => {
+                if app.split_window(command.name() == "vsplit") {
+                    if let Some(filename) = words.first() {
+                        edit_file(app, filename);
+                    }
+                }
+            }
+            "tabnew" => {
+                if app.new_tab() {
+                    if let Some(filename) = words.first() {
+                        edit_file(app, filename);
                     }
+                }
+            }
+            "close" => {
+                if app.split.is_none() && app.tabs.is_empty() {
+                    app.set_error_message("Cannot close last window".to_string());
+                } else {
+                    app.close_window();
+                }
+            }
+            "tabclose" => {
+                if app.tabs.is_empty() {
+                    app.set_error_message("Cannot close last tab page".to_string());
+                } else {
+                    app.close_tab();
+                }
+            }
+            "tabnext" | "tabprevious" => app.switch_tab(command.name() == "tabnext"),
+            "buffer" => {
+                let target = command.text();
+                if target.is_empty() {
+                    app.set_status_message(format!(
+                        "Buffer {}: {}",
+                        app.current_buffer + 1,
+                        app.buffer_names()[app.current_buffer]
+                    ));
                 } else {
-                    app.set_error_message("Usage: :e <filename>".to_string());
+                    match find_buffer(&app.buffer_names(), target) {
+                        Ok(buffer_idx) => app.switch_to_buffer(buffer_idx),
+                        Err(e) => app.set_error_message(e.to_string()),
+                    }
                 }
             }
+            "ls" => {
+                let listing: Vec<String> = app
+                    .buffer_names()
+                    .iter()
+                    .zip(&app.buffers)
+                    .enumerate()
+                    .map(|(idx, (name, buffer))| {
+                        let current = if idx == app.current_buffer { "%" } else { " " };
+                        let modified = if buffer.is_modified { "+" } else { " " };
+                        format!("{}{current}{modified} {name}", idx + 1)
+                    })
+                    .collect();
+                app.set_status_message(listing.join(" | "));
+            }
             "pwd" => {
                 let current_dir = app.get_current_directory();
                 app.set_status_message(format!("Current directory: {}", current_dir.display()));
             }
             "cd" => {
-                if parts.len() > 1 {
-                    let target_path = std::path::PathBuf::from(parts[1]);
+                if !words.is_empty() {
+                    let target_path = std::path::PathBuf::from(&words[0]);
                     match app.file_explorer.navigate_to(&target_path) {
                         Ok(()) => {
                             app.set_status_message(format!(
@@ -1194,8 +1978,8 @@ impl EventHandler {
                 }
             },
             "theme" => {
-                if parts.len() > 1 {
-                    if let Ok(index) = parts[1].parse::<usize>() {
+                if !words.is_empty() {
+                    if let Ok(index) = words[0].parse::<usize>() {
                         match app.config.set_theme_by_index(index) {
                             Ok(()) => {
                                 app.set_status_message(format!(
@@ -1207,7 +1991,7 @@ impl EventHandler {
                                 app.set_error_message(format!("Error setting theme: {e}"));
                             }
                         }
-                    } else if parts[1] == "list" {
+                    } else if words[0] == "list" {
                         let themes = app.config.list_available_themes();
                         let mut message = "Available themes:\n".to_string();
                         for (index, name, author, description) in themes {
@@ -1216,9 +2000,9 @@ impl EventHandler {
                             ));
                         }
                         app.set_status_message(message);
-                    } else if parts[1] == "default" {
-                        if parts.len() > 2 {
-                            if let Ok(index) = parts[2].parse::<usize>() {
+                    } else if words[0] == "default" {
+                        if words.len() > 1 {
+                            if let Ok(index) = words[1].parse::<usize>() {
                                 match app.config.set_default_theme_by_index(index) {
                                     Ok(()) => {
                                         app.set_status_message(format!(
@@ -1246,7 +2030,7 @@ impl EventHandler {
                             app.set_status_message(message);
                         }
                     } else {
-                        match app.config.set_theme_by_name(parts[1]) {
+                        match app.config.set_theme_by_name(&words[0]) {
                             Ok(()) => {
                                 app.set_status_message(format!(
                                     "Theme changed to: {}",
@@ -1254,7 +2038,7 @@ impl EventHandler {
                                 ));
                             }
                             Err(_) => {
-                                let theme_path = std::path::PathBuf::from(parts[1]);
+                                let theme_path = std::path::PathBuf::from(&words[0]);
 
                                 if let Some(extension) = theme_path.extension() {
                                     if extension != "nctheme" {
@@ -1292,10 +2076,9 @@ impl EventHandler {
                     ));
                 }
             }
-            "goto" | "g" => {
-                if parts.len() > 1 {
-                    let arg = parts[1];
-
+            "goto" => {
+                let parts: Vec<&str> = args.split_whitespace().collect();
+                if let Some(&arg) = parts.first() {
                     if arg.ends_with('j') || arg.ends_with('k') {
                         let direction = arg.chars().last().unwrap();
                         let number_part = &arg[..arg.len() - 1];
@@ -1325,7 +2108,7 @@ impl EventHandler {
                     } else if let Ok(line_num) = arg.parse::<usize>() {
                         let buffer = app.current_buffer();
                         if line_num > 0 && line_num <= buffer.line_count() {
-                            if app.config.editor.relative_line_numbers {
+                            if app.editor_config().relative_line_numbers {
                                 if line_num <= app.cursor.line {
                                     app.cursor.line = app.cursor.line.saturating_sub(line_num);
                                 } else {
@@ -1346,11 +2129,11 @@ impl EventHandler {
                             ));
                         }
                     } else {
-                        let query = parts[1..].join(" ");
+                        let query = parts.join(" ");
                         app.search(&query);
                         if !app.search_state.results.is_empty() {
                             app.set_status_message(format!(
-                                "Found {} matches - Use Up/Down arrows to navigate",
+                                "Found {} matches - Use n/N to navigate",
                                 app.search_state.results.len()
                             ));
                         }
@@ -1362,332 +2145,424 @@ impl EventHandler {
                     );
                 }
             }
-            "help" | "h" => {
+            "help" => {
                 self.show_help(app);
             }
-            "set" => {
-                if parts.len() == 1 {
-                    let settings = app.config.get_all_settings_display();
+            "set" | "setlocal" => {
+                let local = command.name() == "setlocal";
+                if words.is_empty() {
+                    let settings = if local {
+                        let mut settings = vec!["Buffer-local options:".to_string()];
+                        settings.extend(app.current_buffer().options.display());
+                        settings.push("Window-local options:".to_string());
+                        settings.extend(app.window_options.display());
+                        settings
+                    } else {
+                        app.config.get_all_settings_display()
+                    };
                     app.set_status_message(settings.join("\n"));
-                } else if parts.len() == 2 && parts[1] == "all" {
-                    let mut settings = app.config.get_all_settings_display();
-                    settings.insert(0, "All Settings with Descriptions:".to_string());
-                    settings.push("".to_string());
-                    settings.push("Setting Shortcuts (Vim-style):".to_string());
-                    settings.push("  nu/number          - Line numbers".to_string());
-                    settings.push("  rnu/relativenumber - Relative line numbers".to_string());
-                    settings.push("  ts/tabsize         - Tab size".to_string());
-                    settings.push("  et/expandtab       - Insert tabs as spaces".to_string());
-                    settings.push("  so/scrolloffset    - Scroll offset".to_string());
+                } else if words == ["all"] {
+                    let mut settings = vec!["All Settings with Descriptions:".to_string()];
+                    for option in options::OPTIONS {
+                        let mut names = vec![option.name];
+                        names.extend(option.aliases);
+                        settings.push(format!(
+                            "  {} = {} - {}",
+                            names.join("/"),
+                            app.option_value(option),
+                            option.help
+                        ));
+                    }
                     app.set_status_message(settings.join("\n"));
                 } else {
-                    for setting in parts.iter().skip(1) {
-                        if let Some(setting_name) = setting.strip_suffix('?') {
-                            let display = app.config.get_setting_display(setting_name);
-                            app.set_status_message(display);
-                            continue;
-                        }
-
-                        if let Some(eq_pos) = setting.find('=') {
-                            let (key, value) = setting.split_at(eq_pos);
-                            let value = &value[1..];
-                            if let Err(e) = self.handle_set_assignment(app, key, value) {
-                                app.set_error_message(e.to_string());
-                                return Ok(());
-                            }
-                            continue;
-                        }
-
-                        if let Err(e) = self.handle_set_flag(app, setting) {
+                    for setting in words {
+                        if let Err(e) = self.handle_set(app, setting, local) {
                             app.set_error_message(e.to_string());
                             return Ok(());
                         }
                     }
                 }
             }
+            "grep" => match split_grep_args(args) {
+                Some((pattern, path)) => app.grep(&pattern, path),
+                None => app.set_error_message("Usage: :grep <regex> [path]".to_string()),
+            },
+            "cnext" => {
+                app.quickfix_step(1);
+            }
+            "cprevious" => {
+                app.quickfix_step(-1);
+            }
+            "cc" => match words.join(" ").parse::<usize>() {
+                Ok(number) if number > 0 => app.quickfix_jump(number - 1),
+                _ => app.quickfix_jump(app.quickfix.selected_index),
+            },
+            "files" => {
+                app.open_fuzzy_finder();
+            }
+            "copen" => {
+                app.quickfix.open();
+            }
+            "cclose" => {
+                app.quickfix.close();
+            }
+            "find" => {
+                if args.is_empty() {
+                    app.set_error_message("Usage: :find <pattern>[/offset]".to_string());
+                } else {
+                    app.search(args);
+                }
+            }
+            "findnext" => {
+                app.search_next();
+            }
+            "findprev" => {
+                app.search_previous();
+            }
             "clear" => {
                 app.search_state.clear();
                 app.set_status_message("Search cleared".to_string());
             }
             "cmd" => {
                 app.open_terminal();
-                if parts.len() > 1 {
-                    let command = parts[1..].join(" ");
-                    if let Err(e) = app.current_buffer_mut().execute_terminal_command(&command) {
+                if !args.is_empty() {
+                    if let Err(e) = app.current_buffer_mut().execute_terminal_command(args) {
                         app.set_error_message(format!("Command error: {e}"));
                     }
                 } else {
                     app.set_status_message("Terminal opened".to_string());
                 }
             }
-            _ => {
-                app.set_error_message(format!(
-                    "Unknown command: {}. Type :help for available commands",
-                    parts[0]
-                ));
-            }
-        }
-        Ok(())
-    }
-
-    fn show_help(&self, app: &mut App) {
-        app.show_help();
-    }
-
-    fn handle_set_assignment(&self, app: &mut App, key: &str, value: &str) -> Result<()> {
-        match key.to_lowercase().as_str() {
-            "ts" | "tabsize" | "tab_size" => {
-                if let Ok(size) = value.parse::<usize>() {
-                    app.config.set_tab_size(size)?;
-                    app.set_status_message(format!("Tab size set to {size}"));
+            "!" => {
+                let shell_command = if command.bang {
+                    match &app.last_shell_command {
+                        Some(last) => format!("{last}{args}"),
+                        None => {
+                            app.set_error_message("No previous command".to_string());
+                            return Ok(());
+                        }
+                    }
                 } else {
-                    return Err(anyhow::anyhow!("Invalid tab size: {}", value));
+                    args.to_string()
+                };
+                if shell_command.is_empty() {
+                    app.set_error_message("Usage: :[range]!command".to_string());
+                    return Ok(());
                 }
-            }
-            "so" | "scrolloffset" | "scroll_offset" => {
-                if let Ok(offset) = value.parse::<usize>() {
-                    app.config.set_scroll_offset(offset)?;
-                    app.set_status_message(format!("Scroll offset set to {offset}"));
+                app.last_shell_command = Some(shell_command.clone());
+                if command.range.is_some() {
+                    app.filter_lines(range.start, range.end, &shell_command);
                 } else {
-                    return Err(anyhow::anyhow!("Invalid scroll offset: {}", value));
+                    app.run_shell_command(&shell_command, None);
                 }
             }
-            "nu" | "number" | "line_numbers" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_line_numbers(true)?;
-                    app.set_status_message("Line numbers enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_line_numbers(false)?;
-                    app.set_status_message("Line numbers disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for line numbers: {} (use true/false)",
-                        value
-                    ))
-                }
+            "read" => match command.shell() {
+                Some("") => app.set_error_message("Usage: :r !command".to_string()),
+                Some(shell_command) => app.read_shell_output(range.end, shell_command),
+                None => match words.first() {
+                    Some(path) => match std::fs::read_to_string(path) {
+                        Ok(text) => {
+                            if !text.is_empty() {
+                                app.insert_lines_below(range.end, &text);
+                            }
+                            app.set_status_message(format!(
+                                "\"{path}\" {} line(s) read",
+                                text.lines().count()
+                            ));
+                        }
+                        Err(e) => app.set_error_message(format!("Can't open file {path}: {e}")),
+                    },
+                    None => app.set_error_message("Usage: :r <file> or :r !command".to_string()),
+                },
             },
-            "rnu" | "relativenumber" | "relative_line_numbers" => {
-                match value.to_lowercase().as_str() {
-                    "true" | "1" => {
-                        app.config.set_relative_line_numbers(true)?;
-                        app.set_status_message("Relative line numbers enabled".to_string());
-                    }
-                    "false" | "0" => {
-                        app.config.set_relative_line_numbers(false)?;
-                        app.set_status_message("Relative line numbers disabled".to_string());
-                    }
-                    _ => {
-                        return Err(anyhow::anyhow!(
-                            "Invalid value for relative line numbers: {} (use true/false)",
-                            value
-                        ))
-                    }
+            "command" => {
+                let mut commands: Vec<String> = app
+                    .config
+                    .commands
+                    .iter()
+                    .map(|(name, replacement)| format!("{name} = {replacement}"))
+                    .collect();
+                commands.sort();
+                if commands.is_empty() {
+                    app.set_status_message("No user-defined commands".to_string());
+                } else {
+                    app.set_status_message(commands.join(" | "));
                 }
             }
-            "et" | "expandtab" | "insert_tabs" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_insert_tabs(true)?;
-                    app.set_status_message("Expand tabs enabled (tabs as spaces)".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_insert_tabs(false)?;
-                    app.set_status_message("Expand tabs disabled (use actual tabs)".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for expand tabs: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "autosave" | "auto_save" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_auto_save(true)?;
-                    app.set_status_message("Auto-save enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_auto_save(false)?;
-                    app.set_status_message("Auto-save disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for auto-save: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "wrap" | "wrap_lines" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_wrap_lines(true)?;
-                    app.set_status_message("Line wrapping enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_wrap_lines(false)?;
-                    app.set_status_message("Line wrapping disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for line wrapping: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "syntax" | "syntax_highlighting" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_syntax_highlighting(true)?;
-                    app.set_status_message("Syntax highlighting enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_syntax_highlighting(false)?;
-                    app.set_status_message("Syntax highlighting disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for syntax highlighting: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "cursorblink" | "cursor_blink" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_cursor_blink(true)?;
-                    app.set_status_message("Cursor blinking enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_cursor_blink(false)?;
-                    app.set_status_message("Cursor blinking disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for cursor blink: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "statusline" | "show_status_line" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_show_status_line(true)?;
-                    app.set_status_message("Status line enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_show_status_line(false)?;
-                    app.set_status_message("Status line disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for status line: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "commandline" | "show_command_line" => match value.to_lowercase().as_str() {
-                "true" | "1" => {
-                    app.config.set_show_command_line(true)?;
-                    app.set_status_message("Command line enabled".to_string());
-                }
-                "false" | "0" => {
-                    app.config.set_show_command_line(false)?;
-                    app.set_status_message("Command line disabled".to_string());
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid value for command line: {} (use true/false)",
-                        value
-                    ))
-                }
-            },
-            "fastcl" | "fastcommandline" | "fast_command_line" => {
-                match value.to_lowercase().as_str() {
-                    "true" | "1" => {
-                        app.config.set_fast_command_line(true)?;
-                        app.set_status_message("Fast command line enabled".to_string());
-                    }
-                    "false" | "0" => {
-                        app.config.set_fast_command_line(false)?;
-                        app.set_status_message("Fast command line disabled".to_string());
-                    }
-                    _ => {
-                        return Err(anyhow::anyhow!(
-                            "Invalid value for fast command line: {} (use true/false)",
-                            value
-                        ))
-                    }
-                }
+            "iabbrev" => Self::execute_iabbrev(app, command.text()),
+            _ if command.user.is_some() => {
+                return self.execute_user_command(app, command, range);
             }
-            _ => {
-                return Err(anyhow::anyhow!("Unknown setting: {}", key));
+            name => {
+                app.set_error_message(format!("Not an editor command: {name}"));
             }
         }
         Ok(())
     }
 
-    fn handle_set_flag(&self, app: &mut App, flag: &str) -> Result<()> {
-        match flag.to_lowercase().as_str() {
-            "nu" | "number" => {
-                app.config.set_line_numbers(true)?;
-                app.set_status_message("Line numbers enabled".to_string());
-            }
-            "nonu" | "nonumber" => {
-                app.config.set_line_numbers(false)?;
-                app.set_status_message("Line numbers disabled".to_string());
-            }
-            "rnu" | "relativenumber" => {
-                app.config.set_relative_line_numbers(true)?;
-                app.set_status_message("Relative line numbers enabled".to_string());
-            }
-            "nornu" | "norelativenumber" => {
-                app.config.set_relative_line_numbers(false)?;
-                app.set_status_message("Relative line numbers disabled".to_string());
-            }
-            "et" | "expandtab" => {
-                app.config.set_insert_tabs(true)?;
-                app.set_status_message("Expand tabs enabled (tabs as spaces)".to_string());
-            }
-            "noet" | "noexpandtab" => {
-                app.config.set_insert_tabs(false)?;
-                app.set_status_message("Expand tabs disabled (use actual tabs)".to_string());
-            }
-            "autosave" => {
-                app.config.set_auto_save(true)?;
-                app.set_status_message("Auto-save enabled".to_string());
-            }
-            "noautosave" => {
-                app.config.set_auto_save(false)?;
-                app.set_status_message("Auto-save disabled".to_string());
-            }
-            "wrap" => {
-                app.config.set_wrap_lines(true)?;
-                app.set_status_message("Line wrapping enabled".to_string());
+    /// Runs a command from `[commands]` by expanding its replacement and
+    /// running that as a command line.
+    fn execute_user_command(
+        &mut self,
+        app: &mut App,
+        command: &ExCommand,
+        range: LineRange,
+    ) -> Result<()> {
+        let name = command.name();
+        let Some(replacement) = app.config.commands.get(name).cloned() else {
+            app.set_error_message(format!("Not an editor command: {name}"));
+            return Ok(());
+        };
+        if self.user_command_depth >= MAX_USER_COMMAND_DEPTH {
+            app.set_error_message(format!("Recursive user command: {name}"));
+            return Ok(());
+        }
+
+        let expanded = match user_commands::expand(
+            name,
+            &replacement,
+            command.text(),
+            command.bang,
+            range,
+            command.range.is_some(),
+        ) {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                app.set_error_message(e.to_string());
+                return Ok(());
             }
-            "nowrap" => {
-                app.config.set_wrap_lines(false)?;
-                app.set_status_message("Line wrapping disabled".to_string());
+        };
+        self.user_command_depth += 1;
+        let result = self.execute_command(app, &expanded);
+        self.user_command_depth -= 1;
+        result
+    }
+
+    /// `:iabbrev lhs rhs` adds an abbreviation, `:iabbrev lhs` shows one and
+    /// `:iabbrev` lists them all.
+    fn execute_iabbrev(app: &mut App, args: &str) {
+        match args.split_once(char::is_whitespace) {
+            Some((lhs, rhs)) => {
+                if !lhs.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+                    app.set_error_message(format!("Invalid abbreviation: {lhs}"));
+                    return;
+                }
+                app.config
+                    .abbreviations
+                    .insert(lhs.to_string(), rhs.trim_start().to_string());
+            }
+            None if !args.is_empty() => match app.config.abbreviations.get(args) {
+                Some(expansion) => app.set_status_message(format!("{args} = {expansion}")),
+                None => app.set_error_message(format!("No such abbreviation: {args}")),
+            },
+            None => {
+                let mut abbreviations: Vec<String> = app
+                    .config
+                    .abbreviations
+                    .iter()
+                    .map(|(lhs, rhs)| format!("{lhs} = {rhs}"))
+                    .collect();
+                abbreviations.sort();
+                if abbreviations.is_empty() {
+                    app.set_status_message("No abbreviations".to_string());
+                } else {
+                    app.set_status_message(abbreviations.join(" | "));
+                }
             }
-            "syntax" => {
-                app.config.set_syntax_highlighting(true)?;
-                app.set_status_message("Syntax highlighting enabled".to_string());
+        }
+    }
+
+    /// Runs `:[range]g/pattern/command` (or `:v` with `invert`) as a single
+    /// undo step. The matching lines are marked first, so the command runs
+    /// on each of them even as earlier runs insert or delete lines.
+    fn execute_global(
+        &mut self,
+        app: &mut App,
+        range: LineRange,
+        invert: bool,
+        args: &str,
+    ) -> Result<()> {
+        if self.in_global {
+            app.set_error_message("Cannot use :global recursively".to_string());
+            return Ok(());
+        }
+        let editor = app.editor_config();
+        let global = match GlobalCommand::parse(
+            args,
+            invert,
+            app.search_state.pattern.as_ref(),
+            editor.ignorecase,
+            editor.smartcase,
+        ) {
+            Ok(global) => global,
+            Err(e) => {
+                app.set_error_message(e.to_string());
+                return Ok(());
             }
-            "nosyntax" => {
-                app.config.set_syntax_highlighting(false)?;
-                app.set_status_message("Syntax highlighting disabled".to_string());
+        };
+
+        app.set_last_search(global.pattern.clone(), false);
+
+        let lines = global.matching_lines(app.current_buffer(), range.start, range.end);
+        if lines.is_empty() {
+            app.set_error_message(format!("Pattern not found: {}", global.pattern.source));
+            return Ok(());
+        }
+
+        if matches!(global.command.as_str(), "" | "p" | "print") {
+            let buffer = app.current_buffer();
+            let listing: Vec<String> = lines
+                .iter()
+                .map(|&line| format!("{:>4} {}", line + 1, buffer.line(line).unwrap_or_default()))
+                .collect();
+            app.set_status_message(listing.join("\n"));
+            return Ok(());
+        }
+
+        let buffer_index = app.current_buffer;
+        let line_count = app.current_buffer().line_count();
+        let cursor = app.cursor;
+        let undo_stack = app.current_buffer_mut().begin_undo_group(&cursor);
+        app.current_buffer_mut().line_marks = lines.iter().map(|&line| Some(line)).collect();
+
+        self.in_global = true;
+        let mut result = Ok(());
+        for mark in 0..lines.len() {
+            if app.current_buffer != buffer_index {
+                break;
+            }
+            let Some(line) = app.current_buffer().line_marks[mark] else {
+                continue;
+            };
+            app.cursor.line = line;
+            app.cursor.col = 0;
+            app.cursor.desired_col = 0;
+            result = self.execute_command(app, &global.command);
+            if result.is_err() || app.error_message.is_some() {
+                break;
             }
-            "fastcl" | "fastcommandline" => {
-                app.config.set_fast_command_line(true)?;
-                app.set_status_message("Fast command line enabled".to_string());
+        }
+        self.in_global = false;
+
+        let buffer = &mut app.buffers[buffer_index];
+        buffer.line_marks.clear();
+        buffer.end_undo_group(undo_stack);
+        if app.current_buffer == buffer_index {
+            let buffer = app.current_buffer().clone();
+            app.cursor.clamp_to_buffer(&buffer);
+        }
+
+        if app.error_message.is_none() {
+            let new_count = app.buffers[buffer_index].line_count();
+            let mut message = format!("{} line(s) matched", lines.len());
+            if new_count < line_count {
+                message.push_str(&format!(", {} fewer line(s)", line_count - new_count));
+            } else if new_count > line_count {
+                message.push_str(&format!(", {} more line(s)", new_count - line_count));
             }
-            "nofastcl" | "nofastcommandline" => {
-                app.config.set_fast_command_line(false)?;
-                app.set_status_message("Fast command line disabled".to_string());
+            app.set_status_message(message);
+        }
+        result
+    }
+
+    /// Runs `keys` as normal-mode commands on each line of `range`, as
+    /// `:normal` does, ending any insert or visual mode they leave open.
+    /// Lines are marked first so edits that add or remove lines are fine.
+    fn execute_normal(&mut self, app: &mut App, range: LineRange, keys: &str) -> Result<()> {
+        let buffer = app.current_buffer();
+        let end = range.end.min(buffer.line_count().saturating_sub(1));
+        let marks: Vec<Option<usize>> = (range.start..=end).map(Some).collect();
+        // Inside `:g` the lines are already marked and the undo step is open.
+        let saved = (!self.in_global).then(|| {
+            let cursor = app.cursor;
+            let buffer = app.current_buffer_mut();
+            let undo_stack = buffer.begin_undo_group(&cursor);
+            buffer.line_marks = marks.clone();
+            undo_stack
+        });
+
+        for (mark, &line) in marks.iter().enumerate() {
+            let line = if saved.is_some() {
+                match app.current_buffer().line_marks.get(mark).copied().flatten() {
+                    Some(line) => line,
+                    None => continue,
+                }
+            } else {
+                line.unwrap_or(0)
+            };
+            app.cursor.line = line;
+            app.cursor.col = 0;
+            app.cursor.desired_col = 0;
+            for ch in keys.chars() {
+                self.handle_key_event(app, KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE))?;
+            }
+            if app.mode != Mode::Normal {
+                self.handle_key_event(app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))?;
+            }
+            self.pending_keys.clear();
+            self.pending_count = None;
+        }
+
+        if let Some(undo_stack) = saved {
+            let buffer = app.current_buffer_mut();
+            buffer.line_marks.clear();
+            buffer.end_undo_group(undo_stack);
+        }
+        Ok(())
+    }
+
+    fn show_help(&self, app: &mut App) {
+        app.show_help();
+    }
+
+    /// Applies one `:set` or `:setlocal` argument such as `ts=4`, `noet`,
+    /// `wrap!`, `tw?`, `fdm&` or `lcs+=eol:$`.
+    fn handle_set(&self, app: &mut App, setting: &str, local: bool) -> Result<()> {
+        let (option, action) = options::parse_set_argument(setting)?;
+        if local && option.scope == OptionScope::Global {
+            return Err(anyhow::anyhow!("{} is a global option", option.name));
+        }
+
+        let current = app.option_value(option);
+        let value = match action {
+            SetAction::Show => {
+                app.set_status_message(format!("{} = {}", option.name, current));
+                return Ok(());
             }
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "Unknown setting: {}. Use ':set' to see available options",
-                    flag
-                ));
+            SetAction::UseGlobal => None,
+            SetAction::Enable => Some(OptionValue::Bool(true)),
+            SetAction::Disable => Some(OptionValue::Bool(false)),
+            SetAction::Toggle => Some(OptionValue::Bool(current != OptionValue::Bool(true))),
+            SetAction::Reset => Some(option.default_value()),
+            SetAction::Assign(text) => Some(option.parse(&text)?),
+            SetAction::Add(text) => Some(option.add(&current, &text)?),
+            SetAction::Remove(text) => Some(option.remove(&current, &text)?),
+        };
+
+        match value {
+            None => {
+                app.clear_local_option(option.name);
+                app.set_status_message(format!("{} uses the global value", option.name));
+            }
+            Some(value) => {
+                let message = format!("{} = {}", option.name, value);
+                match app.local_options_mut(option.scope).filter(|_| local) {
+                    Some(local_options) => {
+                        local_options.set(option.name, value);
+                        app.set_status_message(format!("{message} (local)"));
+                    }
+                    None => {
+                        app.config.set_option(option, value)?;
+                        app.clear_local_option(option.name);
+                        app.set_status_message(message);
+                    }
+                }
             }
         }
+
+        if option.name == "foldmethod" {
+            app.reset_folds();
+        }
         Ok(())
     }
 }
@@ -1697,3 +2572,60 @@ impl Default for EventHandler {
         Self::new()
     }
 }
+
+/// `:e file`: opens `filename` in the current window, creating the buffer
+/// for a file that does not exist yet.
+fn edit_file(app: &mut App, filename: &str) {
+    match app.open_or_create_file(filename) {
+        Ok(()) => {
+            if app.current_buffer().is_modified {
+                app.set_status_message(format!("Created new file: {filename}"));
+            } else {
+                app.set_status_message(format!("Opened: {filename}"));
+            }
+        }
+        Err(e) => app.set_error_message(format!("Error opening/creating file: {e}")),
+    }
+}
+
+/// The buffer `:b` switches to: a buffer number, or a name matched as a
+/// substring of exactly one buffer name.
+fn find_buffer(names: &[String], target: &str) -> Result<usize> {
+    if let Ok(number) = target.parse::<usize>() {
+        return if (1..=names.len()).contains(&number) {
+            Ok(number - 1)
+        } else {
+            Err(anyhow::anyhow!("Buffer {number} does not exist"))
+        };
+    }
+    if let Some(idx) = names.iter().position(|name| name == target) {
+        return Ok(idx);
+    }
+    let mut matches = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.contains(target));
+    match (matches.next(), matches.next()) {
+        (Some((idx, _)), None) => Ok(idx),
+        (Some(_), Some(_)) => Err(anyhow::anyhow!("More than one match for {target}")),
+        (None, _) => Err(anyhow::anyhow!("No matching buffer for {target}")),
+    }
+}
+
+/// Splits `:grep` arguments into the pattern, which may be quoted to
+/// contain spaces, and an optional path.
+fn split_grep_args(args: &str) -> Option<(String, Option<&str>)> {
+    let args = args.trim();
+    let (pattern, rest) = match args.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = args[1..].find(quote)? + 1;
+            (args[1..end].to_string(), &args[end + 1..])
+        }
+        _ => {
+            let end = args.find(char::is_whitespace).unwrap_or(args.len());
+            (args[..end].to_string(), &args[end..])
+        }
+    };
+    let path = rest.trim();
+    Some((pattern, (!path.is_empty()).then_some(path)))
+}
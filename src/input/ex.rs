@@ -0,0 +1,1144 @@
+use crate::editor::substitute::is_delimiter;
+use crate::input::range::{Address, AddressBase, LineRange, Range, RangeContext};
+use crate::input::user_commands;
+use crate::Result;
+use std::fmt;
+
+/// An ex command line that could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// One-based character column where the problem starts.
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Whether a command takes a range, and which lines it covers without one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeKind {
+    None,
+    CurrentLine,
+    WholeFile,
+}
+
+/// How the text after a command name is read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    None,
+    /// Whitespace-separated words. `"..."` and `'...'` quote, and a backslash
+    /// escapes a space, quote, backslash or `|`.
+    Words,
+    /// At most one word, such as a file name.
+    File,
+    /// Text up to the next `|` (`\|` for a literal one), as typed.
+    Text,
+    /// This many delimited fields, as in `/pattern/replacement/`, then text
+    /// up to the next `|`. A `|` inside the fields belongs to them.
+    Delimited(usize),
+    /// The rest of the line, `|` included, for commands such as `:g` whose
+    /// argument is itself a command or a pattern.
+    Rest,
+    /// A file name, or `!` and a shell command taking the rest of the line,
+    /// as in `:r !date`.
+    FileOrShell,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CommandDef {
+    /// Canonical name, used to dispatch the command.
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub range: RangeKind,
+    /// Whether the command accepts a `!` after its name.
+    pub bang: bool,
+    /// Whether a count may follow the name, as in `:d 3`.
+    pub count: bool,
+    pub args: ArgKind,
+}
+
+pub static COMMANDS: &[CommandDef] = &[
+    CommandDef {
+        name: "sort",
+        aliases: &["sor"],
+        range: RangeKind::WholeFile,
+        bang: true,
+        count: false,
+        args: ArgKind::Text,
+    },
+    CommandDef {
+        name: "uniq",
+        aliases: &[],
+        range: RangeKind::WholeFile,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "reverse",
+        aliases: &[],
+        range: RangeKind::WholeFile,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "align",
+        aliases: &[],
+        range: RangeKind::WholeFile,
+        bang: false,
+        count: false,
+        args: ArgKind::Text,
+    },
+    CommandDef {
+        name: "join",
+        aliases: &["j"],
+        range: RangeKind::CurrentLine,
+        bang: true,
+        count: true,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "delete",
+        aliases: &["d", "de", "del"],
+        range: RangeKind::CurrentLine,
+        bang: false,
+        count: true,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "yank",
+        aliases: &["y", "ya"],
+        range: RangeKind::CurrentLine,
+        bang: false,
+        count: true,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "!",
+        aliases: &[],
+        range: RangeKind::CurrentLine,
+        bang: true,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "read",
+        aliases: &["r", "re"],
+        range: RangeKind::CurrentLine,
+        bang: false,
+        count: false,
+        args: ArgKind::FileOrShell,
+    },
+    CommandDef {
+        name: "normal",
+        aliases: &["norm"],
+        range: RangeKind::CurrentLine,
+        bang: true,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "substitute",
+        aliases: &["s"],
+        range: RangeKind::CurrentLine,
+        bang: false,
+        count: false,
+        args: ArgKind::Delimited(2),
+    },
+    CommandDef {
+        name: "global",
+        aliases: &[],
+        range: RangeKind::WholeFile,
+        bang: true,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "vglobal",
+        aliases: &["v"],
+        range: RangeKind::WholeFile,
+        bang: false,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "quit",
+        aliases: &["q"],
+        range: RangeKind::None,
+        bang: true,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "qall",
+        aliases: &["qa", "quita", "quitall"],
+        range: RangeKind::None,
+        bang: true,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "write",
+        aliases: &["w"],
+        range: RangeKind::WholeFile,
+        bang: true,
+        count: false,
+        args: ArgKind::FileOrShell,
+    },
+    CommandDef {
+        name: "wq",
+        aliases: &[],
+        range: RangeKind::WholeFile,
+        bang: true,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "xit",
+        aliases: &["x", "xi", "exi", "exit"],
+        range: RangeKind::WholeFile,
+        bang: true,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "wall",
+        aliases: &["wa"],
+        range: RangeKind::None,
+        bang: true,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "wqall",
+        aliases: &["wqa", "xall", "xa"],
+        range: RangeKind::None,
+        bang: true,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "saveas",
+        aliases: &["sav"],
+        range: RangeKind::None,
+        bang: true,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "edit",
+        aliases: &["e"],
+        range: RangeKind::None,
+        bang: true,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "split",
+        aliases: &["sp"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "vsplit",
+        aliases: &["vs", "vsp"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "close",
+        aliases: &["clo"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "tabnew",
+        aliases: &["tabe", "tabedit"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "tabclose",
+        aliases: &["tabc"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "tabnext",
+        aliases: &["tabn"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "tabprevious",
+        aliases: &["tabp", "tabN", "tabNext"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "buffer",
+        aliases: &["b", "bu", "buf"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Text,
+    },
+    CommandDef {
+        name: "ls",
+        aliases: &["buffers"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "pwd",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "cd",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::File,
+    },
+    CommandDef {
+        name: "explorer",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "refresh",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "theme",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Words,
+    },
+    CommandDef {
+        name: "goto",
+        aliases: &["g"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "help",
+        aliases: &["h"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Words,
+    },
+    CommandDef {
+        name: "set",
+        aliases: &["se"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Words,
+    },
+    CommandDef {
+        name: "setlocal",
+        aliases: &["setl"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Words,
+    },
+    CommandDef {
+        name: "grep",
+        aliases: &["gr"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "cnext",
+        aliases: &["cn"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "cprevious",
+        aliases: &["cp", "cprev", "cN", "cNext"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "cc",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Words,
+    },
+    CommandDef {
+        name: "copen",
+        aliases: &["cope"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "cclose",
+        aliases: &["ccl"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "files",
+        aliases: &["fzf"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "find",
+        aliases: &["f"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "findnext",
+        aliases: &["fn"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "findprev",
+        aliases: &["fp"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "clear",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "cmd",
+        aliases: &[],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Rest,
+    },
+    CommandDef {
+        name: "command",
+        aliases: &["com"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::None,
+    },
+    CommandDef {
+        name: "iabbrev",
+        aliases: &["iab", "iabbr"],
+        range: RangeKind::None,
+        bang: false,
+        count: false,
+        args: ArgKind::Text,
+    },
+];
+
+/// How every user-defined command from `[commands]` is parsed: with a range,
+/// a bang and the rest of the command as its arguments. What is allowed is
+/// checked when the replacement is expanded.
+pub static USER_COMMAND: CommandDef = CommandDef {
+    name: "",
+    aliases: &[],
+    range: RangeKind::CurrentLine,
+    bang: true,
+    count: false,
+    args: ArgKind::Text,
+};
+
+pub fn lookup(name: &str) -> Option<&'static CommandDef> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name || command.aliases.contains(&name))
+}
+
+/// Command names and aliases starting with `prefix`.
+pub fn complete_names(prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = COMMANDS
+        .iter()
+        .flat_map(|command| std::iter::once(command.name).chain(command.aliases.iter().copied()))
+        .filter(|name| name.starts_with(prefix) && name.starts_with(char::is_alphabetic))
+        .map(String::from)
+        .collect();
+    names.sort();
+    names
+}
+
+/// The arguments of a parsed command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExArgs {
+    None,
+    Words(Vec<String>),
+    Text(String),
+    /// The shell command after `!` of an [`ArgKind::FileOrShell`] command.
+    Shell(String),
+    /// The file name, if any, after `>>` of an [`ArgKind::FileOrShell`]
+    /// command, as in `:w >> log.txt`.
+    Append(Vec<String>),
+}
+
+/// One command of an ex command line such as `:10,20d` or `:e! "my file"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExCommand {
+    pub range: Option<Range>,
+    /// `None` for a bare range such as `:12`, which moves the cursor.
+    pub command: Option<&'static CommandDef>,
+    /// The name of a user-defined command, whose definition is
+    /// [`USER_COMMAND`].
+    pub user: Option<String>,
+    pub bang: bool,
+    pub count: Option<usize>,
+    pub args: ExArgs,
+}
+
+impl ExCommand {
+    pub fn name(&self) -> &str {
+        match &self.user {
+            Some(user) => user,
+            None => self.command.map_or("", |command| command.name),
+        }
+    }
+
+    /// The shell command of `:r !cmd` or `:w !cmd`.
+    pub fn shell(&self) -> Option<&str> {
+        match &self.args {
+            ExArgs::Shell(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    /// Whether the file of `:w >> file` is appended to.
+    pub fn appends(&self) -> bool {
+        matches!(self.args, ExArgs::Append(_))
+    }
+
+    /// The arguments of an [`ArgKind::Words`] or [`ArgKind::File`] command,
+    /// or the file name of an [`ArgKind::FileOrShell`] one.
+    pub fn words(&self) -> &[String] {
+        match &self.args {
+            ExArgs::Words(words) | ExArgs::Append(words) => words,
+            _ => &[],
+        }
+    }
+
+    /// The arguments of an [`ArgKind::Text`], [`ArgKind::Delimited`] or
+    /// [`ArgKind::Rest`] command.
+    pub fn text(&self) -> &str {
+        match &self.args {
+            ExArgs::Text(text) => text,
+            _ => "",
+        }
+    }
+
+    /// The lines the command applies to: its range, or the command's default
+    /// without one. A count counts lines from the end of the range.
+    pub fn line_range(&self, context: &RangeContext) -> Result<LineRange> {
+        let last = context.line_count.saturating_sub(1);
+        let range = match &self.range {
+            Some(range) => range.resolve(context)?,
+            None if self
+                .command
+                .is_some_and(|c| c.range == RangeKind::WholeFile) =>
+            {
+                LineRange {
+                    start: 0,
+                    end: last,
+                }
+            }
+            None => LineRange {
+                start: context.current_line,
+                end: context.current_line,
+            },
+        };
+        Ok(match self.count {
+            Some(count) => LineRange {
+                start: range.end,
+                end: (range.end + count - 1).min(last),
+            },
+            None => range,
+        })
+    }
+}
+
+/// Parses an ex command line into its `|`-separated commands.
+pub fn parse(input: &str) -> std::result::Result<Vec<ExCommand>, ParseError> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let mut commands = Vec::new();
+    loop {
+        commands.extend(parser.parse_command()?);
+        match parser.peek() {
+            None => return Ok(commands),
+            Some('|') => parser.pos += 1,
+            Some(_) => {
+                return Err(parser.error(
+                    parser.pos,
+                    format!("Trailing characters: {}", parser.rest()),
+                ))
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn at_separator(&self) -> bool {
+        matches!(self.peek(), None | Some('|'))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn rest(&self) -> String {
+        self.chars[self.pos..].iter().collect()
+    }
+
+    fn error(&self, pos: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            column: pos + 1,
+        }
+    }
+
+    /// Parses one command, leaving the parser at the `|` after it or at the
+    /// end. Returns `None` for an empty command.
+    fn parse_command(&mut self) -> std::result::Result<Option<ExCommand>, ParseError> {
+        while self
+            .peek()
+            .is_some_and(|ch| ch == ':' || ch.is_whitespace())
+        {
+            self.pos += 1;
+        }
+        if self.at_separator() {
+            return Ok(None);
+        }
+
+        let range_start = self.pos;
+        let range = self.parse_range()?;
+        self.skip_whitespace();
+
+        let name_start = self.pos;
+        let mut name = self.take_while(|ch| ch.is_ascii_alphabetic());
+        if name.starts_with(|ch: char| ch.is_ascii_uppercase()) {
+            name.push_str(&self.take_while(|ch| ch.is_ascii_alphanumeric()));
+        }
+        let name = if name.is_empty() && self.peek() == Some('!') {
+            self.pos += 1;
+            "!".to_string()
+        } else {
+            name
+        };
+        if name.is_empty() {
+            if self.at_separator() {
+                return Ok(Some(ExCommand {
+                    range,
+                    command: None,
+                    user: None,
+                    bang: false,
+                    count: None,
+                    args: ExArgs::None,
+                }));
+            }
+            return Err(self.error(name_start, format!("Unknown command: {}", self.rest())));
+        }
+
+        let mut user = None;
+        let mut command = match lookup(&name) {
+            Some(command) => command,
+            // User commands are looked up when they run.
+            None if user_commands::is_valid_name(&name) => {
+                user = Some(name.clone());
+                &USER_COMMAND
+            }
+            None => return Err(self.error(name_start, format!("Unknown command: {name}"))),
+        };
+        // `:g` is `:goto` unless a pattern follows, as in `:g/pat/d`.
+        if name == "g" && self.peek().is_some_and(|ch| ch == '!' || is_delimiter(ch)) {
+            command = lookup("global").unwrap_or(command);
+        }
+
+        let mut bang = false;
+        // `:r!cmd` is `:r !cmd`, since `:read` takes no bang.
+        let shell_follows = command.args == ArgKind::FileOrShell && !command.bang;
+        if self.peek() == Some('!') && !shell_follows {
+            if !command.bang {
+                return Err(self.error(self.pos, format!("No ! allowed for :{}", command.name)));
+            }
+            bang = true;
+            self.pos += 1;
+        }
+        if range.is_some() && command.range == RangeKind::None {
+            return Err(self.error(
+                range_start,
+                format!("No range allowed for :{}", command.name),
+            ));
+        }
+
+        let count = if command.count {
+            self.parse_count()?
+        } else {
+            None
+        };
+
+        let args = match command.args {
+            ArgKind::None => {
+                self.skip_whitespace();
+                if !self.at_separator() {
+                    let start = self.pos;
+                    let text = self.parse_text();
+                    return Err(self.error(start, format!("Trailing characters: {text}")));
+                }
+                ExArgs::None
+            }
+            ArgKind::Words | ArgKind::File => {
+                let words = self.parse_words()?;
+                if command.args == ArgKind::File && words.len() > 1 {
+                    return Err(self.error(words[1].0, "Too many file names"));
+                }
+                ExArgs::Words(words.into_iter().map(|(_, word)| word).collect())
+            }
+            ArgKind::Text => ExArgs::Text(self.parse_text()),
+            ArgKind::Delimited(fields) => ExArgs::Text(self.parse_delimited(fields)),
+            ArgKind::Rest => {
+                let rest = self.rest();
+                self.pos = self.chars.len();
+                ExArgs::Text(rest.trim_start().to_string())
+            }
+            ArgKind::FileOrShell => {
+                self.skip_whitespace();
+                if self.peek() == Some('!') {
+                    self.pos += 1;
+                    let rest = self.rest();
+                    self.pos = self.chars.len();
+                    ExArgs::Shell(rest.trim().to_string())
+                } else {
+                    let append = self.rest().starts_with(">>");
+                    if append {
+                        self.pos += 2;
+                    }
+                    let words = self.parse_words()?;
+                    if words.len() > 1 {
+                        return Err(self.error(words[1].0, "Too many file names"));
+                    }
+                    let words = words.into_iter().map(|(_, word)| word).collect();
+                    if append {
+                        ExArgs::Append(words)
+                    } else {
+                        ExArgs::Words(words)
+                    }
+                }
+            }
+        };
+
+        Ok(Some(ExCommand {
+            range,
+            command: Some(command),
+            user,
+            bang,
+            count,
+            args,
+        }))
+    }
+
+    fn parse_range(&mut self) -> std::result::Result<Option<Range>, ParseError> {
+        if self.peek() == Some('%') {
+            self.pos += 1;
+            return Ok(Some(Range::Whole));
+        }
+
+        let start = match self.parse_address()? {
+            Some(start) => start,
+            // `,5` starts at the current line.
+            None if matches!(self.peek(), Some(',' | ';')) => Address {
+                base: AddressBase::Current,
+                offset: 0,
+            },
+            None => return Ok(None),
+        };
+
+        let Some(separator) = self.peek().filter(|ch| matches!(ch, ',' | ';')) else {
+            return Ok(Some(Range::Lines {
+                start,
+                end: None,
+                relative: false,
+            }));
+        };
+        self.pos += 1;
+        let end_pos = self.pos;
+        let end = self
+            .parse_address()?
+            .ok_or_else(|| self.error(end_pos, format!("Missing address after '{separator}'")))?;
+        Ok(Some(Range::Lines {
+            start,
+            end: Some(end),
+            relative: separator == ';',
+        }))
+    }
+
+    fn parse_number(&mut self) -> std::result::Result<Option<usize>, ParseError> {
+        let start = self.pos;
+        let digits = self.take_while(|ch| ch.is_ascii_digit());
+        if digits.is_empty() {
+            return Ok(None);
+        }
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| self.error(start, "Number too large"))
+    }
+
+    fn parse_address(&mut self) -> std::result::Result<Option<Address>, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let base = match self.peek() {
+            Some(ch) if ch.is_ascii_digit() => {
+                AddressBase::Number(self.parse_number()?.unwrap_or_default())
+            }
+            Some('.') => {
+                self.pos += 1;
+                AddressBase::Current
+            }
+            Some('$') => {
+                self.pos += 1;
+                AddressBase::Last
+            }
+            Some('\'') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(mark) if mark.is_ascii_lowercase() || matches!(mark, '<' | '>') => {
+                        self.pos += 1;
+                        AddressBase::Mark(mark)
+                    }
+                    _ => return Err(self.error(start, "Invalid mark")),
+                }
+            }
+            Some('+' | '-') => AddressBase::Current,
+            _ => return Ok(None),
+        };
+
+        let mut offset = 0isize;
+        while let Some(sign) = self.peek().filter(|ch| matches!(ch, '+' | '-')) {
+            self.pos += 1;
+            let number_pos = self.pos;
+            let amount = self.parse_number()?.unwrap_or(1);
+            let amount =
+                isize::try_from(amount).map_err(|_| self.error(number_pos, "Number too large"))?;
+            offset += if sign == '+' { amount } else { -amount };
+        }
+        Ok(Some(Address { base, offset }))
+    }
+
+    /// A count after the command name, as in `:d 3`.
+    fn parse_count(&mut self) -> std::result::Result<Option<usize>, ParseError> {
+        let before = self.pos;
+        self.skip_whitespace();
+        let start = self.pos;
+        let count = self.parse_number()?;
+        if count.is_none() || !(self.at_separator() || self.peek().is_some_and(char::is_whitespace))
+        {
+            self.pos = before;
+            return Ok(None);
+        }
+        if count == Some(0) {
+            return Err(self.error(start, "Positive count required"));
+        }
+        Ok(count)
+    }
+
+    /// Words up to the next unquoted `|`, with their starting positions.
+    fn parse_words(&mut self) -> std::result::Result<Vec<(usize, String)>, ParseError> {
+        let mut words = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.at_separator() {
+                return Ok(words);
+            }
+            let start = self.pos;
+            let mut word = String::new();
+            while let Some(ch) = self.peek() {
+                if ch.is_whitespace() || ch == '|' {
+                    break;
+                }
+                self.pos += 1;
+                match ch {
+                    '\\' => match self.peek() {
+                        Some(escaped @ (' ' | '\t' | '\\' | '"' | '\'' | '|')) => {
+                            self.pos += 1;
+                            word.push(escaped);
+                        }
+                        _ => word.push('\\'),
+                    },
+                    '"' | '\'' => word.push_str(&self.parse_quoted(ch)?),
+                    _ => word.push(ch),
+                }
+            }
+            words.push((start, word));
+        }
+    }
+
+    /// The inside of a quoted string whose opening `quote` was just read.
+    /// Double quotes allow `\"` and `\\`; single quotes take text literally.
+    fn parse_quoted(&mut self, quote: char) -> std::result::Result<String, ParseError> {
+        let start = self.pos - 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error(start, "Unterminated quote")),
+                Some(ch) if ch == quote => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') if quote == '"' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(escaped @ ('"' | '\\')) => {
+                            self.pos += 1;
+                            text.push(escaped);
+                        }
+                        _ => text.push('\\'),
+                    }
+                }
+                Some(ch) => {
+                    self.pos += 1;
+                    text.push(ch);
+                }
+            }
+        }
+    }
+
+    /// `fields` fields separated by the punctuation character that starts
+    /// them, then text up to the next `|`, all as typed.
+    fn parse_delimited(&mut self, fields: usize) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        if let Some(delimiter) = self.peek().filter(|&ch| is_delimiter(ch)) {
+            self.pos += 1;
+            let mut remaining = fields;
+            while remaining > 0 {
+                match self.peek() {
+                    None => break,
+                    Some('\\') => self.pos = (self.pos + 2).min(self.chars.len()),
+                    Some(ch) => {
+                        self.pos += 1;
+                        if ch == delimiter {
+                            remaining -= 1;
+                        }
+                    }
+                }
+            }
+        }
+        while !self.at_separator() {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    /// Text up to the next `|`, trimmed, with `\|` unescaped.
+    fn parse_text(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(ch) = self.peek() {
+            if ch == '|' {
+                break;
+            }
+            self.pos += 1;
+            if ch == '\\' && self.peek() == Some('|') {
+                self.pos += 1;
+                text.push('|');
+            } else {
+                text.push(ch);
+            }
+        }
+        text.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(input: &str) -> ExCommand {
+        let mut commands = parse(input).unwrap();
+        assert_eq!(commands.len(), 1, "{input}");
+        commands.remove(0)
+    }
+
+    fn error(input: &str) -> (String, usize) {
+        let error = parse(input).unwrap_err();
+        (error.message, error.column)
+    }
+
+    #[test]
+    fn test_ranges_bang_and_count() {
+        let context = RangeContext {
+            current_line: 4,
+            line_count: 20,
+            visual: Some((2, 6)),
+            marks: {
+                let mut marks = [None; 26];
+                marks[0] = Some(7);
+                marks[1] = Some(11);
+                marks
+            },
+        };
+        let lines = |input: &str| {
+            let command = parse_one(input);
+            let range = command.line_range(&context).unwrap();
+            (command.name().to_string(), range.start, range.end)
+        };
+
+        assert_eq!(lines("10,20d"), ("delete".into(), 9, 19));
+        assert_eq!(lines(".,$y"), ("yank".into(), 4, 19));
+        assert_eq!(lines("'<,'>sort"), ("sort".into(), 2, 6));
+        assert_eq!(lines("%s/a/b/"), ("substitute".into(), 0, 19));
+        assert_eq!(lines("sort"), ("sort".into(), 0, 19));
+        assert_eq!(lines("3;+2j"), ("join".into(), 2, 4));
+        assert_eq!(lines("d 3"), ("delete".into(), 4, 6));
+        assert_eq!(lines("2,3d 3"), ("delete".into(), 2, 4));
+        assert_eq!(lines("12"), ("".into(), 11, 11));
+        assert_eq!(lines("::  $-1"), ("".into(), 18, 18));
+
+        let command = parse_one("sort! n");
+        assert!(command.bang);
+        assert_eq!(command.text(), "n");
+        assert_eq!(parse_one("g/x/d").name(), "global");
+        assert!(parse_one("g!/x/d").bang);
+        assert_eq!(parse_one("g 12").name(), "goto");
+        let command = parse_one("'<,'>Fmt2! a");
+        assert_eq!(command.name(), "Fmt2");
+        assert!(command.bang);
+        assert_eq!(command.text(), "a");
+        assert_eq!(lines("'a,'bw out.txt"), ("write".into(), 7, 11));
+        assert!(parse_one("'a,'cd").line_range(&context).is_err());
+        assert_eq!(error("'Ad"), ("Invalid mark".to_string(), 1));
+    }
+
+    #[test]
+    fn test_quoting_and_chaining() {
+        assert_eq!(parse_one(r"e my\ file.txt").words(), ["my file.txt"]);
+        assert_eq!(parse_one(r#"e "my file.txt""#).words(), ["my file.txt"]);
+        assert_eq!(parse_one(r"e 'a\b c'").words(), [r"a\b c"]);
+        assert_eq!(parse_one(r"e C:\src\main.rs").words(), [r"C:\src\main.rs"]);
+        assert_eq!(
+            parse_one(r"set lcs=tab:>\ ,eol:$").words(),
+            ["lcs=tab:> ,eol:$"]
+        );
+
+        let commands = parse("set nu | w|q").unwrap();
+        let names: Vec<_> = commands.iter().map(ExCommand::name).collect();
+        assert_eq!(names, ["set", "write", "quit"]);
+        assert_eq!(commands[0].words(), ["nu"]);
+        assert_eq!(parse_one(r"align \|").text(), "|");
+        assert_eq!(parse_one("g/a|b/d").text(), "/a|b/d");
+        let command = parse_one("2,3w >> log.txt");
+        assert!(command.appends());
+        assert_eq!(command.words(), ["log.txt"]);
+        assert!(parse_one("w >>").appends());
+        assert_eq!(parse_one("xa").name(), "wqall");
+
+        let commands = parse(r"s/a|b/c\/|/g | s#x##").unwrap();
+        assert_eq!(commands[0].text(), r"/a|b/c\/|/g");
+        assert_eq!(commands[1].text(), "#x##");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(error("foo"), ("Unknown command: foo".to_string(), 1));
+        assert_eq!(error("  pwd x"), ("Trailing characters: x".to_string(), 7));
+        assert_eq!(error("1,d"), ("Missing address after ','".to_string(), 3));
+        assert_eq!(error("5pwd"), ("No range allowed for :pwd".to_string(), 1));
+        assert_eq!(error("uniq!"), ("No ! allowed for :uniq".to_string(), 5));
+        assert_eq!(error(r#"e "a b"#), ("Unterminated quote".to_string(), 3));
+        assert_eq!(error("e a b"), ("Too many file names".to_string(), 5));
+        assert_eq!(error("d 0"), ("Positive count required".to_string(), 3));
+        assert_eq!(error("'!d"), ("Invalid mark".to_string(), 1));
+    }
+}
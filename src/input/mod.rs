@@ -1,7 +1,13 @@
+pub mod cmdline;
 pub mod commands;
+pub mod completion;
 pub mod events;
+pub mod ex;
 pub mod keybindings;
+pub mod range;
+pub mod user_commands;
 
+pub use cmdline::CommandLine;
 pub use commands::Command;
 pub use events::EventHandler;
 pub use keybindings::KeyBindings;
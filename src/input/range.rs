@@ -0,0 +1,223 @@
+use crate::editor::Buffer;
+use crate::Result;
+
+/// An inclusive range of zero-based line numbers given before an ex command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Context needed to resolve line addresses such as `.`, `$` and `'<`.
+#[derive(Debug, Clone, Copy)]
+pub struct RangeContext {
+    pub current_line: usize,
+    /// Lines that can be addressed; `$` is the last of them.
+    pub line_count: usize,
+    /// Lines of the last visual selection, for `'<` and `'>`.
+    pub visual: Option<(usize, usize)>,
+    /// Lines of the named marks `'a` to `'z`.
+    pub marks: [Option<usize>; 26],
+}
+
+impl RangeContext {
+    /// The context for `buffer` with the cursor on `current_line`. The empty
+    /// line after a final newline cannot be addressed, so `%` and `$` end at
+    /// the last line of text.
+    pub fn new(buffer: &Buffer, current_line: usize, visual: Option<(usize, usize)>) -> Self {
+        let line_count = buffer.text_line_count();
+        Self {
+            current_line: current_line.min(line_count.saturating_sub(1)),
+            line_count,
+            visual,
+            marks: buffer.marks,
+        }
+    }
+}
+
+/// Where a line address starts counting from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressBase {
+    /// `12`: a one-based line number.
+    Number(usize),
+    /// `.`, or an address made only of offsets such as `+2`.
+    Current,
+    /// `$`
+    Last,
+    /// `'a` to `'z`, or `'<` and `'>` for the last visual selection.
+    Mark(char),
+}
+
+/// A line address such as `.`, `$-1` or `'<+2`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Address {
+    pub base: AddressBase,
+    /// The sum of the `+n` / `-n` offsets after the base.
+    pub offset: isize,
+}
+
+/// The range before an ex command, as typed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Range {
+    /// `%`: the whole buffer.
+    Whole,
+    /// One address, or two separated by `,`. With `;` the first address
+    /// becomes the current line before the second is resolved.
+    Lines {
+        start: Address,
+        end: Option<Address>,
+        relative: bool,
+    },
+}
+
+impl Address {
+    /// The zero-based line the address refers to.
+    pub fn resolve(&self, context: &RangeContext) -> Result<usize> {
+        let base = match self.base {
+            AddressBase::Number(number) => number.saturating_sub(1) as isize,
+            AddressBase::Current => context.current_line as isize,
+            AddressBase::Last => context.line_count.saturating_sub(1) as isize,
+            AddressBase::Mark(mark) => {
+                let line = match mark {
+                    '<' => context.visual.map(|(start, _)| start),
+                    '>' => context.visual.map(|(_, end)| end),
+                    _ => Buffer::mark_slot(mark).and_then(|slot| context.marks[slot]),
+                };
+                line.ok_or_else(|| anyhow::anyhow!("Mark not set: '{mark}"))? as isize
+            }
+        };
+        let line = base + self.offset;
+        if line < 0 || line as usize >= context.line_count.max(1) {
+            return Err(anyhow::anyhow!("Invalid range"));
+        }
+        Ok(line as usize)
+    }
+}
+
+impl Range {
+    /// The lines the range covers. Backwards ranges such as `5,3` are
+    /// swapped.
+    pub fn resolve(&self, context: &RangeContext) -> Result<LineRange> {
+        let (start, end, relative) = match self {
+            Range::Whole => {
+                return Ok(LineRange {
+                    start: 0,
+                    end: context.line_count.saturating_sub(1),
+                })
+            }
+            Range::Lines {
+                start,
+                end,
+                relative,
+            } => (start, end, relative),
+        };
+
+        let first = start.resolve(context)?;
+        let second = match end {
+            Some(end) if *relative => end.resolve(&RangeContext {
+                current_line: first,
+                ..*context
+            })?,
+            Some(end) => end.resolve(context)?,
+            None => first,
+        };
+        Ok(LineRange {
+            start: first.min(second),
+            end: first.max(second),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    #[test]
+    fn test_resolve_range() {
+        let context = RangeContext {
+            current_line: 4,
+            line_count: 20,
+            visual: Some((2, 6)),
+            marks: {
+                let mut marks = [None; 26];
+                marks[0] = Some(9);
+                marks
+            },
+        };
+        let address = |base, offset| Address { base, offset };
+        let lines = |start, end, relative| Range::Lines {
+            start,
+            end,
+            relative,
+        };
+
+        assert_eq!(
+            Range::Whole.resolve(&context).unwrap(),
+            LineRange { start: 0, end: 19 }
+        );
+        assert_eq!(
+            lines(
+                address(AddressBase::Mark('<'), 0),
+                Some(address(AddressBase::Mark('>'), 0)),
+                false
+            )
+            .resolve(&context)
+            .unwrap(),
+            LineRange { start: 2, end: 6 }
+        );
+        assert_eq!(
+            lines(
+                address(AddressBase::Number(8), 0),
+                Some(address(AddressBase::Current, 2)),
+                true
+            )
+            .resolve(&context)
+            .unwrap(),
+            LineRange { start: 7, end: 9 }
+        );
+        assert_eq!(
+            lines(address(AddressBase::Last, -1), None, false)
+                .resolve(&context)
+                .unwrap(),
+            LineRange { start: 18, end: 18 }
+        );
+        assert!(lines(address(AddressBase::Number(30), 0), None, false)
+            .resolve(&context)
+            .is_err());
+        assert_eq!(
+            lines(
+                address(AddressBase::Mark('a'), 1),
+                Some(address(AddressBase::Last, 0)),
+                false
+            )
+            .resolve(&context)
+            .unwrap(),
+            LineRange { start: 10, end: 19 }
+        );
+        assert!(lines(address(AddressBase::Mark('b'), 0), None, false)
+            .resolve(&context)
+            .is_err());
+    }
+
+    #[test]
+    fn test_trailing_newline_is_not_a_line() {
+        let mut buffer = Buffer::empty();
+        buffer.content = Rope::from_str("b\na\n");
+        let context = RangeContext::new(&buffer, 2, None);
+        let range = Range::Whole.resolve(&context).unwrap();
+        assert_eq!(range, LineRange { start: 0, end: 1 });
+        assert_eq!(context.current_line, 1);
+
+        // What `:%sort` does with the range.
+        let mut lines: Vec<String> = (range.start..=range.end)
+            .map(|line| buffer.line(line).unwrap_or_default())
+            .collect();
+        lines.sort();
+        buffer.replace_lines(range.start, range.end, &lines);
+        assert_eq!(buffer.content.to_string(), "a\nb\n");
+
+        buffer.content = Rope::from_str("no newline");
+        assert_eq!(RangeContext::new(&buffer, 0, None).line_count, 1);
+    }
+}
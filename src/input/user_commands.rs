@@ -0,0 +1,129 @@
+use crate::input::range::LineRange;
+use crate::Result;
+
+/// User command names start with an uppercase letter, so they never clash
+/// with built-in commands, and contain only letters and digits.
+pub fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|ch: char| ch.is_ascii_uppercase())
+        && name.chars().all(|ch| ch.is_ascii_alphanumeric())
+}
+
+/// Quotes `args` as one word for `<q-args>`.
+fn quote(args: &str) -> String {
+    format!("\"{}\"", args.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Expands the replacement text of the user command `name`:
+///
+/// - `<args>`: the arguments as typed; `<q-args>`: the same, quoted as one word
+/// - `<bang>`: `!` when the command was run with a bang
+/// - `<line1>`, `<line2>`: the first and last line of the range (or the
+///   current line); `<range>`: `line1,line2` when a range was given
+/// - `<lt>`: a literal `<`
+///
+/// A range given to a command whose replacement uses none of the line
+/// placeholders is put in front of the replacement. Arguments or a bang the
+/// replacement has no placeholder for are an error.
+pub fn expand(
+    name: &str,
+    replacement: &str,
+    args: &str,
+    bang: bool,
+    range: LineRange,
+    has_range: bool,
+) -> Result<String> {
+    let takes_args = replacement.contains("<args>") || replacement.contains("<q-args>");
+    if !args.is_empty() && !takes_args {
+        return Err(anyhow::anyhow!("Trailing characters: {args}"));
+    }
+    if bang && !replacement.contains("<bang>") {
+        return Err(anyhow::anyhow!("No ! allowed for :{name}"));
+    }
+
+    let (line1, line2) = (range.start + 1, range.end + 1);
+    let uses_lines = ["<line1>", "<line2>", "<range>"]
+        .iter()
+        .any(|placeholder| replacement.contains(placeholder));
+    let replacement = if has_range && !uses_lines {
+        format!("{line1},{line2}{replacement}")
+    } else {
+        replacement.to_string()
+    };
+
+    let placeholders = [
+        ("<args>", args.to_string()),
+        ("<q-args>", quote(args)),
+        ("<bang>", if bang { "!" } else { "" }.to_string()),
+        ("<line1>", line1.to_string()),
+        ("<line2>", line2.to_string()),
+        (
+            "<range>",
+            if has_range {
+                format!("{line1},{line2}")
+            } else {
+                String::new()
+            },
+        ),
+        ("<lt>", "<".to_string()),
+    ];
+    // One pass, so placeholders inside the arguments are left as typed.
+    let mut expanded = String::new();
+    let mut rest = replacement.as_str();
+    while let Some(pos) = rest.find('<') {
+        expanded.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        match placeholders
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                expanded.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                expanded.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let line = LineRange { start: 4, end: 4 };
+        let lines = LineRange { start: 1, end: 9 };
+
+        assert_eq!(
+            expand("Fmt", "%!rustfmt", "", false, line, false).unwrap(),
+            "%!rustfmt"
+        );
+        assert_eq!(
+            expand("Sort", "sort<bang>", "", true, lines, true).unwrap(),
+            "2,10sort!"
+        );
+        assert_eq!(
+            expand("G", "grep <q-args>", r#"say "hi""#, false, line, false).unwrap(),
+            r#"grep "say \"hi\"""#
+        );
+        assert_eq!(
+            expand("Del", "<range>d | echo <line1><lt>", "", false, line, false).unwrap(),
+            "d | echo 5<"
+        );
+        assert_eq!(
+            expand("G", "grep <args> <lt>bang>", "<line1>", false, line, false).unwrap(),
+            "grep <line1> <bang>"
+        );
+        assert!(expand("Fmt", "%!rustfmt", "x", false, line, false).is_err());
+        assert!(expand("Fmt", "%!rustfmt", "", true, line, false).is_err());
+
+        assert!(is_valid_name("Fmt2"));
+        assert!(!is_valid_name("fmt"));
+        assert!(!is_valid_name("Fmt-x"));
+    }
+}
@@ -63,13 +63,18 @@ fn main() -> Result<()> {
     }
 
     let mut terminal = setup_terminal()?;
-    log::debug!("Terminal setup complete, rawmode enabled = {}", crossterm::terminal::is_raw_mode_enabled()?.to_string());
+    log::debug!(
+        "Terminal setup complete, rawmode enabled = {}",
+        crossterm::terminal::is_raw_mode_enabled()?
+    );
     let mut renderer =
         Renderer::new_with_glass_effects(app.config.theme.clone(), &app.config.current_theme);
 
     loop {
         app.update_cursor_blink();
         app.check_file_changes();
+        app.poll_grep();
+        app.fuzzy_finder.poll();
         renderer.update_theme_with_effects(app.config.theme.clone(), &app.config.current_theme);
 
         let (width, _) = crossterm::terminal::size()?;
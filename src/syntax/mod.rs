@@ -1,18 +1,27 @@
 use ratatui::style::{Color, Style as RatatuiStyle};
 use std::path::Path;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+
+/// Lines parsed before the one being looked at to find out whether it
+/// starts inside a comment or string. One opened further back is missed.
+pub const CONTEXT_LINES: usize = 500;
+
+/// Parser state at the start of a line, carried over from the lines before
+/// it so that comments and strings spanning several lines are recognised.
+#[derive(Debug, Clone)]
+pub struct LineState {
+    parse: ParseState,
+    scopes: ScopeStack,
+}
 
 pub struct SyntaxHighlighter {
     syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
 }
 
 impl SyntaxHighlighter {
     pub fn new() -> Self {
         Self {
             syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
         }
     }
 
@@ -30,13 +39,96 @@ impl SyntaxHighlighter {
         None
     }
 
+    /// The parser state at the start of a file.
+    pub fn line_state(&self, syntax: &SyntaxReference) -> LineState {
+        LineState {
+            parse: ParseState::new(syntax),
+            scopes: ScopeStack::new(),
+        }
+    }
+
+    /// Parses `lines` in order and returns the state at the start of the
+    /// line after them.
+    pub fn state_after<I, S>(&self, syntax: &SyntaxReference, lines: I) -> LineState
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut state = self.line_state(syntax);
+        for line in lines {
+            for (_, op) in self.parse_line(&mut state, line.as_ref()) {
+                state.scopes.apply(&op).unwrap_or(());
+            }
+        }
+        state
+    }
+
+    /// Parses `line`, which has no line ending, moving the parse state of
+    /// `state` to the next line. The scope stack is left for the caller to
+    /// apply the returned operations to.
+    fn parse_line(&self, state: &mut LineState, line: &str) -> Vec<(usize, ScopeStackOp)> {
+        // The default syntaxes expect each line to end in a newline, which
+        // is what ends line comments.
+        state
+            .parse
+            .parse_line(&format!("{line}\n"), &self.syntax_set)
+            .unwrap_or_default()
+    }
+
+    /// Returns the syntect scope stack in effect at `byte_pos` of `line`,
+    /// which starts in `state`.
+    pub fn scope_at(&self, state: &LineState, line: &str, byte_pos: usize) -> String {
+        let mut state = state.clone();
+        let ops = self.parse_line(&mut state, line);
+
+        let mut scope_stack = state.scopes;
+        for (pos, op) in ops {
+            if pos > byte_pos {
+                break;
+            }
+            scope_stack.apply(&op).unwrap_or(());
+        }
+
+        scope_stack.to_string()
+    }
+
+    pub fn is_comment_at(&self, state: &LineState, line: &str, byte_pos: usize) -> bool {
+        self.scope_at(state, line, byte_pos).contains("comment")
+    }
+
+    /// Marks every character of `line` that lies inside a string or comment,
+    /// and moves `state` past the line.
+    pub fn literal_mask(&self, state: &mut LineState, line: &str) -> Vec<bool> {
+        let ops = self.parse_line(state, line);
+        let is_literal = |scopes: &ScopeStack| {
+            let scopes = scopes.to_string();
+            scopes.contains("comment") || scopes.contains("string")
+        };
+
+        let mut ops = ops.into_iter().peekable();
+        let mut in_literal = is_literal(&state.scopes);
+        let mut mask = Vec::with_capacity(line.len());
+
+        for (byte_pos, _) in line.char_indices() {
+            while let Some((_, op)) = ops.next_if(|(pos, _)| *pos <= byte_pos) {
+                state.scopes.apply(&op).unwrap_or(());
+                in_literal = is_literal(&state.scopes);
+            }
+            mask.push(in_literal);
+        }
+        for (_, op) in ops {
+            state.scopes.apply(&op).unwrap_or(());
+        }
+
+        mask
+    }
+
     pub fn highlight_line(
         &self,
         line: &str,
         syntax: &SyntaxReference,
         theme_colors: &crate::ui::themes::ThemeColors,
     ) -> Vec<(RatatuiStyle, String)> {
-        use syntect::parsing::ParseState;
         let mut parse_state = ParseState::new(syntax);
         let ops = parse_state
             .parse_line(line, &self.syntax_set)
@@ -116,3 +208,29 @@ fn scope_to_theme_color(
         theme_colors.foreground.to_ratatui_color()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments_and_strings_carry_across_lines() {
+        let highlighter = SyntaxHighlighter::new();
+        let syntax = highlighter
+            .detect_language(Some(Path::new("main.rs")))
+            .unwrap();
+
+        let state = highlighter.state_after(syntax, ["let x = 1; /* start", "still"]);
+        assert!(highlighter.is_comment_at(&state, "end */ x", 0));
+        assert!(!highlighter.is_comment_at(&state, "end */ x", 7));
+
+        let state = highlighter.state_after(syntax, ["// line comment"]);
+        assert!(!highlighter.is_comment_at(&state, "code", 0));
+
+        let mut state = highlighter.state_after(syntax, ["let s = \"a"]);
+        assert_eq!(
+            highlighter.literal_mask(&mut state, "b\"; (c)"),
+            [true, true, false, false, false, false, false]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::file::FileIndex;
+use crate::syntax::SyntaxHighlighter;
+use crate::ui::themes::ThemeColors;
+use crate::ui::Theme;
+use crate::utils::{fuzzy_match, FuzzyMatch, TextUtils};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Lines of the selected file read for the preview pane.
+const PREVIEW_LINES: usize = 200;
+
+/// At most this much of the selected file is read for the preview.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+
+/// Where to open the file picked in the finder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenTarget {
+    Current,
+    Split,
+    VerticalSplit,
+    Tab,
+}
+
+/// The `Ctrl-P` popup that fuzzy-matches the project's files.
+#[derive(Default)]
+pub struct FuzzyFinder {
+    pub visible: bool,
+    pub query: String,
+    pub index: Option<FileIndex>,
+    /// All indexed paths, relative to the index root.
+    pub files: Vec<String>,
+    /// Indices into `files` of the matches, best first.
+    pub results: Vec<(usize, FuzzyMatch)>,
+    pub selected_index: usize,
+    pub list_state: ListState,
+    preview: Option<(String, Vec<String>)>,
+}
+
+impl FuzzyFinder {
+    /// Shows the finder and starts indexing `root` in the background.
+    pub fn open(&mut self, root: PathBuf) {
+        self.visible = true;
+        self.query.clear();
+        self.files.clear();
+        self.results.clear();
+        self.selected_index = 0;
+        self.preview = None;
+        self.index = Some(FileIndex::start(root));
+    }
+
+    pub fn close(&mut self) {
+        self.visible = false;
+        self.index = None;
+        self.preview = None;
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.index.as_ref().is_some_and(|index| !index.finished)
+    }
+
+    /// Adds files found by the background index, re-ranking if any arrived.
+    pub fn poll(&mut self) {
+        let Some(index) = &mut self.index else {
+            return;
+        };
+        let found = index.poll();
+        if found.is_empty() {
+            return;
+        }
+        self.files
+            .extend(found.iter().map(|path| path.to_string_lossy().into_owned()));
+        self.update_results(true);
+    }
+
+    pub fn push_char(&mut self, ch: char) {
+        self.query.push(ch);
+        self.update_results(false);
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.update_results(false);
+    }
+
+    pub fn clear_query(&mut self) {
+        self.query.clear();
+        self.update_results(false);
+    }
+
+    /// Re-ranks the files against the query. With `keep_selection` the
+    /// selected file stays selected, so results streaming in from the index
+    /// do not move it.
+    fn update_results(&mut self, keep_selection: bool) {
+        let selected = self
+            .selected_path()
+            .filter(|_| keep_selection)
+            .map(String::from);
+        self.results = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, path)| Some((idx, fuzzy_match(&self.query, path)?)))
+            .collect();
+        let files = &self.files;
+        self.results.sort_by(|(a, a_match), (b, b_match)| {
+            b_match
+                .score
+                .cmp(&a_match.score)
+                .then(files[*a].len().cmp(&files[*b].len()))
+                .then(files[*a].cmp(&files[*b]))
+        });
+
+        self.selected_index = selected
+            .and_then(|path| {
+                self.results
+                    .iter()
+                    .position(|(idx, _)| self.files[*idx] == path)
+            })
+            .unwrap_or(0);
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected_index = self.selected_index.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected_index + 1 < self.results.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    pub fn selected_path(&self) -> Option<&str> {
+        let (idx, _) = self.results.get(self.selected_index)?;
+        Some(&self.files[*idx])
+    }
+
+    /// The selected file as an absolute path.
+    pub fn selected_file(&self) -> Option<PathBuf> {
+        let root = &self.index.as_ref()?.root;
+        Some(root.join(self.selected_path()?))
+    }
+
+    fn preview_lines(&mut self) -> &[String] {
+        let Some(path) = self.selected_path().map(String::from) else {
+            return &[];
+        };
+        if self.preview.as_ref().map(|(cached, _)| cached) != Some(&path) {
+            let lines = self
+                .selected_file()
+                .and_then(|file| read_preview(&file))
+                .unwrap_or_else(|| vec!["[binary or unreadable file]".to_string()]);
+            self.preview = Some((path, lines));
+        }
+        self.preview
+            .as_ref()
+            .map_or(&[], |(_, lines)| lines.as_slice())
+    }
+
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        theme: &Theme,
+        highlighter: &SyntaxHighlighter,
+        colors: &ThemeColors,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let width = (area.width * 9 / 10).max(40).min(area.width);
+        let height = (area.height * 4 / 5).max(10).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(3)])
+            .split(popup);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(rows[1]);
+
+        let status = format!(
+            " {}/{}{} ",
+            self.results.len(),
+            self.files.len(),
+            if self.is_indexing() {
+                " indexing..."
+            } else {
+                ""
+            }
+        );
+        let prompt = Paragraph::new(Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Yellow)),
+            Span::raw(self.query.clone()),
+            Span::styled("█", Style::default().fg(theme.cursor)),
+        ]))
+        .style(Style::default().fg(theme.foreground).bg(theme.background))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Find File ")
+                .title_bottom(status)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+        frame.render_widget(prompt, rows[0]);
+
+        let visible_rows = columns[0].height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .take(self.selected_index.max(visible_rows) + visible_rows)
+            .map(|(idx, found)| {
+                let spans = self.files[*idx]
+                    .chars()
+                    .enumerate()
+                    .map(|(pos, ch)| {
+                        let style = if found.positions.contains(&pos) {
+                            Style::default()
+                                .fg(Color::Yellow)
+                                .add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default().fg(theme.foreground)
+                        };
+                        Span::styled(ch.to_string(), style)
+                    })
+                    .collect::<Vec<_>>();
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(Style::default().bg(theme.background)),
+            )
+            .highlight_style(Style::default().bg(theme.selection))
+            .highlight_symbol("▶ ");
+        self.list_state.select(if self.results.is_empty() {
+            None
+        } else {
+            Some(self.selected_index)
+        });
+        frame.render_stateful_widget(list, columns[0], &mut self.list_state);
+
+        let title = self
+            .selected_path()
+            .map_or(" Preview ".to_string(), |path| format!(" {path} "));
+        let syntax = self
+            .selected_file()
+            .and_then(|file| highlighter.detect_language(Some(&file)).cloned());
+        let preview_rows = columns[1].height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self
+            .preview_lines()
+            .iter()
+            .take(preview_rows)
+            .map(|line| {
+                let line = TextUtils::expand_tabs(line, 4);
+                match &syntax {
+                    Some(syntax) => Line::from(
+                        highlighter
+                            .highlight_line(&line, syntax, colors)
+                            .into_iter()
+                            .map(|(style, text)| Span::styled(text, style))
+                            .collect::<Vec<_>>(),
+                    ),
+                    None => Line::from(line),
+                }
+            })
+            .collect();
+        let preview = Paragraph::new(lines)
+            .style(Style::default().fg(theme.foreground).bg(theme.background))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(preview, columns[1]);
+    }
+}
+
+/// The first `PREVIEW_LINES` of `path`, reading no more than `PREVIEW_BYTES`,
+/// or `None` if it cannot be read or is not text.
+fn read_preview(path: &Path) -> Option<Vec<String>> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(PREVIEW_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) => text,
+        // The read can stop in the middle of a character.
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    Some(text.lines().take(PREVIEW_LINES).map(String::from).collect())
+}
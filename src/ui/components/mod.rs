@@ -1,5 +1,11 @@
 pub mod file_explorer;
+pub mod fuzzy_finder;
+pub mod quickfix;
 pub mod terminal;
+pub mod wildmenu;
 
 pub use file_explorer::FileExplorer;
+pub use fuzzy_finder::{FuzzyFinder, OpenTarget};
+pub use quickfix::QuickfixList;
 pub use terminal::Terminal;
+pub use wildmenu::Wildmenu;
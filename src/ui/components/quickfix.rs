@@ -0,0 +1,131 @@
+use crate::file::GrepMatch;
+use crate::ui::Theme;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+use std::path::{Path, PathBuf};
+
+/// Height of the quickfix panel, including its border.
+pub const QUICKFIX_HEIGHT: u16 = 10;
+
+/// The list of locations filled by `:grep`, shown in a panel below the
+/// editor with `:copen`.
+#[derive(Debug, Default)]
+pub struct QuickfixList {
+    pub entries: Vec<GrepMatch>,
+    pub selected_index: usize,
+    pub list_state: ListState,
+    /// The command that produced the list, shown in the panel title.
+    pub title: String,
+    /// Paths are shown relative to this directory.
+    pub root: PathBuf,
+    pub visible: bool,
+    /// Whether j/k/Enter go to the panel instead of the editor.
+    pub focused: bool,
+    pub searching: bool,
+}
+
+impl QuickfixList {
+    /// Clears the list for a new search.
+    pub fn reset(&mut self, title: String, root: PathBuf) {
+        self.entries.clear();
+        self.selected_index = 0;
+        self.list_state = ListState::default();
+        self.title = title;
+        self.root = root;
+        self.searching = true;
+    }
+
+    pub fn open(&mut self) {
+        self.visible = true;
+        self.focused = true;
+    }
+
+    pub fn close(&mut self) {
+        self.visible = false;
+        self.focused = false;
+    }
+
+    pub fn selected(&self) -> Option<&GrepMatch> {
+        self.entries.get(self.selected_index)
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected_index = self.selected_index.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected_index + 1 < self.entries.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    fn display_path<'a>(&self, path: &'a Path) -> std::path::Display<'a> {
+        path.strip_prefix(&self.root).unwrap_or(path).display()
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        if !self.visible {
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!(
+                            "{}:{}:{}",
+                            self.display_path(&entry.path),
+                            entry.line + 1,
+                            entry.col + 1
+                        ),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                    Span::raw(": "),
+                    Span::styled(entry.text.clone(), Style::default().fg(theme.foreground)),
+                ]))
+            })
+            .collect();
+
+        let title = format!(
+            " Quickfix - {} ({} matches{}) ",
+            self.title,
+            self.entries.len(),
+            if self.searching { ", searching..." } else { "" }
+        );
+        let border_style = if self.focused {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(theme.line_number)
+        };
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title(title)
+                    .style(Style::default().bg(theme.background)),
+            )
+            .highlight_style(
+                Style::default()
+                    .bg(theme.selection)
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▶ ");
+
+        self.list_state.select(if self.entries.is_empty() {
+            None
+        } else {
+            Some(self.selected_index)
+        });
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+    }
+}
@@ -1,5 +1,4 @@
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
+use crate::utils::shell;
 
 #[derive(Debug, Clone)]
 pub struct TerminalOutput {
@@ -65,55 +64,22 @@ impl TerminalOutput {
         self.add_line(format!("$ {command}"));
         self.is_running = true;
 
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
-            return Ok(());
-        }
-
-        let mut cmd = Command::new(parts[0]);
-        if parts.len() > 1 {
-            cmd.args(&parts[1..]);
-        }
-
-        match cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
-            Ok(mut child) => {
-                if let Some(stdout) = child.stdout.take() {
-                    let reader = BufReader::new(stdout);
-                    for line in reader.lines() {
-                        match line {
-                            Ok(line) => self.add_line(line),
-                            Err(_) => break,
-                        }
-                    }
+        match shell::run(command, None) {
+            Ok(output) => {
+                for line in output.stdout.lines() {
+                    self.add_line(line.to_string());
                 }
-
-                if let Some(stderr) = child.stderr.take() {
-                    let reader = BufReader::new(stderr);
-                    for line in reader.lines() {
-                        match line {
-                            Ok(line) => self.add_line(format!("ERROR: {line}")),
-                            Err(_) => break,
-                        }
-                    }
+                for line in output.stderr.lines() {
+                    self.add_line(format!("ERROR: {line}"));
                 }
-
-                match child.wait() {
-                    Ok(status) => {
-                        if !status.success() {
-                            self.add_line(format!(
-                                "Command exited with code: {}",
-                                status.code().unwrap_or(-1)
-                            ));
-                        }
-                    }
-                    Err(e) => {
-                        self.add_line(format!("Failed to wait for command: {e}"));
-                    }
+                if !output.success() {
+                    self.add_line(format!(
+                        "Command exited with code: {}",
+                        output.status.code().unwrap_or(-1)
+                    ));
                 }
             }
-            Err(e) => {
-                self.add_line(format!("Failed to execute command '{command}': {e}"));
-            }
+            Err(e) => self.add_line(e.to_string()),
         }
 
         self.is_running = false;
@@ -0,0 +1,117 @@
+use crate::ui::Theme;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// Most candidates shown at once; the list scrolls past this.
+const MAX_ROWS: u16 = 10;
+
+/// The completion candidates shown above the command line while Tab cycles
+/// through them.
+#[derive(Debug, Clone, Default)]
+pub struct Wildmenu {
+    pub candidates: Vec<String>,
+    /// `None` while the word as typed is on the command line.
+    pub selected: Option<usize>,
+    /// Byte offset in the command line of the word being completed.
+    pub start: usize,
+    /// The word as typed, restored after cycling past the last candidate.
+    pub original: String,
+}
+
+impl Wildmenu {
+    pub fn new(candidates: Vec<String>, start: usize, original: String) -> Self {
+        Self {
+            candidates,
+            selected: None,
+            start,
+            original,
+        }
+    }
+
+    /// Selects the next (or previous) candidate and returns the text to put
+    /// in place of the word. Cycling wraps through the word as typed.
+    pub fn cycle(&mut self, forward: bool) -> &str {
+        let count = self.candidates.len();
+        self.selected = match (self.selected, forward) {
+            (None, true) => Some(0),
+            (None, false) => count.checked_sub(1),
+            (Some(idx), true) if idx + 1 < count => Some(idx + 1),
+            (Some(idx), false) if idx > 0 => Some(idx - 1),
+            (Some(_), _) => None,
+        };
+        match self.selected {
+            Some(idx) => &self.candidates[idx],
+            None => &self.original,
+        }
+    }
+
+    /// Draws the candidates in a popup just above `command_area`, lined up
+    /// with the word being completed in `command_line`.
+    pub fn render(&self, frame: &mut Frame, command_area: Rect, command_line: &str, theme: &Theme) {
+        if self.candidates.is_empty() || command_area.y == 0 {
+            return;
+        }
+
+        let widest = self
+            .candidates
+            .iter()
+            .map(|candidate| candidate.width())
+            .max()
+            .unwrap_or(0) as u16;
+        let width = (widest + 2).min(command_area.width);
+        let height = (self.candidates.len() as u16).min(MAX_ROWS) + 2;
+        let height = height.min(command_area.y);
+        // One column for the ':' prompt.
+        let word_column = 1 + command_line[..self.start.min(command_line.len())].width() as u16;
+        let x = command_area.x + word_column.min(command_area.width - width);
+        let popup = Rect {
+            x,
+            y: command_area.y - height,
+            width,
+            height,
+        };
+
+        let items: Vec<ListItem> = self
+            .candidates
+            .iter()
+            .map(|candidate| ListItem::new(candidate.as_str()))
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(theme.line_number))
+                    .style(Style::default().bg(theme.background).fg(theme.foreground)),
+            )
+            .highlight_style(
+                Style::default()
+                    .bg(theme.selection)
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        let mut state = ListState::default().with_selected(self.selected);
+        frame.render_widget(Clear, popup);
+        frame.render_stateful_widget(list, popup, &mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycle_wraps_through_original() {
+        let mut menu = Wildmenu::new(vec!["sor".into(), "sort".into()], 0, "so".into());
+        assert_eq!(menu.cycle(true), "sor");
+        assert_eq!(menu.cycle(true), "sort");
+        assert_eq!(menu.cycle(true), "so");
+        assert_eq!(menu.cycle(false), "sort");
+        assert_eq!(menu.selected, Some(1));
+    }
+}
@@ -1,6 +1,10 @@
 use crate::app::App;
+use crate::editor::matching::HIGHLIGHT_SCAN_LINES;
+use crate::editor::wrap;
+use crate::ui::components::quickfix::QUICKFIX_HEIGHT;
 use crate::ui::theme::Theme;
 use crate::ui::themes::NeoTheme;
+use crate::utils::TextUtils;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -57,6 +61,9 @@ impl Renderer {
             let status_line_height = if app.config.ui.show_status_line { 1 } else { 0 };
 
             let mut constraints = vec![Constraint::Min(3)];
+            if app.quickfix.visible {
+                constraints.push(Constraint::Length(QUICKFIX_HEIGHT));
+            }
             if app.config.ui.show_status_line {
                 constraints.push(Constraint::Length(status_line_height));
             }
@@ -69,9 +76,14 @@ impl Renderer {
                 .constraints(constraints)
                 .split(main_chunks[1]);
 
-            self.render_editor(frame, app, editor_chunks[0]);
+            self.render_windows(frame, app, editor_chunks[0]);
 
             let mut chunk_idx = 1;
+            if app.quickfix.visible {
+                app.quickfix
+                    .render(frame, editor_chunks[chunk_idx], &self.theme);
+                chunk_idx += 1;
+            }
             if app.config.ui.show_status_line {
                 self.render_status_line(frame, app, editor_chunks[chunk_idx]);
                 chunk_idx += 1;
@@ -93,6 +105,9 @@ impl Renderer {
             let status_line_height = if app.config.ui.show_status_line { 1 } else { 0 };
 
             let mut constraints = vec![Constraint::Min(3)];
+            if app.quickfix.visible {
+                constraints.push(Constraint::Length(QUICKFIX_HEIGHT));
+            }
             if app.config.ui.show_status_line {
                 constraints.push(Constraint::Length(status_line_height));
             }
@@ -105,9 +120,13 @@ impl Renderer {
                 .constraints(constraints)
                 .split(size);
 
-            self.render_editor(frame, app, chunks[0]);
+            self.render_windows(frame, app, chunks[0]);
 
             let mut chunk_idx = 1;
+            if app.quickfix.visible {
+                app.quickfix.render(frame, chunks[chunk_idx], &self.theme);
+                chunk_idx += 1;
+            }
             if app.config.ui.show_status_line {
                 self.render_status_line(frame, app, chunks[chunk_idx]);
                 chunk_idx += 1;
@@ -117,6 +136,16 @@ impl Renderer {
             }
         }
 
+        if app.fuzzy_finder.visible {
+            app.fuzzy_finder.render(
+                frame,
+                size,
+                &self.theme,
+                &app.syntax_highlighter,
+                &app.config.current_theme.colors,
+            );
+        }
+
         if app.help_window.visible {
             self.render_help_window(frame, app, size);
         }
@@ -126,84 +155,177 @@ impl Renderer {
         }
     }
 
-    fn render_editor(&self, frame: &mut Frame, app: &App, area: Rect) {
+    /// Draws the tab line, when there are several tab pages, and the
+    /// windows of the current one.
+    fn render_windows(&self, frame: &mut Frame, app: &mut App, area: Rect) {
+        let area = if app.tabs.is_empty() {
+            area
+        } else {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(1)])
+                .split(area);
+            self.render_tab_line(frame, app, chunks[0]);
+            chunks[1]
+        };
+
+        let Some(split) = &app.split else {
+            self.render_editor(frame, app, area, true);
+            return;
+        };
+        let (direction, borders) = if split.vertical {
+            (Direction::Horizontal, Borders::LEFT)
+        } else {
+            (Direction::Vertical, Borders::TOP)
+        };
+        let chunks = Layout::default()
+            .direction(direction)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+
+        // The second window is set off by a border naming its buffer.
+        let names = app.buffer_names();
+        let second_buffer = if split.current_first {
+            split.window.buffer
+        } else {
+            app.current_buffer
+        };
+        let block = Block::default()
+            .borders(borders)
+            .border_style(Style::default().fg(self.theme.line_number))
+            .title(if split.vertical {
+                String::new()
+            } else {
+                format!(
+                    " {} ",
+                    names.get(second_buffer).cloned().unwrap_or_default()
+                )
+            });
+        let second = block.inner(chunks[1]);
+        frame.render_widget(block, chunks[1]);
+
+        let (current, other) = if split.current_first {
+            (chunks[0], second)
+        } else {
+            (second, chunks[0])
+        };
+        self.render_editor(frame, app, current, true);
+        app.with_other_window(|app| self.render_editor(frame, app, other, false));
+    }
+
+    fn render_tab_line(&self, frame: &mut Frame, app: &App, area: Rect) {
+        let spans: Vec<Span> = app
+            .tab_names()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let style = if idx == app.tab_index {
+                    Style::default()
+                        .fg(self.theme.background)
+                        .bg(self.theme.foreground)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(self.theme.foreground)
+                };
+                Span::styled(format!(" {} ", name), style)
+            })
+            .collect();
+        let background_style = self.get_glass_style(app, self.theme.background);
+        frame.render_widget(
+            Paragraph::new(Line::from(spans)).style(Style::default().bg(background_style)),
+            area,
+        );
+    }
+
+    /// Draws the current window into `area`; `active` is false for the other
+    /// window of a split, which shows no cursor.
+    fn render_editor(&self, frame: &mut Frame, app: &App, area: Rect, active: bool) {
         let buffer = app.current_buffer();
         let cursor = &app.cursor;
+        let editor = app.editor_config();
 
         if buffer.is_terminal() {
             self.render_terminal(frame, app, area);
             return;
         }
 
-        let line_number_width =
-            if app.config.editor.line_numbers || app.config.editor.relative_line_numbers {
-                5
-            } else {
-                0
-            };
-        let content_width = area.width as usize - line_number_width;
+        let line_number_width = if editor.line_numbers || editor.relative_line_numbers {
+            5
+        } else {
+            0
+        };
+        let content_width = (area.width as usize)
+            .saturating_sub(line_number_width)
+            .max(1);
         let viewport_height = area.height as usize;
-        let scroll_offset = app.config.editor.scroll_offset;
+        let scroll_offset = editor.scroll_offset;
+        let tab_size = editor.tab_size;
+        let wrap_options = app.wrap_options(content_width);
+        let eol_width = wrap_options.eol_width;
+
+        let cursor_line_content = buffer.line(cursor.line).unwrap_or_default();
+        let cursor_display_col =
+            TextUtils::display_column(&cursor_line_content, cursor.col, tab_size);
+
+        let bracket_pair = app
+            .bracket_matcher()
+            .with_max_lines(HIGHLIGHT_SCAN_LINES)
+            .highlight_pair(cursor.line, cursor.col);
+        let brackets: Vec<(usize, usize)> = bracket_pair
+            .map(|(first, second)| vec![first, second])
+            .unwrap_or_default();
 
         let mut visual_lines = Vec::new();
-        let mut current_visual_line = 0;
         let mut cursor_visual_line = 0;
 
         for line_idx in 0..buffer.line_count() {
+            if buffer.folds.is_hidden(line_idx) {
+                continue;
+            }
+            if let Some((start, end)) = buffer.folds.closed_range(line_idx) {
+                if (start..=end).contains(&cursor.line) {
+                    cursor_visual_line = visual_lines.len();
+                }
+                visual_lines.push((line_idx, 0, 0, content_width, 0));
+                continue;
+            }
+
             let line_content = buffer.line(line_idx).unwrap_or_default();
-            let line_len = line_content.len();
-
-            if !app.config.editor.wrap_lines || line_len <= content_width {
-                let displayed_content = if !app.config.editor.wrap_lines {
-                    let h_offset = app.get_horizontal_scroll_offset();
-                    if h_offset < line_len {
-                        let end = (h_offset + content_width).min(line_len);
-                        let result = line_content[h_offset..end].to_string();
-                        log::debug!(
-                            "  Line {line_idx} no-wrap: h_offset={h_offset}, showing chars {h_offset}..{end}, content=\"{result}\""
-                        );
-                        result
-                    } else {
-                        log::info!(
-                            "  Line {line_idx} no-wrap: h_offset={h_offset} >= line_len={line_len}, showing empty"
-                        );
-                        String::new()
-                    }
-                } else {
-                    log::info!("  Line {line_idx} fits in viewport: showing full content");
-                    line_content.clone()
-                };
+            let line_width =
+                TextUtils::display_width(&TextUtils::expand_tabs(&line_content, tab_size))
+                    + eol_width;
 
-                visual_lines.push((line_idx, 0, displayed_content));
+            if !editor.wrap_lines || line_width <= content_width {
+                let start_col = app.get_horizontal_scroll_offset();
                 if line_idx == cursor.line {
-                    cursor_visual_line = current_visual_line;
-                    log::info!(
-                        "  Line {line_idx} is cursor line, cursor_visual_line={cursor_visual_line}",
-                    );
+                    cursor_visual_line = visual_lines.len();
                 }
-                current_visual_line += 1;
+                visual_lines.push((line_idx, 0, start_col, start_col + content_width, 0));
             } else {
-                let wrapped_lines = line_len.div_ceil(content_width);
-                log::info!("  Line {line_idx} wrapping: {wrapped_lines} visual lines needed",);
-
-                for wrap_idx in 0..wrapped_lines {
-                    let start = wrap_idx * content_width;
-                    let end = (start + content_width).min(line_len);
-                    let segment = line_content[start..end].to_string();
-
-                    log::info!(
-                        "    Wrap {wrap_idx} of line {line_idx}: chars {start}..{end}, content=\"{segment}\"",
-                    );
+                let segments = wrap::wrap_line(&line_content, &wrap_options);
+                let cursor_segment = wrap::segment_index(&segments, cursor.col);
+                log::info!(
+                    "  Line {line_idx} wrapping: {} visual lines needed",
+                    segments.len()
+                );
 
-                    visual_lines.push((line_idx, wrap_idx, segment));
+                for (wrap_idx, segment) in segments.iter().enumerate() {
+                    let end_col = if wrap_idx + 1 == segments.len() {
+                        segment.end_col + eol_width
+                    } else {
+                        segment.end_col
+                    };
 
-                    if line_idx == cursor.line && cursor.col >= start && cursor.col < end {
-                        cursor_visual_line = current_visual_line;
-                        log::info!(
-                            "    Cursor found in wrap {wrap_idx} of line {line_idx}, cursor_visual_line={cursor_visual_line}",
-                        );
+                    if line_idx == cursor.line && wrap_idx == cursor_segment {
+                        cursor_visual_line = visual_lines.len();
                     }
-                    current_visual_line += 1;
+                    visual_lines.push((
+                        line_idx,
+                        wrap_idx,
+                        segment.start_col,
+                        end_col,
+                        segment.prefix_width,
+                    ));
                 }
             }
         }
@@ -228,101 +350,80 @@ impl Renderer {
         );
 
         let mut lines = Vec::new();
+        let mut display_cache: Option<(usize, Vec<Span<'static>>)> = None;
         for visual_idx in start_visual_line..end_visual_line {
-            if let Some((line_idx, wrap_idx, content)) = visual_lines.get(visual_idx) {
-                let line_number = if app.config.editor.relative_line_numbers {
-                    if *wrap_idx == 0 {
-                        if *line_idx == cursor.line {
-                            let line_num = if app.config.editor.line_numbers {
+            if let Some(&(line_idx, wrap_idx, start_col, end_col, prefix_width)) =
+                visual_lines.get(visual_idx)
+            {
+                let line_number = if editor.relative_line_numbers {
+                    if wrap_idx == 0 {
+                        if line_idx == cursor.line {
+                            if editor.line_numbers {
                                 format!("{:4} ", line_idx + 1)
                             } else {
                                 format!("{:4} ", 0)
-                            };
-                            log::info!("  Line number (cursor line): \"{}\"", line_num.trim());
-                            line_num
+                            }
                         } else {
-                            let relative_distance = (*line_idx).abs_diff(cursor.line);
-                            let line_num = format!("{relative_distance:4} ");
-                            log::info!(
-                                "  Line number (relative): distance={}, display=\"{}\"",
-                                relative_distance,
-                                line_num.trim()
-                            );
-                            line_num
+                            let relative_distance = line_idx.abs_diff(cursor.line);
+                            format!("{relative_distance:4} ")
                         }
                     } else {
-                        log::info!("  Line number (wrap continuation): empty");
                         "     ".to_string()
                     }
-                } else if app.config.editor.line_numbers {
-                    if *wrap_idx == 0 {
-                        let line_num = format!("{:4} ", line_idx + 1);
-                        log::info!("  Line number (absolute): {}", line_num.trim());
-                        line_num
+                } else if editor.line_numbers {
+                    if wrap_idx == 0 {
+                        format!("{:4} ", line_idx + 1)
                     } else {
-                        log::info!("  Line number (wrap continuation): empty");
                         "     ".to_string()
                     }
                 } else {
-                    log::info!("  Line numbers disabled");
                     String::new()
                 };
 
-                let buffer = app.current_buffer();
                 let mut spans = vec![Span::styled(
                     line_number,
                     Style::default().fg(self.theme.line_number),
                 )];
 
-                let content_with_highlights = self.apply_highlighting(content, *line_idx, app);
-
-                if app.config.editor.syntax_highlighting {
-                    if let Some(syntax) = buffer
-                        .file_path()
-                        .and_then(|path| app.syntax_highlighter.detect_language(Some(path)))
-                    {
-                        let highlighted_spans = app.syntax_highlighter.highlight_line(
-                            content,
-                            syntax,
-                            &app.config.current_theme.colors,
-                        );
-
-                        let mut processed_spans = Vec::new();
-                        for (mut highlight_style, text) in highlighted_spans {
-                            if *line_idx == cursor.line {
-                                highlight_style = highlight_style.bg(self.theme.current_line);
-                            }
-                            processed_spans.push(Span::styled(text, highlight_style));
-                        }
+                if let Some((start, end)) = buffer.folds.closed_range(line_idx) {
+                    spans.push(self.fold_summary(app, (start, end), content_width));
+                    lines.push(Line::from(spans));
+                    continue;
+                }
 
-                        let final_spans = self.apply_cursor_overlay(
-                            processed_spans,
-                            app,
-                            *line_idx,
-                            wrap_idx * content_width,
-                        );
-                        spans.extend(final_spans);
-                    } else {
-                        let final_spans = self.apply_cursor_overlay(
-                            content_with_highlights,
-                            app,
-                            *line_idx,
-                            wrap_idx * content_width,
-                        );
-                        spans.extend(final_spans);
-                    }
-                } else {
-                    let simple_spans = vec![Span::raw(content)];
-                    let final_spans = self.apply_cursor_overlay(
-                        simple_spans,
+                if display_cache.as_ref().map(|(idx, _)| *idx) != Some(line_idx) {
+                    let line_content = buffer.line(line_idx).unwrap_or_default();
+                    display_cache = Some((
+                        line_idx,
+                        self.display_spans(&line_content, line_idx, &brackets, app),
+                    ));
+                }
+                let display_spans = display_cache
+                    .as_ref()
+                    .map(|(_, spans)| spans.as_slice())
+                    .unwrap_or_default();
+
+                let segment = Self::slice_spans(display_spans, start_col, end_col);
+                let is_last_segment = visual_lines
+                    .get(visual_idx + 1)
+                    .is_none_or(|next| next.0 != line_idx);
+                let final_spans = if active {
+                    self.apply_cursor_overlay(
+                        segment,
                         app,
-                        *line_idx,
-                        wrap_idx * content_width,
-                    );
-                    spans.extend(final_spans);
+                        line_idx,
+                        (start_col, end_col),
+                        is_last_segment,
+                        cursor_display_col,
+                    )
+                } else {
+                    segment
+                };
+                if prefix_width > 0 {
+                    spans.extend(self.showbreak_prefix(app, prefix_width));
                 }
+                spans.extend(final_spans);
 
-                log::info!("  Final spans count: {}", spans.len());
                 lines.push(Line::from(spans));
             }
         }
@@ -341,27 +442,303 @@ impl Renderer {
         frame.render_widget(paragraph, area);
     }
 
+    fn display_spans(
+        &self,
+        content: &str,
+        line_idx: usize,
+        brackets: &[(usize, usize)],
+        app: &App,
+    ) -> Vec<Span<'static>> {
+        let buffer = app.current_buffer();
+        let bracket_cols: Vec<usize> = brackets
+            .iter()
+            .filter(|(line, _)| *line == line_idx)
+            .map(|(_, col)| *col)
+            .collect();
+
+        let highlighted = if app.config.editor.syntax_highlighting {
+            if let Some(syntax) = buffer
+                .file_path()
+                .and_then(|path| app.syntax_highlighter.detect_language(Some(path)))
+            {
+                let spans = app
+                    .syntax_highlighter
+                    .highlight_line(content, syntax, &app.config.current_theme.colors)
+                    .into_iter()
+                    .map(|(mut highlight_style, text)| {
+                        if line_idx == app.cursor.line {
+                            highlight_style = highlight_style.bg(self.theme.current_line);
+                        }
+                        Span::styled(text, highlight_style)
+                    })
+                    .collect();
+                let spans = self.highlight_search(spans, content, line_idx, app);
+                self.highlight_brackets(spans, &bracket_cols)
+            } else {
+                self.apply_highlighting(content, line_idx, &bracket_cols, app)
+            }
+        } else {
+            self.highlight_brackets(vec![Span::raw(content)], &bracket_cols)
+        };
+
+        self.expand_whitespace(highlighted, content, line_idx, app)
+    }
+
+    /// Renders the single line shown in place of a closed fold.
+    fn fold_summary(&self, app: &App, fold: (usize, usize), width: usize) -> Span<'static> {
+        let (start, end) = fold;
+        let first_line = app.current_buffer().line(start).unwrap_or_default();
+        let mut summary = format!(
+            "+--{:>3} lines: {}",
+            end + 1 - start,
+            TextUtils::expand_tabs(first_line.trim(), app.editor_config().tab_size)
+        );
+        let summary_width = TextUtils::display_width(&summary);
+        if summary_width < width {
+            summary.push_str(&"-".repeat(width - summary_width));
+        }
+
+        let mut style = Style::default()
+            .fg(self.theme.line_number)
+            .bg(self.theme.current_line);
+        if (start..=end).contains(&app.cursor.line) && app.should_show_cursor() {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        Span::styled(summary, style)
+    }
+
+    /// Gives the search matches on a syntax-highlighted line the search
+    /// match style.
+    fn highlight_search<'a>(
+        &self,
+        spans: Vec<Span<'a>>,
+        content: &str,
+        line_idx: usize,
+        app: &App,
+    ) -> Vec<Span<'a>> {
+        let matches = app.search_state.line_matches(line_idx, content);
+        if matches.is_empty() {
+            return spans;
+        }
+
+        let mut result = Vec::new();
+        let mut byte_idx = 0;
+
+        for span in spans {
+            let mut text = String::new();
+            let mut in_match = false;
+            for ch in span.content.chars() {
+                let matched = matches
+                    .iter()
+                    .any(|&(start, end)| (start..end).contains(&byte_idx));
+                if matched != in_match && !text.is_empty() {
+                    let style = if in_match {
+                        span.style.bg(Color::Yellow).fg(Color::Black)
+                    } else {
+                        span.style
+                    };
+                    result.push(Span::styled(std::mem::take(&mut text), style));
+                }
+                in_match = matched;
+                text.push(ch);
+                byte_idx += ch.len_utf8();
+            }
+            if !text.is_empty() {
+                let style = if in_match {
+                    span.style.bg(Color::Yellow).fg(Color::Black)
+                } else {
+                    span.style
+                };
+                result.push(Span::styled(text, style));
+            }
+        }
+
+        result
+    }
+
+    /// Gives the characters at `cols` the matching-bracket background.
+    fn highlight_brackets<'a>(&self, spans: Vec<Span<'a>>, cols: &[usize]) -> Vec<Span<'a>> {
+        if cols.is_empty() {
+            return spans;
+        }
+
+        let mut result = Vec::new();
+        let mut char_idx = 0;
+
+        for span in spans {
+            let mut text = String::new();
+            for ch in span.content.chars() {
+                if cols.contains(&char_idx) {
+                    if !text.is_empty() {
+                        result.push(Span::styled(std::mem::take(&mut text), span.style));
+                    }
+                    result.push(Span::styled(
+                        ch.to_string(),
+                        span.style
+                            .bg(self.theme.match_paren)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                } else {
+                    text.push(ch);
+                }
+                char_idx += 1;
+            }
+            if !text.is_empty() {
+                result.push(Span::styled(text, span.style));
+            }
+        }
+
+        result
+    }
+
+    /// Expands tabs to the configured width and, in `list` mode, replaces
+    /// tabs, trailing spaces and non-breaking spaces with their `listchars`.
+    fn expand_whitespace(
+        &self,
+        spans: Vec<Span<'_>>,
+        content: &str,
+        line_idx: usize,
+        app: &App,
+    ) -> Vec<Span<'static>> {
+        let editor = app.editor_config();
+        let tab_size = editor.tab_size;
+        let list = editor.list;
+        let list_chars = app.config.list_chars();
+        let trail_start = content.trim_end_matches(' ').chars().count();
+
+        let mut result = Vec::new();
+        let mut col = 0;
+        let mut char_idx = 0;
+
+        for span in spans {
+            let whitespace_style = span.style.fg(self.theme.whitespace);
+            let mut text = String::new();
+
+            for ch in span.content.chars() {
+                let marker = match ch {
+                    '\t' => {
+                        let width = TextUtils::tab_width(col, tab_size);
+                        col += width;
+                        let glyphs = if list {
+                            list_chars.render_tab(width)
+                        } else {
+                            None
+                        };
+                        if glyphs.is_none() {
+                            text.push_str(&" ".repeat(width));
+                        }
+                        glyphs
+                    }
+                    ' ' if list && char_idx >= trail_start && list_chars.trail.is_some() => {
+                        col += 1;
+                        list_chars.trail.map(String::from)
+                    }
+                    '\u{a0}' if list && list_chars.nbsp.is_some() => {
+                        col += 1;
+                        list_chars.nbsp.map(String::from)
+                    }
+                    _ => {
+                        col += TextUtils::char_width(ch);
+                        text.push(ch);
+                        None
+                    }
+                };
+
+                if let Some(marker) = marker {
+                    if !text.is_empty() {
+                        result.push(Span::styled(std::mem::take(&mut text), span.style));
+                    }
+                    result.push(Span::styled(marker, whitespace_style));
+                }
+                char_idx += 1;
+            }
+
+            if !text.is_empty() {
+                result.push(Span::styled(text, span.style));
+            }
+        }
+
+        if list {
+            if let Some(eol) = list_chars.eol {
+                let eol_style = self
+                    .get_text_style(line_idx, false, false, app)
+                    .fg(self.theme.whitespace);
+                result.push(Span::styled(eol.to_string(), eol_style));
+            }
+        }
+
+        result
+    }
+
+    /// Break indent followed by the `showbreak` marker, `width` columns in total.
+    fn showbreak_prefix(&self, app: &App, width: usize) -> Vec<Span<'static>> {
+        let showbreak = &app.config.editor.showbreak;
+        let indent = width.saturating_sub(TextUtils::display_width(showbreak));
+        let mut spans = Vec::new();
+        if indent > 0 {
+            spans.push(Span::raw(" ".repeat(indent)));
+        }
+        if !showbreak.is_empty() && indent < width {
+            spans.push(Span::styled(
+                showbreak.clone(),
+                Style::default().fg(self.theme.whitespace),
+            ));
+        }
+        spans
+    }
+
+    fn slice_spans(
+        spans: &[Span<'static>],
+        start_col: usize,
+        end_col: usize,
+    ) -> Vec<Span<'static>> {
+        let mut result = Vec::new();
+        let mut col = 0;
+
+        for span in spans {
+            if col >= end_col {
+                break;
+            }
+
+            let mut text = String::new();
+            for ch in span.content.chars() {
+                let width = TextUtils::char_width(ch);
+                if col >= start_col && col + width <= end_col {
+                    text.push(ch);
+                }
+                col += width;
+            }
+
+            if !text.is_empty() {
+                result.push(Span::styled(text, span.style));
+            }
+        }
+
+        result
+    }
+
     fn apply_cursor_overlay<'a>(
         &self,
         spans: Vec<Span<'a>>,
         app: &App,
         line_idx: usize,
-        line_start_col: usize,
+        segment: (usize, usize),
+        is_last_segment: bool,
+        cursor_col: usize,
     ) -> Vec<Span<'a>> {
         if line_idx != app.cursor.line || !app.should_show_cursor() {
             return spans;
         }
 
-        let h_offset = app.get_horizontal_scroll_offset();
-        let cursor_col = app.cursor.col;
-
-        let cursor_pos_in_segment = if cursor_col >= h_offset + line_start_col {
-            cursor_col - h_offset - line_start_col
-        } else {
+        let (start_col, end_col) = segment;
+        if cursor_col < start_col
+            || cursor_col > end_col
+            || (cursor_col == end_col && !is_last_segment)
+        {
             return spans;
-        };
+        }
 
-        self.apply_normal_cursor(spans, cursor_pos_in_segment)
+        self.apply_normal_cursor(spans, cursor_col - start_col)
     }
 
     fn apply_normal_cursor<'a>(&self, spans: Vec<Span<'a>>, cursor_pos: usize) -> Vec<Span<'a>> {
@@ -442,7 +819,10 @@ impl Renderer {
             " [No Name] ".to_string()
         };
 
-        let cursor_info = format!(" {}:{} ", cursor.line + 1, cursor.col + 1);
+        let cursor_info = match app.search_state.counter() {
+            Some(counter) => format!(" {} {}:{} ", counter, cursor.line + 1, cursor.col + 1),
+            None => format!(" {}:{} ", cursor.line + 1, cursor.col + 1),
+        };
 
         let spans = vec![
             Span::styled(
@@ -491,11 +871,29 @@ impl Renderer {
         };
 
         let content = if app.mode.is_command() {
-            format!(":{}", app.command_line)
+            let prompt = match &app.search_prompt {
+                Some(prompt) if prompt.backward => '?',
+                Some(_) => '/',
+                None => ':',
+            };
+            // The character under the command-line cursor is drawn reversed,
+            // or a space when the cursor is at the end.
+            let line = &app.command_line;
+            let before = line.before_cursor();
+            let mut after = line.text[line.cursor..].chars();
+            let under = after.next().unwrap_or(' ');
+            Line::from(vec![
+                Span::raw(format!("{prompt}{before}")),
+                Span::styled(
+                    under.to_string(),
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ),
+                Span::raw(after.as_str().to_string()),
+            ])
         } else if let Some(ref message) = app.status_message {
-            message.clone()
+            Line::from(message.clone())
         } else {
-            String::new()
+            Line::default()
         };
 
         let background_style = self.get_glass_style(app, self.theme.background);
@@ -507,6 +905,10 @@ impl Renderer {
 
         frame.render_widget(paragraph, chunks[0]);
 
+        if let Some(menu) = app.wildmenu.as_ref().filter(|_| app.mode.is_command()) {
+            menu.render(frame, chunks[0], app.command_line.as_str(), &self.theme);
+        }
+
         if let Some(ref error_msg) = app.error_message {
             if chunks.len() > 1 {
                 let error_paragraph = Paragraph::new(error_msg.clone())
@@ -520,6 +922,7 @@ impl Renderer {
         &self,
         content: &'a str,
         line_idx: usize,
+        bracket_cols: &[usize],
         app: &App,
     ) -> Vec<Span<'a>> {
         let mut segments = Vec::new();
@@ -560,11 +963,7 @@ impl Renderer {
         }
 
         let mut spans = Vec::new();
-        let query = if app.search_state.is_active && !app.search_state.query.is_empty() {
-            Some(&app.search_state.query)
-        } else {
-            None
-        };
+        let matches = app.search_state.line_matches(line_idx, content);
 
         for (start, end, is_selected, _) in segments {
             let text = &content[start..end];
@@ -572,37 +971,38 @@ impl Renderer {
                 continue;
             }
 
-            if let Some(search_query) = query {
-                let mut last_pos = 0;
-                while let Some(match_pos) = text[last_pos..].find(search_query) {
-                    let actual_pos = last_pos + match_pos;
-
-                    if actual_pos > last_pos {
-                        let before_text = &text[last_pos..actual_pos];
-                        let style = self.get_text_style(line_idx, is_selected, false, app);
-                        spans.push(Span::styled(before_text, style));
-                    }
+            let mut last_pos = 0;
+            let segment_matches = matches
+                .iter()
+                .map(|&(match_start, match_end)| {
+                    (
+                        match_start.clamp(start, end) - start,
+                        match_end.clamp(start, end) - start,
+                    )
+                })
+                .filter(|(match_start, match_end)| match_start < match_end);
+            for (match_start, match_end) in segment_matches {
+                if match_start > last_pos {
+                    let before_text = &text[last_pos..match_start];
+                    let style = self.get_text_style(line_idx, is_selected, false, app);
+                    spans.push(Span::styled(before_text, style));
+                }
 
-                    let match_end = actual_pos + search_query.len();
-                    let match_text = &text[actual_pos..match_end];
-                    let style = self.get_text_style(line_idx, is_selected, true, app);
-                    spans.push(Span::styled(match_text, style));
+                let match_text = &text[match_start..match_end];
+                let style = self.get_text_style(line_idx, is_selected, true, app);
+                spans.push(Span::styled(match_text, style));
 
-                    last_pos = match_end;
-                }
+                last_pos = match_end;
+            }
 
-                if last_pos < text.len() {
-                    let remaining_text = &text[last_pos..];
-                    let style = self.get_text_style(line_idx, is_selected, false, app);
-                    spans.push(Span::styled(remaining_text, style));
-                }
-            } else {
+            if last_pos < text.len() {
+                let remaining_text = &text[last_pos..];
                 let style = self.get_text_style(line_idx, is_selected, false, app);
-                spans.push(Span::styled(text, style));
+                spans.push(Span::styled(remaining_text, style));
             }
         }
 
-        spans
+        self.highlight_brackets(spans, bracket_cols)
     }
 
     fn get_text_style(
@@ -8,6 +8,8 @@ pub struct Theme {
     pub selection: Color,
     pub line_number: Color,
     pub current_line: Color,
+    pub whitespace: Color,
+    pub match_paren: Color,
     pub status_bg: Color,
     pub status_fg: Color,
     pub command_bg: Color,
@@ -70,6 +72,8 @@ impl Theme {
             selection: Color::Rgb(70, 130, 180),    // #4682b4 (Steel Blue - better contrast)
             line_number: Color::Rgb(133, 133, 133), // #858585
             current_line: Color::Rgb(45, 45, 45),   // #2d2d2d
+            whitespace: Color::Rgb(133, 133, 133),  // #858585
+            match_paren: Color::Rgb(81, 81, 81),    // #515151
             status_bg: Color::Rgb(0, 120, 215),     // #0078d7
             status_fg: Color::Rgb(255, 255, 255),   // #ffffff
             command_bg: Color::Rgb(30, 30, 30),     // #1e1e1e
@@ -95,6 +99,8 @@ impl Theme {
             selection: Color::Rgb(0, 120, 215),      // #0078d7 (Blue - good contrast on white)
             line_number: Color::Rgb(128, 128, 128),  // #808080
             current_line: Color::Rgb(245, 245, 245), // #f5f5f5
+            whitespace: Color::Rgb(128, 128, 128),   // #808080
+            match_paren: Color::Rgb(200, 200, 200),  // #c8c8c8
             status_bg: Color::Rgb(0, 120, 215),      // #0078d7
             status_fg: Color::Rgb(255, 255, 255),    // #ffffff
             command_bg: Color::Rgb(255, 255, 255),   // #ffffff
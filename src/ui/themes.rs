@@ -21,6 +21,10 @@ pub struct ThemeColors {
     pub selection: ColorValue,
     pub line_number: ColorValue,
     pub current_line: ColorValue,
+    #[serde(default = "default_whitespace")]
+    pub whitespace: ColorValue,
+    #[serde(default = "default_match_paren")]
+    pub match_paren: ColorValue,
 
     // Glass/Transparency effects
     #[serde(default = "default_opacity")]
@@ -132,6 +136,20 @@ fn default_highlight() -> ColorValue {
         b: 255,
     }
 }
+fn default_whitespace() -> ColorValue {
+    ColorValue::Rgb {
+        r: 96,
+        g: 96,
+        b: 96,
+    }
+}
+fn default_match_paren() -> ColorValue {
+    ColorValue::Rgb {
+        r: 81,
+        g: 81,
+        b: 81,
+    }
+}
 fn default_shadow() -> ColorValue {
     ColorValue::Rgb { r: 0, g: 0, b: 0 }
 }
@@ -375,6 +393,16 @@ impl NeoTheme {
                     g: 45,
                     b: 45,
                 },
+                whitespace: ColorValue::Rgb {
+                    r: 133,
+                    g: 133,
+                    b: 133,
+                },
+                match_paren: ColorValue::Rgb {
+                    r: 81,
+                    g: 81,
+                    b: 81,
+                },
 
                 background_opacity: 1.0,
                 blur_radius: 0.0,
@@ -554,6 +582,8 @@ impl NeoTheme {
             selection: self.colors.selection.to_ratatui_color(),
             line_number: self.colors.line_number.to_ratatui_color(),
             current_line: self.colors.current_line.to_ratatui_color(),
+            whitespace: self.colors.whitespace.to_ratatui_color(),
+            match_paren: self.colors.match_paren.to_ratatui_color(),
             status_bg: self.colors.status_bg.to_ratatui_color(),
             status_fg: self.colors.status_fg.to_ratatui_color(),
             command_bg: self.colors.command_bg.to_ratatui_color(),
@@ -0,0 +1,118 @@
+/// A candidate matched by [`fuzzy_match`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Character indices of the matched characters, for highlighting.
+    pub positions: Vec<usize>,
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_FILE_NAME: i64 = 12;
+const PENALTY_GAP_START: i64 = 3;
+
+/// Bonus for a match right after `prev`, rewarding starts of words and
+/// path components as fzf does.
+fn boundary_bonus(prev: Option<char>, ch: char) -> i64 {
+    match prev {
+        None => 10,
+        Some('/' | '\\') => 9,
+        Some('_' | '-' | '.' | ' ') => 8,
+        Some(prev) if prev.is_lowercase() && ch.is_uppercase() => 7,
+        Some(prev) if !prev.is_alphanumeric() => 6,
+        _ => 0,
+    }
+}
+
+/// Matches the characters of `query` in order anywhere in `candidate`,
+/// fzf-style. Case is ignored unless the query contains an uppercase
+/// letter; whitespace in the query is ignored.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let normalize = |ch: char| {
+        if case_sensitive {
+            ch
+        } else {
+            ch.to_lowercase().next().unwrap_or(ch)
+        }
+    };
+    let query: Vec<char> = query
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .map(normalize)
+        .collect();
+    if query.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+
+    // Find where the first complete match ends, then walk back from there
+    // to find the shortest window ending at that point.
+    let mut matched = 0;
+    let end = chars.iter().position(|&ch| {
+        if normalize(ch) == query[matched] {
+            matched += 1;
+        }
+        matched == query.len()
+    })?;
+
+    let mut positions = vec![0; query.len()];
+    let mut remaining = query.len();
+    for idx in (0..=end).rev() {
+        if normalize(chars[idx]) == query[remaining - 1] {
+            remaining -= 1;
+            positions[remaining] = idx;
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    for (n, &pos) in positions.iter().enumerate() {
+        score += SCORE_MATCH + boundary_bonus(pos.checked_sub(1).map(|p| chars[p]), chars[pos]);
+        if n > 0 {
+            match pos - positions[n - 1] - 1 {
+                0 => score += BONUS_CONSECUTIVE,
+                gap => score -= PENALTY_GAP_START + gap as i64,
+            }
+        }
+    }
+
+    let name_start = chars
+        .iter()
+        .rposition(|&ch| ch == '/' || ch == '\\')
+        .map_or(0, |idx| idx + 1);
+    if positions[0] >= name_start {
+        score += BONUS_FILE_NAME;
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions_and_case() {
+        let found = fuzzy_match("mrs", "src/main.rs").unwrap();
+        assert_eq!(found.positions, vec![4, 9, 10]);
+        assert!(fuzzy_match("xyz", "src/main.rs").is_none());
+        assert!(fuzzy_match("Main", "src/main.rs").is_none());
+        assert!(fuzzy_match("main", "src/Main.rs").is_some());
+    }
+
+    #[test]
+    fn test_ranking_prefers_boundaries_and_file_names() {
+        let score = |candidate| fuzzy_match("fb", candidate).unwrap().score;
+        assert!(score("foo_bar.rs") > score("fabric.rs"));
+        assert!(score("src/foo/bar.rs") < score("src/fooBar.rs"));
+
+        let score = |candidate| fuzzy_match("app", candidate).unwrap().score;
+        assert!(score("src/app.rs") > score("app/src/wrapper.rs"));
+    }
+}
@@ -1,3 +1,6 @@
+pub mod fuzzy;
+pub mod shell;
 pub mod text;
 
+pub use fuzzy::{fuzzy_match, FuzzyMatch};
 pub use text::*;
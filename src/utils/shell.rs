@@ -0,0 +1,104 @@
+use crate::Result;
+use anyhow::Context;
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// What a shell command printed and how it exited.
+#[derive(Debug, Clone)]
+pub struct ShellOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+impl ShellOutput {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// A one-line report of a failed command: its exit code and the first
+    /// line of its error output.
+    pub fn error_message(&self) -> String {
+        let code = match self.status.code() {
+            Some(code) => format!("exited with code {code}"),
+            None => "was terminated by a signal".to_string(),
+        };
+        match self.stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("Command {code}: {line}"),
+            None => format!("Command {code}"),
+        }
+    }
+}
+
+/// Builds the command that runs `command` through the user's shell, so
+/// pipes and quoting work as typed.
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let mut cmd = Command::new(shell);
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Runs `command` in the shell, writing `input` to its stdin, and waits for
+/// it to finish.
+pub fn run(command: &str, input: Option<&str>) -> Result<ShellOutput> {
+    let mut child = shell_command(command)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to execute command '{command}'"))?;
+
+    // Write from another thread so a command that prints before reading all
+    // of its input cannot deadlock against us.
+    let writer = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => {
+            let input = input.to_string();
+            Some(std::thread::spawn(move || {
+                stdin.write_all(input.as_bytes())
+            }))
+        }
+        _ => None,
+    };
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to wait for command '{command}'"))?;
+    if let Some(writer) = writer {
+        // A command that exits without reading its input closes the pipe,
+        // which is not an error.
+        let _ = writer.join();
+    }
+
+    Ok(ShellOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        status: output.status,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_with_input_and_failure() {
+        let output = run("sort -r | tr a-z A-Z", Some("a\nc\nb\n")).unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout, "C\nB\nA\n");
+
+        let output = run("echo oops >&2; exit 3", None).unwrap();
+        assert!(!output.success());
+        assert_eq!(output.error_message(), "Command exited with code 3: oops");
+    }
+}
@@ -55,7 +55,7 @@ impl TextUtils {
 
         for ch in text.chars() {
             if ch == '\t' {
-                let spaces_needed = tab_size - (col % tab_size);
+                let spaces_needed = Self::tab_width(col, tab_size);
                 result.push_str(&" ".repeat(spaces_needed));
                 col += spaces_needed;
             } else if ch == '\n' {
@@ -79,11 +79,11 @@ impl TextUtils {
                 break;
             }
 
-            if ch == '\t' {
-                col += tab_size - (col % tab_size);
+            col += if ch == '\t' {
+                Self::tab_width(col, tab_size)
             } else {
-                col += Self::char_width(ch);
-            }
+                Self::char_width(ch)
+            };
 
             pos += ch.len_utf8();
         }
@@ -91,6 +91,43 @@ impl TextUtils {
         col
     }
 
+    pub fn char_to_byte(text: &str, char_idx: usize) -> usize {
+        text.char_indices()
+            .nth(char_idx)
+            .map(|(byte_idx, _)| byte_idx)
+            .unwrap_or(text.len())
+    }
+
+    /// Display column of the character at `char_col`, with tabs expanded.
+    pub fn display_column(line: &str, char_col: usize, tab_size: usize) -> usize {
+        Self::column_position(line, Self::char_to_byte(line, char_col), tab_size)
+    }
+
+    /// Columns a tab starting at display column `col` spans. A zero
+    /// `tab_size` is treated as one.
+    pub fn tab_width(col: usize, tab_size: usize) -> usize {
+        let tab_size = tab_size.max(1);
+        tab_size - col % tab_size
+    }
+
+    pub fn char_column(line: &str, display_col: usize, tab_size: usize) -> usize {
+        let mut col = 0;
+
+        for (idx, ch) in line.chars().enumerate() {
+            let width = if ch == '\t' {
+                Self::tab_width(col, tab_size)
+            } else {
+                Self::char_width(ch)
+            };
+            if col + width > display_col {
+                return idx;
+            }
+            col += width;
+        }
+
+        line.chars().count()
+    }
+
     pub fn is_blank_line(line: &str) -> bool {
         line.trim().is_empty()
     }
@@ -108,7 +145,7 @@ impl TextUtils {
                 _ => break,
             }
         }
-        level / tab_size
+        level / tab_size.max(1)
     }
 
     pub fn indent_string(level: usize, tab_size: usize, use_tabs: bool) -> String {